@@ -63,6 +63,7 @@ const statusSchema = zod.union([
 
 export enum EventType {
   ProjectUpdated = "projectUpdated",
+  RefsUpdated = "refsUpdated",
   RequestCreated = "requestCreated",
   RequestQueried = "requestQueried",
   RequestCloned = "requestCloned",
@@ -76,6 +77,22 @@ export interface ProjectUpdated {
   urn: string;
 }
 
+export interface RefChange {
+  ref: string;
+  // `null` if the reference was created or if `oldUnknown` is set.
+  old: string | null;
+  // `true` if the target of the reference before the change is not known.
+  oldUnknown: boolean;
+  new: string | null;
+}
+
+export interface RefsUpdated {
+  type: EventType.RefsUpdated;
+  urn: string;
+  peer: string;
+  changes: RefChange[];
+}
+
 interface RequestCreated {
   type: EventType.RequestCreated;
   urn: string;
@@ -113,6 +130,7 @@ export type RequestEvent =
 
 export type Event =
   | ProjectUpdated
+  | RefsUpdated
   | RequestEvent
   | WaitingRoomTransition
   | { type: EventType.StatusChanged; old: Status; new: Status };
@@ -122,6 +140,19 @@ const eventSchema: zod.Schema<Event> = zod.union([
     type: zod.literal(EventType.ProjectUpdated),
     urn: zod.string(),
   }),
+  zod.object({
+    type: zod.literal(EventType.RefsUpdated),
+    urn: zod.string(),
+    peer: zod.string(),
+    changes: zod.array(
+      zod.object({
+        ref: zod.string(),
+        old: zod.string().nullable(),
+        oldUnknown: zod.boolean(),
+        new: zod.string().nullable(),
+      })
+    ),
+  }),
   zod.object({
     type: zod.literal(EventType.RequestCreated),
     urn: zod.string(),
@@ -31,11 +31,14 @@ const statsSchema: zod.Schema<Stats> = zod.object({
 export interface CommitSummary {
   headers: CommitHeader[];
   stats: Stats;
+  // `true` if there are older commits than the ones in `headers`.
+  hasMore: boolean;
 }
 
 const commitSummarySchema: zod.Schema<CommitSummary> = zod.object({
   headers: zod.array(commitHeaderSchema),
   stats: statsSchema,
+  hasMore: zod.boolean(),
 });
 
 export enum ObjectType {
@@ -147,6 +150,12 @@ interface CommitsGetParams {
   projectUrn: string;
   peerId?: string;
   revision: RevisionSelector;
+  // Only include commits that changed this file or directory.
+  path?: string;
+  // Maximum number of commits to return.
+  limit?: number;
+  // Return the commits after the commit with this SHA.
+  after?: string;
 }
 
 interface CommitGetParams {
@@ -247,6 +256,9 @@ export class Client {
             ...params.revision,
             peerId: params.peerId,
           },
+          path: params.path,
+          limit: params.limit,
+          after: params.after,
         },
         options,
       },
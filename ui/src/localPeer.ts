@@ -13,6 +13,7 @@ import {
   EventType,
   Event,
   ProjectUpdated,
+  RefsUpdated,
   RequestEvent,
   type Status,
   StatusType,
@@ -100,6 +101,15 @@ export const projectEvents: bacon.EventStream<ProjectUpdated> = bacon.filterMap(
   }
 );
 
+export const refsEvents: bacon.EventStream<RefsUpdated> = bacon.filterMap(
+  eventBus,
+  event => {
+    if (event.type === EventType.RefsUpdated) {
+      return event;
+    }
+  }
+);
+
 export const requestEvents: bacon.EventStream<RequestEvent> = bacon.filterMap(
   eventBus,
   event => {
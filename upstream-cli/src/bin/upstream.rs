@@ -16,6 +16,10 @@
 use anyhow::Context;
 use librad::PeerId;
 
+mod monorepo;
+#[cfg(test)]
+mod test_util;
+
 const VERSION: &str = concat!(
     env!("CARGO_PKG_VERSION"),
     "-git",
@@ -31,6 +35,8 @@ fn main() {
         Err(err) => {
             if let Some(program_error) = err.root_cause().downcast_ref::<ProgramError>() {
                 println!("{}", program_error)
+            } else if let Some(patch_error) = err.root_cause().downcast_ref::<PatchError>() {
+                println!("{}", patch_error)
             } else {
                 println!("{:?}", err)
             }
@@ -57,6 +63,10 @@ struct Program {
 
 impl Program {
     fn run(self) -> anyhow::Result<()> {
+        // Make `--lnk-home` apply to the Radicle profile we load in this process.
+        if let Some(lnk_home) = &self.options.lnk_home {
+            std::env::set_var("LNK_HOME", lnk_home);
+        }
         self.command.run(self.options)
     }
 }
@@ -65,21 +75,40 @@ impl Program {
 struct Options {
     #[clap(long, env, global = true)]
     lnk_home: Option<String>,
+    /// URL of the Upstream proxy API
+    #[clap(
+        long,
+        env = "UPSTREAM_PROXY_URL",
+        global = true,
+        default_value = "http://127.0.0.1:17246"
+    )]
+    proxy_url: String,
 }
 
 #[derive(Debug, clap::Subcommand)]
 enum Command {
-    /// Create, update or fetch Upstream patches
+    /// Create, update, review and merge Upstream patches
     Patch {
         #[clap(subcommand)]
         command: PatchCommand,
     },
+
+    /// Fetch updates for the project in the current working copy from its seeds and push your
+    /// branches, tags and events to them.
+    ///
+    /// Requires Upstream to be running.
+    Sync {
+        /// Print the result for every seed as JSON.
+        #[clap(long)]
+        json: bool,
+    },
 }
 
 impl Command {
     fn run(self, options: Options) -> anyhow::Result<()> {
         match self {
             Command::Patch { command: commands } => commands.run(options),
+            Command::Sync { json } => sync_project(options, json),
         }
     }
 }
@@ -90,6 +119,23 @@ struct PatchHandle {
     name: String,
 }
 
+impl PatchHandle {
+    /// Path of the patch relative to the project in the Upstream API.
+    fn api_path(&self) -> String {
+        format!(
+            "patches/{}/{}",
+            self.peer_id,
+            encode_uri_component(&self.name)
+        )
+    }
+}
+
+impl std::fmt::Display for PatchHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.peer_id, self.name)
+    }
+}
+
 impl std::str::FromStr for PatchHandle {
     type Err = String;
 
@@ -142,6 +188,82 @@ enum PatchCommand {
         /// Patch to fetch in the format <Peer ID>/<patch name>
         patch_handle: PatchHandle,
     },
+
+    /// List all patches of the project in the current working copy.
+    List {
+        /// Print the patches as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Show the status, message and comments of a patch.
+    Show {
+        /// Patch to show in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Print the patch as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Fetch a patch and check it out as a new local branch.
+    ///
+    /// The branch is named after the patch unless --branch is given.
+    Checkout {
+        /// Patch to check out in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Name of the branch to create.
+        #[clap(short, long)]
+        branch: Option<String>,
+        /// Print the patch, branch and commit as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Close a patch.
+    ///
+    /// Requires Upstream to be running.
+    Close {
+        /// Patch to close in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Print the updated patch as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Reopen a closed patch.
+    ///
+    /// Requires Upstream to be running.
+    Reopen {
+        /// Patch to reopen in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Print the updated patch as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Merge a patch into your default branch and mark it as merged.
+    ///
+    /// Only delegates of the project can merge patches. Requires Upstream to be running.
+    Merge {
+        /// Patch to merge in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Print the result as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Add a comment to a patch.
+    ///
+    /// Requires Upstream to be running.
+    Comment {
+        /// Patch to comment on in the format <Peer ID>/<patch name>
+        patch_handle: PatchHandle,
+        /// Text of the comment
+        comment: String,
+        /// Print the updated patch as JSON.
+        #[clap(long)]
+        json: bool,
+    },
 }
 
 impl PatchCommand {
@@ -149,32 +271,52 @@ impl PatchCommand {
         match self {
             PatchCommand::Create { message, no_sync } => create_patch(options, message, no_sync),
             PatchCommand::Update { message, no_sync } => update_patch(options, message, no_sync),
-            PatchCommand::Fetch { patch_handle } => fetch_patch(options, patch_handle),
+            PatchCommand::Fetch { patch_handle } => fetch_patch(patch_handle),
+            PatchCommand::List { json } => list_patches(json),
+            PatchCommand::Show { patch_handle, json } => show_patch(patch_handle, json),
+            PatchCommand::Checkout {
+                patch_handle,
+                branch,
+                json,
+            } => checkout_patch(patch_handle, branch, json),
+            PatchCommand::Close { patch_handle, json } => {
+                set_patch_status(options, patch_handle, monorepo::State::Closed, json)
+            },
+            PatchCommand::Reopen { patch_handle, json } => {
+                set_patch_status(options, patch_handle, monorepo::State::Open, json)
+            },
+            PatchCommand::Merge { patch_handle, json } => merge_patch(options, patch_handle, json),
+            PatchCommand::Comment {
+                patch_handle,
+                comment,
+                json,
+            } => comment_patch(options, patch_handle, comment, json),
         }
     }
 }
 
 fn create_patch(options: Options, message: Option<String>, no_sync: bool) -> anyhow::Result<()> {
-    let patch_name = get_current_branch_name().context("failed to get current branch name")?;
-    if let Some(message) = message {
-        create_or_update_patch(&options, &patch_name, Some(message), true, false, no_sync)?;
-    } else {
-        let git_show = std::process::Command::new("git")
-            .arg("show")
-            .arg("--quiet")
-            .arg("HEAD")
-            .arg("--pretty=%s%n%n%b")
-            .output()
-            .context("Could not get latest commit message")?;
-
-        if !git_show.status.success() {
-            anyhow::bail!(ProgramError::new("Failed to get latest commit"));
-        }
+    let working_copy = WorkingCopy::open()?;
+    let patch_name = working_copy.current_branch_name()?;
+    if working_copy.find_patch_tag(&patch_name)?.is_some() {
+        anyhow::bail!(PatchError::TagExists { name: patch_name });
+    }
 
-        let last_commit_message = String::from_utf8(git_show.stdout)
-            .expect("Could not convert `git show` stdout to string");
+    let message = match message {
+        Some(message) => message,
+        None => {
+            let head_commit = working_copy
+                .repo
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .map_err(|_| ProgramError::new("Failed to get latest commit"))?;
+            let last_commit_message = format!(
+                "{}\n\n{}",
+                head_commit.summary().unwrap_or_default(),
+                head_commit.body().unwrap_or_default()
+            );
 
-        let patch_help_message = "# Please describe your patch.
+            let patch_help_message = "# Please describe your patch.
 #
 # We have pre-filled the patch title and description with information from the
 # latest commit on this branch. You can edit it to your liking. The first line
@@ -183,126 +325,926 @@ fn create_patch(options: Options, message: Option<String>, no_sync: bool) -> any
 #
 # Any lines starting with '#' will be ignored.";
 
-        let message = format!("{}{}", last_commit_message, patch_help_message);
-        create_or_update_patch(&options, &patch_name, Some(message), true, true, no_sync)?;
+            working_copy.edit_message(&format!(
+                "{}\n{}",
+                last_commit_message.trim_end(),
+                patch_help_message
+            ))?
+        },
     };
+    working_copy.create_patch_tag(&patch_name, &message, false)?;
+    publish_patch(&options, &working_copy, &patch_name, no_sync)?;
     println!("Created patch {}", patch_name);
 
     Ok(())
 }
 
 fn update_patch(options: Options, message: Option<String>, no_sync: bool) -> anyhow::Result<()> {
-    let patch_name = get_current_branch_name().context("failed to get current branch name")?;
-    create_or_update_patch(&options, &patch_name, message, true, false, no_sync)?;
+    let working_copy = WorkingCopy::open()?;
+    let patch_name = working_copy.current_branch_name()?;
+    let current_message = match working_copy.find_patch_tag(&patch_name)? {
+        Some(tag) => tag.message().unwrap_or_default().to_string(),
+        None => anyhow::bail!(PatchError::PatchNotFound { name: patch_name }),
+    };
+
+    let message = match message {
+        Some(message) => message,
+        None => working_copy.edit_message(&format!(
+            "{}\n# Please update the description of your patch.
+#
+# Any lines starting with '#' will be ignored.",
+            current_message.trim_end()
+        ))?,
+    };
+    working_copy.create_patch_tag(&patch_name, &message, true)?;
+    publish_patch(&options, &working_copy, &patch_name, no_sync)?;
     println!("Updated patch {}", patch_name);
 
     Ok(())
 }
 
-fn fetch_patch(options: Options, patch_handle: PatchHandle) -> anyhow::Result<()> {
-    let lnk_home_env = options.lnk_home.as_ref().map(|value| ("LNK_HOME", value));
+fn fetch_patch(patch_handle: PatchHandle) -> anyhow::Result<()> {
+    let working_copy = WorkingCopy::open()?;
+    let project_repo = ProjectRepo::open(&working_copy)?;
+    let patch = project_repo.patch(&patch_handle)?;
+    working_copy.fetch_patch_tag(&project_repo, &patch_handle)?;
+    println!("Fetched patch {} at {}", patch_handle, patch.commit);
+    Ok(())
+}
 
-    let remote_patch_ref = format!(
-        "remotes/{}/tags/radicle-patch/{}",
-        patch_handle.peer_id, patch_handle.name
-    );
-    let local_patch_ref = format!(
-        "tags/radicle-patch/{}/{}",
-        patch_handle.peer_id, patch_handle.name
+fn list_patches(json: bool) -> anyhow::Result<()> {
+    let project_repo = ProjectRepo::open(&WorkingCopy::open()?)?;
+    let patches = project_repo.monorepo.patches(&project_repo.project)?;
+    if json {
+        return print_json(&patches);
+    }
+
+    if patches.is_empty() {
+        println!("No patches");
+    }
+    for patch in patches {
+        println!(
+            "{}/{}\t{}\t{}",
+            patch.peer.peer_id,
+            patch.id,
+            patch.status.current,
+            patch.title()
+        );
+    }
+    Ok(())
+}
+
+fn show_patch(patch_handle: PatchHandle, json: bool) -> anyhow::Result<()> {
+    let project_repo = ProjectRepo::open(&WorkingCopy::open()?)?;
+    let patch = project_repo.patch(&patch_handle)?;
+    if json {
+        return print_json(&patch);
+    }
+
+    println!("Patch {}/{}", patch.peer.peer_id, patch.id);
+    println!("Status: {}", patch.status.current);
+    println!("Commit: {}", patch.commit);
+    if let Some(merge_base) = &patch.merge_base {
+        println!("Merge base: {}", merge_base);
+    }
+    if let Some(message) = &patch.message {
+        println!();
+        for line in message.lines() {
+            println!("    {}", line);
+        }
+    }
+    for comment in &patch.comments {
+        println!();
+        println!("Comment by {}:", comment.peer_id);
+        for line in comment.comment.lines() {
+            println!("    {}", line);
+        }
+    }
+    Ok(())
+}
+
+fn checkout_patch(
+    patch_handle: PatchHandle,
+    branch: Option<String>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let working_copy = WorkingCopy::open()?;
+    let project_repo = ProjectRepo::open(&working_copy)?;
+    let branch = branch.unwrap_or_else(|| patch_handle.name.clone());
+    let commit = working_copy.fetch_patch_tag(&project_repo, &patch_handle)?;
+    working_copy.checkout_new_branch(&branch, commit)?;
+    if json {
+        return print_json(&serde_json::json!({
+            "patch": patch_handle.to_string(),
+            "branch": branch,
+            "commit": commit.to_string(),
+        }));
+    }
+
+    println!("Checked out patch on branch {}", branch);
+    Ok(())
+}
+
+fn set_patch_status(
+    options: Options,
+    patch_handle: PatchHandle,
+    status: monorepo::State,
+    json: bool,
+) -> anyhow::Result<()> {
+    let project_repo = ProjectRepo::open(&WorkingCopy::open()?)?;
+    project_repo.patch(&patch_handle)?;
+    let proxy = ProxyClient::for_current_project(&options)?;
+    proxy
+        .publish_patch_event(&patch_handle, &monorepo::Event::SetStatus { status })
+        .map_err(ProgramError::from)?;
+    if json {
+        return print_json(&project_repo.patch(&patch_handle)?);
+    }
+    println!("Patch {} is {}", patch_handle, status);
+    Ok(())
+}
+
+fn merge_patch(options: Options, patch_handle: PatchHandle, json: bool) -> anyhow::Result<()> {
+    let proxy = ProxyClient::for_current_project(&options)?;
+    let path = format!("{}/merge", patch_handle.api_path());
+    let outcome = match proxy.request("POST", &path, None) {
+        Ok(response) => response
+            .into_json::<serde_json::Value>()
+            .context("invalid response from Upstream")?,
+        Err(err) if err.variant.as_deref() == Some("MERGE_CONFLICT") => {
+            let paths = err
+                .details
+                .as_deref()
+                .and_then(|details| serde_json::from_str::<Vec<String>>(details).ok())
+                .unwrap_or_default();
+            let mut message = format!("Patch {} conflicts with your default branch", patch_handle);
+            for path in paths {
+                message.push_str(&format!("\n    {}", path));
+            }
+            anyhow::bail!(ProgramError::new(&message));
+        },
+        Err(err) => return Err(ProgramError::from(err).into()),
+    };
+    if json {
+        return print_json(&outcome);
+    }
+
+    let head = outcome
+        .get("head")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default();
+    println!(
+        "Merged patch {} into your default branch at {}",
+        patch_handle, head
     );
-    let exit_status = std::process::Command::new("git")
-        .envs(lnk_home_env)
-        .arg("fetch")
-        .arg("rad")
-        .arg("--force")
-        .arg(format!("{}:{}", remote_patch_ref, local_patch_ref))
-        .status()
-        .context("failed to spawn command")?;
-    if !exit_status.success() {
-        anyhow::bail!(ProgramError::new("Failed to push git tag"));
+    Ok(())
+}
+
+fn comment_patch(
+    options: Options,
+    patch_handle: PatchHandle,
+    comment: String,
+    json: bool,
+) -> anyhow::Result<()> {
+    let project_repo = ProjectRepo::open(&WorkingCopy::open()?)?;
+    project_repo.patch(&patch_handle)?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .context("system time is before the Unix epoch")?
+        .as_millis();
+    let timestamp = u64::try_from(timestamp).context("invalid system time")?;
+    let proxy = ProxyClient::for_current_project(&options)?;
+    proxy
+        .publish_patch_event(
+            &patch_handle,
+            &monorepo::Event::AddComment { comment, timestamp },
+        )
+        .map_err(ProgramError::from)?;
+    if json {
+        return print_json(&project_repo.patch(&patch_handle)?);
     }
+    println!("Added comment to patch {}", patch_handle);
+    Ok(())
+}
 
+fn sync_project(options: Options, json: bool) -> anyhow::Result<()> {
+    let proxy = ProxyClient::for_current_project(&options)?;
+    let results = proxy.sync().map_err(ProgramError::from)?;
+    if json {
+        return print_json(&results);
+    }
+    print_sync_results(results)
+}
+
+/// Print the result of syncing with every seed. Fails if no seed provided the project.
+fn print_sync_results(results: serde_json::Value) -> anyhow::Result<()> {
+    let results = serde_json::from_value::<Vec<SeedSyncResult>>(results)
+        .context("invalid sync result returned by Upstream")?;
+    if results.is_empty() {
+        anyhow::bail!(ProgramError::new("No seeds configured for the project"));
+    }
+
+    let mut synced = false;
+    for result in results {
+        match result.fetch.status.as_str() {
+            "updated" => {
+                synced = true;
+                let refs = result.fetch.refs.unwrap_or_default();
+                println!("{}: fetched {} updated refs", result.seed, refs.len());
+                for change in refs {
+                    println!(
+                        "    {} {}..{}",
+                        change.name,
+                        change.old.as_deref().unwrap_or("(new)"),
+                        change.new.as_deref().unwrap_or("(deleted)")
+                    );
+                }
+            },
+            "upToDate" => {
+                synced = true;
+                println!("{}: up to date", result.seed);
+            },
+            "notFound" => println!("{}: project not found", result.seed),
+            _ => println!(
+                "{}: fetch failed: {}",
+                result.seed,
+                result.fetch.message.unwrap_or_default()
+            ),
+        }
+        if let Some(push) = result.push {
+            match push.status.as_str() {
+                "pushed" => println!("{}: pushed", result.seed),
+                "rejected" => {
+                    println!("{}: push rejected", result.seed);
+                    for rejected in push.refs.unwrap_or_default() {
+                        println!("    {} ({})", rejected.name, rejected.reason);
+                    }
+                },
+                _ => println!(
+                    "{}: push failed: {}",
+                    result.seed,
+                    push.message.unwrap_or_default()
+                ),
+            }
+        }
+    }
+
+    if !synced {
+        anyhow::bail!(ProgramError::new("Failed to sync data with any seed"));
+    }
     Ok(())
 }
 
-fn create_or_update_patch(
+fn print_json(value: &impl serde::Serialize) -> anyhow::Result<()> {
+    let output = serde_json::to_string_pretty(value).context("failed to serialize JSON")?;
+    println!("{}", output);
+    Ok(())
+}
+
+/// Push the patch tag to the monorepo and, unless `no_sync` is set, to the project’s seed.
+fn publish_patch(
     options: &Options,
+    working_copy: &WorkingCopy,
     patch_name: &str,
-    message: Option<String>,
-    force: bool,
-    edit: bool,
     no_sync: bool,
 ) -> anyhow::Result<()> {
-    let patch_tag_name = format!("radicle-patch/{}", patch_name);
-
-    let lnk_home_env = options.lnk_home.as_ref().map(|value| ("LNK_HOME", value));
-    let force_opt = if force { Some("--force") } else { None };
-    let edit_opt = if edit { Some("--edit") } else { None };
-    let message_opts = if let Some(message) = message {
-        vec!["--message".to_string(), message]
-    } else {
-        vec![]
+    let profile = lnk_profile::get(None, None)
+        .context("failed to load Radicle profile")?
+        .ok_or(PatchError::MissingProfile)?;
+    let runtime = tokio::runtime::Runtime::new().context("failed to start async runtime")?;
+    let signer = runtime.block_on(ssh_agent_signer(profile.paths()))?;
+    let settings = librad::git::local::transport::Settings {
+        paths: profile.paths().clone(),
+        signer,
     };
 
-    let exit_status = std::process::Command::new("git")
-        .arg("tag")
-        .arg("--annotate")
-        .args(force_opt)
-        .args(edit_opt)
-        .args(message_opts)
-        .arg(&patch_tag_name)
-        .status()
-        .context("failed to spawn git command")?;
-    if !exit_status.success() {
-        anyhow::bail!(ProgramError::new("Failed to create git tag"));
-    }
-
-    let exit_status = std::process::Command::new("git")
-        .envs(lnk_home_env)
-        .arg("push")
-        .args(force_opt)
-        .arg("rad")
-        .arg("tag")
-        .arg(patch_tag_name)
-        .status()
-        .context("failed to spawn git command")?;
-    if !exit_status.success() {
-        anyhow::bail!(ProgramError::new("Failed to push git tag"));
-    }
+    let tag_ref_name = patch_tag_ref_name(patch_name);
+    runtime.block_on({
+        let repo_path = working_copy.repo.path().to_owned();
+        let tag_ref_name = tag_ref_name.clone();
+        async move {
+            tokio::task::spawn_blocking(move || {
+                push_to_rad_remote(&repo_path, settings, &tag_ref_name)
+            })
+            .await
+            .context("failed to join push task")?
+        }
+    })?;
 
     if no_sync {
         return Ok(());
     }
 
-    let exit_status = std::process::Command::new("rad")
-        .envs(lnk_home_env)
-        .arg("sync")
-        .status()
-        .context("failed to spawn rad command")?;
-    if !exit_status.success() {
-        anyhow::bail!(ProgramError::new("Failed to sync data with seed"));
+    let project_urn = working_copy.project_urn()?;
+    let proxy = ProxyClient {
+        base_url: options.proxy_url.trim_end_matches('/').to_string(),
+        project_urn: project_urn.clone(),
+    };
+    match proxy.seed() {
+        Ok(Some(seed_url)) => push_to_seed(
+            profile.paths().git_dir(),
+            &project_urn,
+            peer_id(profile.paths())?,
+            &seed_url,
+            &tag_ref_name,
+        ),
+        Ok(None) => {
+            println!("No seed known for the project. Add a seed in Upstream to publish the patch");
+            Ok(())
+        },
+        // When Upstream starts it pushes our heads, tags and signed refs of every project to the
+        // seeds that replicate the project. This includes the patch tag.
+        Err(err) if err.unreachable => {
+            println!(
+                "Upstream is not running. The patch will be pushed to the project’s seeds when Upstream starts"
+            );
+            Ok(())
+        },
+        Err(err) => Err(ProgramError::from(err).into()),
     }
+}
+
+/// Push the reference `ref_name` from the working copy at `repo_path` to the monorepo using the
+/// `rad` remote.
+fn push_to_rad_remote(
+    repo_path: &std::path::Path,
+    settings: librad::git::local::transport::Settings,
+    ref_name: &str,
+) -> anyhow::Result<()> {
+    use librad::git::types::{remote::LocalPushspec, Force, Remote};
 
+    let repo = git2::Repository::open(repo_path).context("failed to open working copy")?;
+    let mut remote =
+        Remote::<librad::git::local::url::LocalUrl>::find(&repo, librad::reflike!("rad"))
+            .context("failed to load `rad` remote")?
+            .ok_or(PatchError::MissingRadRemote)?;
+    let pattern =
+        librad::git_ext::RefspecPattern::try_from(ref_name).context("invalid patch tag name")?;
+    remote
+        .push(
+            settings,
+            &repo,
+            LocalPushspec::Matching {
+                pattern,
+                force: Force::True,
+            },
+        )
+        .map_err(|err| PatchError::Push {
+            message: err.to_string(),
+        })?;
     Ok(())
 }
 
-fn get_current_branch_name() -> anyhow::Result<String> {
-    let output = std::process::Command::new("git")
-        .arg("branch")
-        .arg("--show-current")
-        .stderr(std::process::Stdio::inherit())
-        .output()
-        .context("failed to spawn command")?;
-    if !output.status.success() {
-        anyhow::bail!("Command failed with status {:?}", output.status)
+/// Push the patch tag and the signed refs of the project from the monorepo at `monorepo_path` to
+/// `seed_url`.
+fn push_to_seed(
+    monorepo_path: &std::path::Path,
+    project_urn: &librad::git::Urn,
+    peer_id: PeerId,
+    seed_url: &str,
+    tag_ref_name: &str,
+) -> anyhow::Result<()> {
+    let id = project_urn.encode_id();
+    let project_seed_url = format!("{}/{}", seed_url.trim_end_matches('/'), id);
+    let refspecs = [tag_ref_name, "refs/rad/signed_refs"]
+        .iter()
+        .map(|ref_name| seed_push_refspec(&id, peer_id, ref_name))
+        .collect::<Vec<_>>();
+    let sync_error = |message: String| PatchError::Sync {
+        seed: project_seed_url.clone(),
+        message,
+    };
+
+    let repo = git2::Repository::open_bare(monorepo_path).context("failed to open monorepo")?;
+    let mut remote = repo
+        .remote_anonymous(&project_seed_url)
+        .map_err(|err| sync_error(err.message().to_string()))?;
+    let mut rejected = vec![];
+    {
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.push_update_reference(|ref_name, status| {
+            if let Some(status) = status {
+                rejected.push(format!("{} ({})", ref_name, status));
+            }
+            Ok(())
+        });
+        let mut push_options = git2::PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        remote
+            .push(&refspecs, Some(&mut push_options))
+            .map_err(|err| sync_error(err.message().to_string()))?;
+    }
+    if !rejected.is_empty() {
+        anyhow::bail!(sync_error(format!("rejected {}", rejected.join(", "))));
+    }
+    Ok(())
+}
+
+/// Refspec that pushes `ref_name` of the project namespace `namespace` to the remote namespace of
+/// `peer_id` on a seed.
+fn seed_push_refspec(namespace: &str, peer_id: PeerId, ref_name: &str) -> String {
+    let ref_name = ref_name.strip_prefix("refs/").unwrap_or(ref_name);
+    format!(
+        "+refs/namespaces/{}/refs/{}:refs/remotes/{}/{}",
+        namespace, ref_name, peer_id, ref_name
+    )
+}
+
+/// Returns the peer ID of the local Radicle identity.
+fn peer_id(paths: &librad::paths::Paths) -> anyhow::Result<PeerId> {
+    let storage =
+        librad::git::storage::ReadOnly::open(paths).context("failed to open Radicle monorepo")?;
+    Ok(*storage.peer_id())
+}
+
+/// Get a signer for the local Radicle key from ssh-agent.
+async fn ssh_agent_signer(
+    paths: &librad::paths::Paths,
+) -> anyhow::Result<link_crypto::BoxedSigner> {
+    let peer_id = peer_id(paths)?;
+    let pk = (*peer_id.as_public_key()).into();
+    let agent = radicle_keystore::sign::SshAgent::new(pk);
+    let keys = radicle_keystore::sign::ssh::list_keys::<tokio::net::UnixStream>(&agent)
+        .await
+        .map_err(|_| PatchError::SignerUnavailable)?;
+    if !keys.contains(&pk) {
+        anyhow::bail!(PatchError::SignerUnavailable);
+    }
+    let signer = agent
+        .connect::<tokio::net::UnixStream>()
+        .await
+        .map_err(|_| PatchError::SignerUnavailable)?;
+    Ok(link_crypto::SomeSigner {
+        signer: std::sync::Arc::new(signer),
+    }
+    .into())
+}
+
+fn patch_tag_ref_name(patch_name: &str) -> String {
+    format!("refs/tags/radicle-patch/{}", patch_name)
+}
+
+/// Git working copy of a Radicle project.
+struct WorkingCopy {
+    repo: git2::Repository,
+}
+
+impl WorkingCopy {
+    /// Open the working copy that contains the current directory.
+    fn open() -> anyhow::Result<Self> {
+        let repo = git2::Repository::open_from_env().map_err(|_| PatchError::NotInWorkingCopy)?;
+        Ok(Self { repo })
+    }
+
+    /// Returns the URN of the project. The URN is obtained from the URL of the `rad` remote.
+    fn project_urn(&self) -> anyhow::Result<librad::git::Urn> {
+        let remote = self
+            .repo
+            .find_remote("rad")
+            .map_err(|_| PatchError::MissingRadRemote)?;
+        let url = remote.url().ok_or(PatchError::MissingRadRemote)?;
+        let id = url
+            .strip_prefix("rad://")
+            .map(|id| id.trim_end_matches('/').trim_end_matches(".git"))
+            .ok_or_else(|| ProgramError::new(&format!("Invalid `rad` remote URL {}", url)))?;
+        let urn = format!("rad:git:{}", id)
+            .parse::<librad::git::Urn>()
+            .map_err(|err| {
+                ProgramError::new(&format!("Invalid `rad` remote URL {}: {}", url, err))
+            })?;
+        Ok(urn)
+    }
+
+    /// Returns the name of the branch that is checked out.
+    fn current_branch_name(&self) -> anyhow::Result<String> {
+        if self
+            .repo
+            .head_detached()
+            .context("failed to get working copy HEAD")?
+        {
+            anyhow::bail!(PatchError::DetachedHead);
+        }
+        let head = self
+            .repo
+            .head()
+            .context("failed to get working copy HEAD")?;
+        let branch_name = head
+            .shorthand()
+            .ok_or_else(|| anyhow::anyhow!("branch name is not valid UTF-8"))?;
+        Ok(branch_name.to_string())
+    }
+
+    fn find_patch_tag(&self, patch_name: &str) -> anyhow::Result<Option<git2::Tag<'_>>> {
+        match self.repo.find_reference(&patch_tag_ref_name(patch_name)) {
+            Ok(reference) => Ok(reference.peel_to_tag().ok()),
+            Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(err) => Err(err).context("failed to find patch tag"),
+        }
+    }
+
+    /// Create an annotated patch tag pointing to `HEAD`. Replaces an existing tag if `force` is
+    /// `true`.
+    fn create_patch_tag(&self, patch_name: &str, message: &str, force: bool) -> anyhow::Result<()> {
+        let head = self
+            .repo
+            .head()
+            .and_then(|head| head.peel(git2::ObjectType::Commit))
+            .context("failed to get HEAD commit")?;
+        let signature = self
+            .repo
+            .signature()
+            .map_err(|_| PatchError::MissingGitIdentity)?;
+        let tag_name = format!("radicle-patch/{}", patch_name);
+        match self.repo.tag(&tag_name, &head, &signature, message, force) {
+            Ok(_) => Ok(()),
+            Err(err) if err.code() == git2::ErrorCode::Exists => {
+                anyhow::bail!(PatchError::TagExists {
+                    name: patch_name.to_string()
+                })
+            },
+            Err(err) => Err(err).context("failed to create patch tag"),
+        }
+    }
+
+    /// Fetch the tag of a patch from the monorepo to `refs/tags/radicle-patch/<PATCH_HANDLE>` and
+    /// return the patch head commit.
+    fn fetch_patch_tag(
+        &self,
+        project_repo: &ProjectRepo,
+        patch_handle: &PatchHandle,
+    ) -> anyhow::Result<git2::Oid> {
+        let source_ref_name = project_repo.monorepo.patch_tag_ref_name(
+            &project_repo.project.urn,
+            patch_handle.peer_id,
+            &patch_handle.name,
+        );
+        let target_ref_name = patch_tag_ref_name(&patch_handle.to_string());
+        let monorepo_path = project_repo
+            .monorepo
+            .path()
+            .to_str()
+            .context("monorepo path is not valid UTF-8")?;
+        let mut remote = self
+            .repo
+            .remote_anonymous(monorepo_path)
+            .context("failed to create monorepo remote")?;
+        remote
+            .fetch(
+                &[format!("+{}:{}", source_ref_name, target_ref_name)],
+                None,
+                None,
+            )
+            .map_err(|err| {
+                ProgramError::new(&format!("Failed to fetch patch {}: {}", patch_handle, err))
+            })?;
+
+        let commit = self
+            .repo
+            .find_reference(&target_ref_name)
+            .and_then(|reference| reference.peel_to_commit())
+            .map_err(|_| PatchError::UnknownPatch {
+                handle: patch_handle.to_string(),
+            })?;
+        Ok(commit.id())
+    }
+
+    /// Create the branch `name` pointing to `commit` and check it out.
+    fn checkout_new_branch(&self, name: &str, commit: git2::Oid) -> anyhow::Result<()> {
+        if self.repo.find_branch(name, git2::BranchType::Local).is_ok() {
+            anyhow::bail!(PatchError::BranchExists {
+                name: name.to_string()
+            });
+        }
+        let commit = self
+            .repo
+            .find_commit(commit)
+            .context("failed to find patch commit")?;
+        self.repo
+            .checkout_tree(
+                commit.as_object(),
+                Some(git2::build::CheckoutBuilder::new().safe()),
+            )
+            .map_err(|err| {
+                ProgramError::new(&format!("Failed to check out patch: {}", err.message()))
+            })?;
+        let branch = self
+            .repo
+            .branch(name, &commit, false)
+            .context("failed to create branch")?;
+        let branch_ref_name = branch
+            .get()
+            .name()
+            .context("branch name is not valid UTF-8")?;
+        self.repo
+            .set_head(branch_ref_name)
+            .context("failed to update HEAD")?;
+        Ok(())
+    }
+
+    /// Open the user’s editor to edit `template` and return the result with comment lines
+    /// removed.
+    fn edit_message(&self, template: &str) -> anyhow::Result<String> {
+        let path = self.repo.path().join("RADICLE_PATCH_EDITMSG");
+        std::fs::write(&path, template).context("failed to write patch message file")?;
+
+        let editor = std::env::var("GIT_EDITOR")
+            .ok()
+            .or_else(|| {
+                self.repo
+                    .config()
+                    .and_then(|config| config.get_string("core.editor"))
+                    .ok()
+            })
+            .or_else(|| std::env::var("VISUAL").ok())
+            .or_else(|| std::env::var("EDITOR").ok())
+            .unwrap_or_else(|| "vi".to_string());
+        let exit_status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(&path)
+            .status()
+            .context("failed to spawn editor")?;
+        if !exit_status.success() {
+            anyhow::bail!(ProgramError::new("There was a problem with the editor"));
+        }
+
+        let edited = std::fs::read_to_string(&path).context("failed to read patch message file")?;
+        let message = edited
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let message = message.trim();
+        if message.is_empty() {
+            anyhow::bail!(PatchError::EmptyMessage);
+        }
+        Ok(format!("{}\n", message))
+    }
+}
+
+/// The monorepo of the local Radicle profile and the project of a working copy.
+struct ProjectRepo {
+    monorepo: monorepo::Monorepo,
+    project: monorepo::ProjectInfo,
+}
+
+impl ProjectRepo {
+    fn open(working_copy: &WorkingCopy) -> anyhow::Result<Self> {
+        let project_urn = working_copy.project_urn()?;
+        let profile = lnk_profile::get(None, None)
+            .context("failed to load Radicle profile")?
+            .ok_or(PatchError::MissingProfile)?;
+        let storage = librad::git::storage::ReadOnly::open(profile.paths())
+            .context("failed to open Radicle monorepo")?;
+        let peer_id = *storage.peer_id();
+        let monorepo = monorepo::Monorepo::open(profile.paths(), peer_id)?;
+        let project = monorepo::ProjectInfo::load(&storage, &monorepo, &project_urn)?;
+        Ok(Self { monorepo, project })
     }
 
-    let branch_name = std::str::from_utf8(&output.stdout)
-        .context("invalid UTF-8 output from command")?
-        .lines()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("empty command output"))?;
-    Ok(branch_name.to_string())
+    /// Get the patch identified by `patch_handle`. Fails with [`PatchError::UnknownPatch`] if the
+    /// patch does not exist.
+    fn patch(&self, patch_handle: &PatchHandle) -> anyhow::Result<monorepo::Patch> {
+        match self
+            .monorepo
+            .patch(&self.project, patch_handle.peer_id, &patch_handle.name)?
+        {
+            Some(patch) => Ok(patch),
+            None => anyhow::bail!(PatchError::UnknownPatch {
+                handle: patch_handle.to_string()
+            }),
+        }
+    }
+}
+
+/// Errors with Git working copies and patch tags that are shown to the user.
+#[derive(Debug, thiserror::Error)]
+enum PatchError {
+    #[error("Not in a Git working copy")]
+    NotInWorkingCopy,
+    #[error("The working copy has no `rad` remote. Is this a Radicle project?")]
+    MissingRadRemote,
+    #[error("HEAD is detached. Check out the branch you want to create a patch from")]
+    DetachedHead,
+    #[error("Patch {name} already exists. Use `upstream patch update` to update it")]
+    TagExists { name: String },
+    #[error("Patch {name} does not exist. Use `upstream patch create` to create it")]
+    PatchNotFound { name: String },
+    #[error("Aborting patch due to empty patch message")]
+    EmptyMessage,
+    #[error("Git user name and email are not configured")]
+    MissingGitIdentity,
+    #[error("No Radicle identity found. Create one with Upstream first")]
+    MissingProfile,
+    #[error("Radicle key not available in ssh-agent. Unlock Upstream or run `rad auth`")]
+    SignerUnavailable,
+    #[error("Failed to push patch to the `rad` remote: {message}")]
+    Push { message: String },
+    #[error("Failed to sync patch with seed {seed}: {message}")]
+    Sync { seed: String, message: String },
+    #[error("Patch {handle} not found. Sync the project to fetch the latest patches")]
+    UnknownPatch { handle: String },
+    #[error("Branch {name} already exists. Use --branch to choose a different name")]
+    BranchExists { name: String },
+}
+
+/// Outcome of syncing the project with a seed as returned by the Upstream API.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeedSyncResult {
+    seed: String,
+    fetch: SeedFetchStatus,
+    push: Option<SeedPushStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeedFetchStatus {
+    status: String,
+    message: Option<String>,
+    refs: Option<Vec<RefChange>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SeedPushStatus {
+    status: String,
+    message: Option<String>,
+    refs: Option<Vec<RejectedRef>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefChange {
+    #[serde(rename = "ref")]
+    name: String,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RejectedRef {
+    #[serde(rename = "ref")]
+    name: String,
+    reason: String,
+}
+
+/// Seed of a project as returned by the Upstream API.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectSeed {
+    url: String,
+    pinned: bool,
+}
+
+/// Returns the URL of the pinned seed or, if no seed is pinned, of the first seed.
+fn select_seed(seeds: Vec<ProjectSeed>) -> Option<String> {
+    let first = seeds.first().map(|seed| seed.url.clone());
+    seeds
+        .into_iter()
+        .find(|seed| seed.pinned)
+        .map(|seed| seed.url)
+        .or(first)
+}
+
+/// Client for the API of a running Upstream proxy scoped to a project.
+struct ProxyClient {
+    base_url: String,
+    project_urn: librad::git::Urn,
+}
+
+impl ProxyClient {
+    /// Create a client for the project of the working copy in the current directory.
+    fn for_current_project(options: &Options) -> anyhow::Result<Self> {
+        Ok(Self {
+            base_url: options.proxy_url.trim_end_matches('/').to_string(),
+            project_urn: WorkingCopy::open()?.project_urn()?,
+        })
+    }
+
+    /// Returns the seed that patches are pushed to: the seed the user pinned for the project or
+    /// the first seed Upstream knows for the project. Returns `None` if no seed is known.
+    fn seed(&self) -> Result<Option<String>, ApiError> {
+        let seeds = self
+            .request("GET", "seeds", None)?
+            .into_json::<Vec<ProjectSeed>>()
+            .map_err(|err| ApiError {
+                variant: None,
+                message: "Invalid response from Upstream".to_string(),
+                details: Some(err.to_string()),
+                unreachable: false,
+            })?;
+        Ok(select_seed(seeds))
+    }
+
+    /// Publish `event` to the event log of the patch. Upstream writes the event to our event log
+    /// and pushes it to the seeds of the project.
+    fn publish_patch_event(
+        &self,
+        patch_handle: &PatchHandle,
+        event: &monorepo::Event,
+    ) -> Result<(), ApiError> {
+        let topic = monorepo::event_topic(patch_handle.peer_id, &patch_handle.name);
+        let event = serde_json::to_value(event).map_err(|err| ApiError {
+            variant: None,
+            message: "Failed to serialize event".to_string(),
+            details: Some(err.to_string()),
+            unreachable: false,
+        })?;
+        self.request(
+            "PUT",
+            &format!("events/{}", encode_uri_component(&topic)),
+            Some(event),
+        )?;
+        Ok(())
+    }
+
+    /// Fetch from and push to the seeds of the project and return the result for every seed.
+    fn sync(&self) -> Result<serde_json::Value, ApiError> {
+        self.request("POST", "sync", None)?
+            .into_json()
+            .map_err(|err| ApiError {
+                variant: None,
+                message: "Invalid response from Upstream".to_string(),
+                details: Some(err.to_string()),
+                unreachable: false,
+            })
+    }
+
+    /// Send a request for `path` relative to the project with an optional JSON body.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<ureq::Response, ApiError> {
+        let url = format!(
+            "{}/v1/projects/{}/{}",
+            self.base_url, self.project_urn, path
+        );
+        let request = ureq::request(method, &url);
+        let result = match body {
+            Some(body) => request.send_json(body),
+            None => request.call(),
+        };
+        match result {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(status, response)) => Err(response
+                .into_json::<ApiError>()
+                .unwrap_or_else(|_| ApiError {
+                    variant: None,
+                    message: format!("Upstream responded with status {}", status),
+                    details: None,
+                    unreachable: false,
+                })),
+            Err(ureq::Error::Transport(transport)) => Err(ApiError {
+                variant: None,
+                message: format!(
+                    "Failed to connect to Upstream at {}. Is Upstream running?",
+                    self.base_url
+                ),
+                details: Some(transport.to_string()),
+                unreachable: true,
+            }),
+        }
+    }
+}
+
+/// Error returned by the Upstream API.
+#[derive(Debug, serde::Deserialize)]
+struct ApiError {
+    variant: Option<String>,
+    message: String,
+    details: Option<String>,
+    /// `true` if we could not connect to Upstream.
+    #[serde(skip)]
+    unreachable: bool,
+}
+
+impl From<ApiError> for ProgramError {
+    fn from(err: ApiError) -> Self {
+        match err.details {
+            Some(details) => ProgramError::new(&format!("{} ({})", err.message, details)),
+            None => ProgramError::new(&err.message),
+        }
+    }
+}
+
+/// Percent-encode all characters of `value` except unreserved characters so that it can be used
+/// as a single URL path segment.
+fn encode_uri_component(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
 }
 
 /// Return a `ProgramError` when you want to show an error message to the user without displaying
@@ -331,3 +1273,231 @@ impl std::fmt::Display for ProgramError {
         write!(f, "{}", self.message)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::commit;
+    use pretty_assertions::assert_eq;
+
+    /// Create a working copy with a single commit on `main`.
+    fn working_copy() -> (tempfile::TempDir, WorkingCopy) {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            tmp.path(),
+            git2::RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Alice").unwrap();
+        config.set_str("user.email", "alice@example.com").unwrap();
+        commit(&repo, Some("HEAD"), "initial", &[]);
+        (tmp, WorkingCopy { repo })
+    }
+
+    fn project_urn() -> librad::git::Urn {
+        librad::git::Urn::new(
+            git2::Oid::hash_object(git2::ObjectType::Blob, b"project")
+                .unwrap()
+                .into(),
+        )
+    }
+
+    fn patch_error(err: &anyhow::Error) -> &PatchError {
+        err.downcast_ref::<PatchError>()
+            .unwrap_or_else(|| panic!("unexpected error {:?}", err))
+    }
+
+    #[test]
+    fn parse_patch_handle() {
+        let peer_id = PeerId::from(link_crypto::SecretKey::new());
+        let handle = format!("{}/feature/foo", peer_id)
+            .parse::<PatchHandle>()
+            .unwrap();
+        assert_eq!(handle.peer_id, peer_id);
+        assert_eq!(handle.name, "feature/foo");
+        assert_eq!(handle.to_string(), format!("{}/feature/foo", peer_id));
+        assert_eq!(
+            handle.api_path(),
+            format!("patches/{}/feature%2Ffoo", peer_id)
+        );
+
+        assert!("feature".parse::<PatchHandle>().is_err());
+        assert!("invalid/feature".parse::<PatchHandle>().is_err());
+    }
+
+    #[test]
+    fn project_urn_from_rad_remote() {
+        let (_tmp, working_copy) = working_copy();
+        let err = working_copy.project_urn().unwrap_err();
+        assert!(matches!(patch_error(&err), PatchError::MissingRadRemote));
+
+        let urn = project_urn();
+        working_copy
+            .repo
+            .remote("rad", &format!("rad://{}.git", urn.encode_id()))
+            .unwrap();
+        assert_eq!(working_copy.project_urn().unwrap(), urn);
+    }
+
+    #[test]
+    fn current_branch_name() {
+        let (_tmp, working_copy) = working_copy();
+        assert_eq!(working_copy.current_branch_name().unwrap(), "main");
+
+        let head = working_copy.repo.refname_to_id("HEAD").unwrap();
+        working_copy.repo.set_head_detached(head).unwrap();
+        let err = working_copy.current_branch_name().unwrap_err();
+        assert!(matches!(patch_error(&err), PatchError::DetachedHead));
+    }
+
+    #[test]
+    fn create_and_update_patch_tag() {
+        let (_tmp, working_copy) = working_copy();
+        assert!(working_copy.find_patch_tag("feature").unwrap().is_none());
+
+        working_copy
+            .create_patch_tag("feature", "First\n", false)
+            .unwrap();
+        let err = working_copy
+            .create_patch_tag("feature", "Second\n", false)
+            .unwrap_err();
+        assert!(matches!(
+            patch_error(&err),
+            PatchError::TagExists { name } if name == "feature"
+        ));
+
+        let head = commit(
+            &working_copy.repo,
+            Some("HEAD"),
+            "change",
+            &[working_copy.repo.refname_to_id("HEAD").unwrap()],
+        );
+        working_copy
+            .create_patch_tag("feature", "Second\n", true)
+            .unwrap();
+        let tag = working_copy.find_patch_tag("feature").unwrap().unwrap();
+        assert_eq!(tag.message(), Some("Second\n"));
+        assert_eq!(tag.target_id(), head);
+    }
+
+    #[test]
+    fn checkout_new_branch() {
+        let (_tmp, working_copy) = working_copy();
+        let main = working_copy.repo.refname_to_id("HEAD").unwrap();
+        let patch_commit = commit(&working_copy.repo, None, "patch", &[main]);
+
+        working_copy
+            .checkout_new_branch("feature", patch_commit)
+            .unwrap();
+        let head = working_copy.repo.head().unwrap();
+        assert_eq!(head.name(), Some("refs/heads/feature"));
+        assert_eq!(head.target(), Some(patch_commit));
+
+        let err = working_copy
+            .checkout_new_branch("main", patch_commit)
+            .unwrap_err();
+        assert!(matches!(
+            patch_error(&err),
+            PatchError::BranchExists { name } if name == "main"
+        ));
+    }
+
+    #[test]
+    fn push_patch_to_seed() {
+        let monorepo_dir = tempfile::tempdir().unwrap();
+        let monorepo = git2::Repository::init_bare(monorepo_dir.path()).unwrap();
+        let seed_dir = tempfile::tempdir().unwrap();
+        let urn = project_urn();
+        let namespace = urn.encode_id();
+        let peer_id = PeerId::from(link_crypto::SecretKey::new());
+
+        let patch_commit = commit(&monorepo, None, "patch", &[]);
+        let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tag = monorepo
+            .tag_annotation_create(
+                "radicle-patch/feature",
+                &monorepo.find_object(patch_commit, None).unwrap(),
+                &signature,
+                "Feature\n",
+            )
+            .unwrap();
+        monorepo
+            .reference(
+                &format!(
+                    "refs/namespaces/{}/refs/tags/radicle-patch/feature",
+                    namespace
+                ),
+                tag,
+                true,
+                "",
+            )
+            .unwrap();
+        let signed_refs = commit(&monorepo, None, "signed refs", &[]);
+        monorepo
+            .reference(
+                &format!("refs/namespaces/{}/refs/rad/signed_refs", namespace),
+                signed_refs,
+                true,
+                "",
+            )
+            .unwrap();
+
+        let seed_url = seed_dir.path().to_str().unwrap();
+        let err = push_to_seed(
+            monorepo_dir.path(),
+            &urn,
+            peer_id,
+            seed_url,
+            "refs/tags/radicle-patch/feature",
+        )
+        .unwrap_err();
+        assert!(matches!(patch_error(&err), PatchError::Sync { .. }));
+
+        let seed = git2::Repository::init_bare(seed_dir.path().join(&namespace)).unwrap();
+        push_to_seed(
+            monorepo_dir.path(),
+            &urn,
+            peer_id,
+            seed_url,
+            "refs/tags/radicle-patch/feature",
+        )
+        .unwrap();
+        assert_eq!(
+            seed.refname_to_id(&format!(
+                "refs/remotes/{}/tags/radicle-patch/feature",
+                peer_id
+            ))
+            .unwrap(),
+            tag
+        );
+        assert_eq!(
+            seed.refname_to_id(&format!("refs/remotes/{}/rad/signed_refs", peer_id))
+                .unwrap(),
+            signed_refs
+        );
+    }
+
+    #[test]
+    fn select_pinned_seed() {
+        let seed = |url: &str, pinned| ProjectSeed {
+            url: url.to_string(),
+            pinned,
+        };
+        assert_eq!(select_seed(vec![]), None);
+        assert_eq!(
+            select_seed(vec![seed("https://a", false), seed("https://b", false)]),
+            Some("https://a".to_string())
+        );
+        assert_eq!(
+            select_seed(vec![seed("https://a", false), seed("https://b", true)]),
+            Some("https://b".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_uri_component_escapes_reserved_characters() {
+        assert_eq!(encode_uri_component("a-b_c.d~e"), "a-b_c.d~e");
+        assert_eq!(encode_uri_component("a/b c%"), "a%2Fb%20c%25");
+    }
+}
@@ -0,0 +1,878 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Read patches and their event logs from the Radicle monorepo.
+//!
+//! Patch tags and event logs are read in the same format that Upstream writes them so that both
+//! see the same patches, statuses and comments. Upstream does not need to be running. Patch events
+//! are published through the Upstream API so that Upstream is the only writer of event logs.
+
+use anyhow::Context as _;
+use either::Either;
+use librad::PeerId;
+
+const TAG_PREFIX: &str = "radicle-patch/";
+
+/// Prefix for event log references excluding the leading `refs/`.
+const EVENT_LOG_REF_PREFIX: &str = "upstream/events.experimental";
+
+const MESSAGE_CONTENT_KEY: &str = "content";
+const MESSAGE_CONTENT_TYPE_KEY: &str = "content-type";
+const MESSAGE_EVENT_CONTENT_TYPE: &str = "radicle-upstream-event.v1";
+
+/// Patch read from the monorepo. Serializes to a subset of the patch representation of the
+/// Upstream API.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patch {
+    pub id: String,
+    pub peer: PatchPeer,
+    pub message: Option<String>,
+    pub commit: String,
+    pub merge_base: Option<String>,
+    pub status: Status,
+    /// Comments on the patch in chronological order.
+    pub comments: Vec<Comment>,
+    /// Time of the most recent event for the patch in milliseconds since the Unix epoch.
+    pub last_activity: Option<u64>,
+}
+
+impl Patch {
+    /// The first line of the patch message.
+    pub fn title(&self) -> &str {
+        self.message
+            .as_deref()
+            .and_then(|message| message.lines().next())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchPeer {
+    pub peer_id: PeerId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub current: State,
+    /// Peer that published the `setStatus` event the current state is derived from.
+    pub by_peer_id: Option<PeerId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum State {
+    Open,
+    Closed,
+    Merged,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Open => write!(f, "open"),
+            State::Closed => write!(f, "closed"),
+            State::Merged => write!(f, "merged"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub peer_id: PeerId,
+    pub comment: String,
+    /// Time the comment was created in milliseconds since the Unix epoch as claimed by the
+    /// author.
+    pub timestamp: u64,
+}
+
+/// Patch events understood by the CLI. Compatible with the `setStatus` and `addComment` events
+/// of the Upstream UI. Other patch events are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "data")]
+pub enum Event {
+    SetStatus { status: State },
+    AddComment { comment: String, timestamp: u64 },
+}
+
+/// Event as stored in an event log commit.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Envelope {
+    /// ID of peer that authored the event
+    peer_id: PeerId,
+    /// The Radicle identity that the event is concerned with
+    identity: librad::git_ext::Oid,
+    topic: String,
+    event: serde_json::Value,
+    /// Commit time of the event in milliseconds since the Unix epoch. Never written to the log.
+    #[serde(default, skip_serializing)]
+    timestamp: Option<u64>,
+}
+
+/// Information about a project required to construct [`Patch`]es.
+pub struct ProjectInfo {
+    pub urn: librad::git::Urn,
+    /// Peer IDs of all the project delegates.
+    pub delegates: Vec<PeerId>,
+    /// Head commit of the first delegate's default branch. `None` if the branch has not been
+    /// replicated.
+    pub default_branch_head: Option<git2::Oid>,
+}
+
+impl ProjectInfo {
+    /// Load the project identity from `storage`.
+    pub fn load(
+        storage: &librad::git::storage::ReadOnly,
+        monorepo: &Monorepo,
+        urn: &librad::git::Urn,
+    ) -> anyhow::Result<Self> {
+        let project = librad::git::identities::project::get(storage, urn)
+            .context("failed to get project")?
+            .ok_or_else(|| anyhow::anyhow!("project {} not found", urn))?;
+        let delegates = project
+            .delegations()
+            .iter()
+            .flat_map(|delegation| match delegation {
+                Either::Left(key) => Either::Left(std::iter::once(key)),
+                Either::Right(person) => Either::Right(person.delegations().iter()),
+            })
+            .map(|key| PeerId::from(*key))
+            .collect::<Vec<_>>();
+        let first_delegate = *delegates
+            .first()
+            .context("project does not have any delegations")?;
+        let default_branch = project
+            .subject()
+            .default_branch
+            .as_ref()
+            .map_or_else(|| "main".to_string(), |name| name.to_string());
+        let remote = if first_delegate == monorepo.local_peer_id {
+            None
+        } else {
+            Some(first_delegate)
+        };
+        let default_branch_head = monorepo.find_oid(&namespaced_ref_name(
+            urn,
+            remote,
+            &format!("heads/{}", default_branch),
+        ))?;
+
+        Ok(Self {
+            urn: urn.clone(),
+            delegates,
+            default_branch_head,
+        })
+    }
+}
+
+/// The Radicle monorepo of the local peer.
+pub struct Monorepo {
+    repo: git2::Repository,
+    local_peer_id: PeerId,
+}
+
+impl Monorepo {
+    /// Open the monorepo of the profile with the given `paths`.
+    pub fn open(paths: &librad::paths::Paths, local_peer_id: PeerId) -> anyhow::Result<Self> {
+        let repo =
+            git2::Repository::open_bare(paths.git_dir()).context("failed to open monorepo")?;
+        Ok(Self::new(repo, local_peer_id))
+    }
+
+    pub fn new(repo: git2::Repository, local_peer_id: PeerId) -> Self {
+        Self {
+            repo,
+            local_peer_id,
+        }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        self.repo.path()
+    }
+
+    /// List the patches of all peers of the project.
+    pub fn patches(&self, project: &ProjectInfo) -> anyhow::Result<Vec<Patch>> {
+        let mut tag_refs = self.patch_tag_refs(&project.urn, None, "*")?;
+        tag_refs.extend(self.patch_tag_refs(&project.urn, Some("*"), "*")?);
+
+        let mut patches = vec![];
+        for ref_name in tag_refs {
+            let (peer_id, id) = self.parse_patch_tag_ref(&project.urn, &ref_name)?;
+            patches.push(self.patch_from_tag(project, peer_id, id, &ref_name)?);
+        }
+        Ok(patches)
+    }
+
+    /// Get the patch `id` authored by `peer_id`. Returns `None` if the patch does not exist.
+    pub fn patch(
+        &self,
+        project: &ProjectInfo,
+        peer_id: PeerId,
+        id: &str,
+    ) -> anyhow::Result<Option<Patch>> {
+        let ref_name = self.patch_tag_ref_name(&project.urn, peer_id, id);
+        match self.find_oid(&ref_name)? {
+            Some(_) => Ok(Some(self.patch_from_tag(
+                project,
+                peer_id,
+                id.to_string(),
+                &ref_name,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the name of the monorepo reference for the patch tag `id` authored by `peer_id`.
+    pub fn patch_tag_ref_name(&self, urn: &librad::git::Urn, peer_id: PeerId, id: &str) -> String {
+        let remote = Some(peer_id).filter(|peer_id| *peer_id != self.local_peer_id);
+        namespaced_ref_name(urn, remote, &format!("tags/{}{}", TAG_PREFIX, id))
+    }
+
+    /// Construct the patch `id` of `peer_id` from the tag referenced by `ref_name` and the patch
+    /// event log.
+    fn patch_from_tag(
+        &self,
+        project: &ProjectInfo,
+        peer_id: PeerId,
+        id: String,
+        ref_name: &str,
+    ) -> anyhow::Result<Patch> {
+        let tag = self
+            .repo
+            .find_reference(ref_name)
+            .and_then(|reference| reference.peel_to_tag())
+            .context(format!("failed to peel {} to tag", ref_name))?;
+        let commit_id = tag.target_id();
+        let merge_base = match project.default_branch_head {
+            Some(head) => match self.repo.merge_base(commit_id, head) {
+                Ok(base) => Some(base),
+                Err(err) if err.code() == git2::ErrorCode::NotFound => None,
+                Err(err) => return Err(err).context("failed to determine merge base for commits"),
+            },
+            None => None,
+        };
+
+        let envelopes = self.events(&project.urn, &event_topic(peer_id, &id))?;
+        let merged = merge_base == Some(commit_id);
+        let (status, comments) = fold_events(peer_id, &project.delegates, merged, &envelopes);
+
+        Ok(Patch {
+            id,
+            peer: PatchPeer { peer_id },
+            message: tag.message().map(ToString::to_string),
+            commit: commit_id.to_string(),
+            merge_base: merge_base.map(|oid| oid.to_string()),
+            status,
+            comments,
+            last_activity: envelopes
+                .iter()
+                .filter_map(|envelope| envelope.timestamp)
+                .max(),
+        })
+    }
+
+    /// Returns the names of the patch tag references of the peer `remote` matching `id_pattern`.
+    /// `remote` may be a glob pattern. If `remote` is `None` the tags of the local peer are
+    /// returned.
+    fn patch_tag_refs(
+        &self,
+        urn: &librad::git::Urn,
+        remote: Option<&str>,
+        id_pattern: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let glob = namespaced_ref_name(urn, remote, &format!("tags/{}{}", TAG_PREFIX, id_pattern));
+        self.ref_names(&glob)
+    }
+
+    /// Returns the author and ID of a patch from the name of its tag reference.
+    fn parse_patch_tag_ref(
+        &self,
+        urn: &librad::git::Urn,
+        ref_name: &str,
+    ) -> anyhow::Result<(PeerId, String)> {
+        let namespace_prefix = format!("refs/namespaces/{}/refs/", urn.encode_id());
+        let name = ref_name
+            .strip_prefix(&namespace_prefix)
+            .context("patch tag reference outside of project namespace")?;
+        let (peer_id, name) = match name.strip_prefix("remotes/") {
+            Some(remote_name) => {
+                let (peer_id, name) = remote_name
+                    .split_once('/')
+                    .context("invalid remote reference name")?;
+                let peer_id = PeerId::from_default_encoding(peer_id)
+                    .context(format!("invalid peer ID in reference name {}", ref_name))?;
+                (peer_id, name)
+            },
+            None => (self.local_peer_id, name),
+        };
+        let id = name
+            .strip_prefix("tags/")
+            .and_then(|name| name.strip_prefix(TAG_PREFIX))
+            .context(format!("invalid patch tag reference name {}", ref_name))?;
+        Ok((peer_id, id.to_string()))
+    }
+
+    /// Read the events for `topic` from all event logs of the project that pass verification.
+    /// Events are ordered from newest to oldest.
+    fn events(&self, urn: &librad::git::Urn, topic: &str) -> anyhow::Result<Vec<Envelope>> {
+        let mut revwalk = self.repo.revwalk().context("failed to create revwalk")?;
+        revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .context("failed to set revwalk sorting")?;
+
+        let mut log_ref_names = self.ref_names(&event_log_ref_name(urn, Some("*"), topic))?;
+        log_ref_names.extend(self.ref_names(&event_log_ref_name(urn, None, topic))?);
+        for log_ref_name in log_ref_names {
+            // Like Upstream we ignore the whole log of a peer if any of its events is forged.
+            if self.verify_log(urn, &log_ref_name).is_ok() {
+                revwalk
+                    .push_ref(&log_ref_name)
+                    .context(format!("failed to push ref `{}` to revwalk", log_ref_name))?;
+            }
+        }
+
+        let mut envelopes = vec![];
+        for oid_result in revwalk {
+            let oid = oid_result.context("failed to get commit from revwalk")?;
+            let commit = self
+                .repo
+                .find_commit(oid)
+                .context(format!("commit {} not found when walking revs", oid))?;
+            let mut envelope = match envelope_from_message(commit.message_bytes()) {
+                Ok(envelope) => envelope,
+                Err(_) => continue,
+            };
+            if envelope.identity != urn.id || envelope.topic != topic {
+                continue;
+            }
+            envelope.timestamp =
+                Some(u64::try_from(commit.time().seconds()).unwrap_or_default() * 1000);
+            envelopes.push(envelope);
+        }
+        Ok(envelopes)
+    }
+
+    /// Verify that every event in the log referenced by `ref_name` is signed by the peer that
+    /// owns the log.
+    fn verify_log(&self, urn: &librad::git::Urn, ref_name: &str) -> anyhow::Result<()> {
+        let remotes_prefix = format!("refs/namespaces/{}/refs/remotes/", urn.encode_id());
+        let log_owner = match ref_name.strip_prefix(&remotes_prefix) {
+            Some(remote_ref_name) => {
+                let (peer_id, _) = remote_ref_name
+                    .split_once('/')
+                    .context("invalid remote reference name")?;
+                PeerId::from_default_encoding(peer_id)
+                    .context(format!("invalid peer ID in reference name {}", ref_name))?
+            },
+            None => self.local_peer_id,
+        };
+
+        let mut revwalk = self.repo.revwalk().context("failed to create revwalk")?;
+        revwalk
+            .push_ref(ref_name)
+            .context("failed to push reference to revwalk")?;
+        for result in revwalk {
+            let oid = result.context("failed to get next commit")?;
+            let commit = self
+                .repo
+                .find_commit(oid)
+                .context(format!("commit {} not found when walking revs", oid))?;
+            let envelope = envelope_from_message(commit.message_bytes())?;
+            if envelope.peer_id != log_owner {
+                anyhow::bail!(
+                    "event {} authored by {} is stored in event log of {}",
+                    oid,
+                    envelope.peer_id,
+                    log_owner
+                );
+            }
+
+            let (signature_encoded, signed) = self
+                .repo
+                .extract_signature(&oid, Some("radicle-ed25519"))
+                .context(format!("event {} is not signed", oid))?;
+            let signature_bytes = std::str::from_utf8(&signature_encoded)
+                .ok()
+                .and_then(|encoded| base64::decode(encoded).ok())
+                .context("invalid signature encoding")?;
+            let signature = minicbor::decode::<link_crypto::Signature>(&signature_bytes)
+                .context("failed to decode signature")?;
+            if !signature.verify(&signed, envelope.peer_id.as_public_key()) {
+                anyhow::bail!("signature of {} could not be verified", oid);
+            }
+        }
+        Ok(())
+    }
+
+    fn find_oid(&self, ref_name: &str) -> anyhow::Result<Option<git2::Oid>> {
+        match self.repo.refname_to_id(ref_name) {
+            Ok(oid) => Ok(Some(oid)),
+            Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(err) => Err(err).context(format!("failed to resolve {}", ref_name)),
+        }
+    }
+
+    fn ref_names(&self, glob: &str) -> anyhow::Result<Vec<String>> {
+        let mut ref_names = vec![];
+        for result in self
+            .repo
+            .references_glob(glob)
+            .context("failed to list refs")?
+        {
+            let reference = result.context("failed to get next reference")?;
+            let ref_name = std::str::from_utf8(reference.name_bytes())
+                .context("reference name is not valid UTF-8")?;
+            ref_names.push(ref_name.to_string());
+        }
+        Ok(ref_names)
+    }
+}
+
+/// Returns the event log topic for the patch `id` authored by `peer_id`.
+pub fn event_topic(peer_id: PeerId, id: &str) -> String {
+    format!("patch/{}/{}", peer_id, id)
+}
+
+/// Derive the status and the comments of a patch from `envelopes` ordered from newest to oldest.
+///
+/// The status is determined by the most recent `setStatus` event published by either the
+/// `author` of the patch or one of the `delegates`. If the patch is `merged` the status is always
+/// [`State::Merged`]. Comments are returned in chronological order.
+fn fold_events(
+    author: PeerId,
+    delegates: &[PeerId],
+    merged: bool,
+    envelopes: &[Envelope],
+) -> (Status, Vec<Comment>) {
+    let mut status = None;
+    let mut comments = vec![];
+    for envelope in envelopes {
+        match serde_json::from_value::<Event>(envelope.event.clone()) {
+            Ok(Event::SetStatus { status: state }) => {
+                let authorized =
+                    envelope.peer_id == author || delegates.contains(&envelope.peer_id);
+                if status.is_none() && authorized {
+                    status = Some(Status {
+                        current: state,
+                        by_peer_id: Some(envelope.peer_id),
+                    })
+                }
+            },
+            Ok(Event::AddComment { comment, timestamp }) => comments.push(Comment {
+                peer_id: envelope.peer_id,
+                comment,
+                timestamp,
+            }),
+            Err(_) => {},
+        }
+    }
+    comments.reverse();
+
+    let status = if merged {
+        Status {
+            current: State::Merged,
+            by_peer_id: None,
+        }
+    } else {
+        status.unwrap_or(Status {
+            current: State::Open,
+            by_peer_id: None,
+        })
+    };
+    (status, comments)
+}
+
+/// Returns the name of `name` in the project namespace. If `remote` is given the name of the
+/// reference in the remote namespace of that peer is returned.
+fn namespaced_ref_name(
+    urn: &librad::git::Urn,
+    remote: Option<impl std::fmt::Display>,
+    name: &str,
+) -> String {
+    let namespace = urn.encode_id();
+    match remote {
+        Some(remote) => format!(
+            "refs/namespaces/{}/refs/remotes/{}/{}",
+            namespace, remote, name
+        ),
+        None => format!("refs/namespaces/{}/refs/{}", namespace, name),
+    }
+}
+
+fn event_log_ref_name(urn: &librad::git::Urn, remote: Option<&str>, topic: &str) -> String {
+    namespaced_ref_name(urn, remote, &format!("{}/{}", EVENT_LOG_REF_PREFIX, topic))
+}
+
+fn envelope_from_message(message: &[u8]) -> anyhow::Result<Envelope> {
+    let message =
+        std::str::from_utf8(message).context("event commit message is not valid UTF-8")?;
+    let trailers =
+        git2::message_trailers_strs(message).context("failed to get message trailers")?;
+    let content_type = trailers
+        .iter()
+        .find(|(key, _)| *key == MESSAGE_CONTENT_TYPE_KEY)
+        .map(|(_, value)| value);
+    if content_type != Some(MESSAGE_EVENT_CONTENT_TYPE) {
+        anyhow::bail!("invalid content type for event commit message");
+    }
+    let content = trailers
+        .iter()
+        .find(|(key, _)| *key == MESSAGE_CONTENT_KEY)
+        .map(|(_, value)| value)
+        .context("no content field in event commit message trailers")?;
+    serde_json::from_str(content).context("failed to parse event envelope from json")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Encode `envelope` in a commit message the way Upstream does.
+    fn envelope_to_message(envelope: &Envelope) -> String {
+        let event_type = envelope
+            .event
+            .get("type")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default();
+        let content = serde_json::to_string(envelope).expect("failed to serialize envelope");
+        format!(
+            "radicle upstream event: {}\n\n{}: {}\n{}: {}\n",
+            event_type,
+            MESSAGE_CONTENT_TYPE_KEY,
+            MESSAGE_EVENT_CONTENT_TYPE,
+            MESSAGE_CONTENT_KEY,
+            content
+        )
+    }
+
+    struct Fixture {
+        _tmp: tempfile::TempDir,
+        path: std::path::PathBuf,
+        urn: librad::git::Urn,
+        base: git2::Oid,
+    }
+
+    impl Fixture {
+        /// Create a monorepo with a project whose default branch points to a single commit.
+        fn new() -> Self {
+            let tmp = tempfile::tempdir().unwrap();
+            let repo = git2::Repository::init_bare(tmp.path()).unwrap();
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Alice").unwrap();
+            config.set_str("user.email", "alice@example.com").unwrap();
+
+            let urn = librad::git::Urn::new(
+                git2::Oid::hash_object(git2::ObjectType::Blob, b"project")
+                    .unwrap()
+                    .into(),
+            );
+            let fixture = Self {
+                path: repo.path().to_owned(),
+                _tmp: tmp,
+                urn,
+                base: git2::Oid::zero(),
+            };
+            let base = fixture.commit(&[], "base");
+            fixture.reference(None::<PeerId>, "heads/main", base);
+            Self { base, ..fixture }
+        }
+
+        fn monorepo(&self, local_peer_id: PeerId) -> Monorepo {
+            Monorepo::new(
+                git2::Repository::open_bare(&self.path).unwrap(),
+                local_peer_id,
+            )
+        }
+
+        fn project(&self, delegate: PeerId) -> ProjectInfo {
+            ProjectInfo {
+                urn: self.urn.clone(),
+                delegates: vec![delegate],
+                default_branch_head: Some(self.base),
+            }
+        }
+
+        fn commit(&self, parents: &[git2::Oid], message: &str) -> git2::Oid {
+            let repo = git2::Repository::open_bare(&self.path).unwrap();
+            crate::test_util::commit(&repo, None, message, parents)
+        }
+
+        fn reference(&self, remote: Option<PeerId>, name: &str, oid: git2::Oid) {
+            let repo = git2::Repository::open_bare(&self.path).unwrap();
+            repo.reference(&namespaced_ref_name(&self.urn, remote, name), oid, true, "")
+                .unwrap();
+        }
+
+        /// Create the patch tag `id` for `commit` in the namespace of `remote`.
+        fn patch_tag(&self, remote: Option<PeerId>, id: &str, commit: git2::Oid, message: &str) {
+            let repo = git2::Repository::open_bare(&self.path).unwrap();
+            let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+            let tag = repo
+                .tag_annotation_create(
+                    &format!("{}{}", TAG_PREFIX, id),
+                    &repo.find_object(commit, None).unwrap(),
+                    &signature,
+                    message,
+                )
+                .unwrap();
+            self.reference(remote, &format!("tags/{}{}", TAG_PREFIX, id), tag);
+        }
+
+        /// Write `event` signed by `key` to the event log of the patch `id` of `author` like
+        /// Upstream does. The event is appended to the log of the remote `log_owner` as if it had
+        /// been replicated from that peer or, if `log_owner` is `None`, to the local log.
+        fn publish(
+            &self,
+            key: &link_crypto::SecretKey,
+            log_owner: Option<PeerId>,
+            author: PeerId,
+            id: &str,
+            event: &Event,
+        ) {
+            use librad::Signer as _;
+
+            let repo = git2::Repository::open_bare(&self.path).unwrap();
+            let topic = event_topic(author, id);
+            let envelope = Envelope {
+                peer_id: PeerId::from(key.clone()),
+                identity: self.urn.id,
+                topic: topic.clone(),
+                event: serde_json::to_value(event).unwrap(),
+                timestamp: None,
+            };
+            let log_owner = log_owner.map(|peer_id| peer_id.to_string());
+            let log_ref_name = event_log_ref_name(&self.urn, log_owner.as_deref(), &topic);
+            let parent = repo
+                .refname_to_id(&log_ref_name)
+                .ok()
+                .map(|oid| repo.find_commit(oid).unwrap());
+            let tree = repo
+                .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+                .unwrap();
+            let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+            let commit_buffer = repo
+                .commit_create_buffer(
+                    &signature,
+                    &signature,
+                    &envelope_to_message(&envelope),
+                    &tree,
+                    &parent.iter().collect::<Vec<_>>(),
+                )
+                .unwrap();
+            let commit_signature = link_crypto::BoxedSigner::from(key.clone())
+                .sign_blocking(commit_buffer.as_ref())
+                .unwrap();
+            let commit_signature =
+                minicbor::to_vec(link_crypto::Signature::from(commit_signature)).unwrap();
+            let commit_id = repo
+                .commit_signed(
+                    commit_buffer.as_str().unwrap(),
+                    &base64::encode(commit_signature),
+                    Some("radicle-ed25519"),
+                )
+                .unwrap();
+            repo.reference(&log_ref_name, commit_id, true, "").unwrap();
+        }
+    }
+
+    fn comment(text: &str, timestamp: u64) -> Event {
+        Event::AddComment {
+            comment: text.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn list_patches_of_all_peers() {
+        let fixture = Fixture::new();
+        let alice = link_crypto::SecretKey::new();
+        let alice_id = PeerId::from(alice.clone());
+        let bob = link_crypto::SecretKey::new();
+        let bob_id = PeerId::from(bob.clone());
+        let carol = link_crypto::SecretKey::new();
+        let carol_id = PeerId::from(carol.clone());
+
+        fixture.patch_tag(None, "merged", fixture.base, "Merged patch\n");
+        let feature = fixture.commit(&[fixture.base], "feature");
+        fixture.patch_tag(Some(bob_id), "feature", feature, "Feature\n\nDescription\n");
+
+        fixture.publish(
+            &bob,
+            Some(bob_id),
+            bob_id,
+            "feature",
+            &Event::SetStatus {
+                status: State::Closed,
+            },
+        );
+        // Carol is neither the author nor a delegate and cannot change the status.
+        fixture.publish(
+            &carol,
+            Some(carol_id),
+            bob_id,
+            "feature",
+            &Event::SetStatus {
+                status: State::Open,
+            },
+        );
+        fixture.publish(
+            &alice,
+            None,
+            bob_id,
+            "feature",
+            &comment("Looks good", 1000),
+        );
+
+        let monorepo = fixture.monorepo(alice_id);
+        let project = fixture.project(alice_id);
+        let mut patches = monorepo.patches(&project).unwrap();
+        patches.sort_by(|a, b| a.id.cmp(&b.id));
+        let summary = patches
+            .iter()
+            .map(|patch| {
+                (
+                    patch.peer.peer_id,
+                    patch.id.as_str(),
+                    patch.title(),
+                    patch.status.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    bob_id,
+                    "feature",
+                    "Feature",
+                    Status {
+                        current: State::Closed,
+                        by_peer_id: Some(bob_id)
+                    }
+                ),
+                (
+                    alice_id,
+                    "merged",
+                    "Merged patch",
+                    Status {
+                        current: State::Merged,
+                        by_peer_id: None
+                    }
+                ),
+            ]
+        );
+
+        let feature_patch = monorepo
+            .patch(&project, bob_id, "feature")
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature_patch, patches[0]);
+        assert_eq!(feature_patch.commit, feature.to_string());
+        assert_eq!(feature_patch.merge_base, Some(fixture.base.to_string()));
+        assert_eq!(
+            feature_patch.comments,
+            vec![Comment {
+                peer_id: alice_id,
+                comment: "Looks good".to_string(),
+                timestamp: 1000,
+            }]
+        );
+        assert!(feature_patch.last_activity.is_some());
+
+        assert_eq!(monorepo.patch(&project, bob_id, "other").unwrap(), None);
+        assert_eq!(monorepo.patch(&project, carol_id, "feature").unwrap(), None);
+    }
+
+    #[test]
+    fn latest_status_wins() {
+        let fixture = Fixture::new();
+        let alice = link_crypto::SecretKey::new();
+        let alice_id = PeerId::from(alice.clone());
+        let feature = fixture.commit(&[fixture.base], "feature");
+        fixture.patch_tag(None, "feature", feature, "Feature\n");
+
+        for (status, text) in [(State::Closed, "first"), (State::Open, "second")] {
+            fixture.publish(
+                &alice,
+                None,
+                alice_id,
+                "feature",
+                &Event::SetStatus { status },
+            );
+            fixture.publish(&alice, None, alice_id, "feature", &comment(text, 0));
+        }
+
+        let patch = fixture
+            .monorepo(alice_id)
+            .patch(&fixture.project(alice_id), alice_id, "feature")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            patch.status,
+            Status {
+                current: State::Open,
+                by_peer_id: Some(alice_id)
+            }
+        );
+        assert_eq!(
+            patch
+                .comments
+                .iter()
+                .map(|comment| comment.comment.as_str())
+                .collect::<Vec<_>>(),
+            vec!["first", "second"]
+        );
+    }
+
+    /// Events stored in the log of a peer that did not author them are ignored together with the
+    /// rest of that log.
+    #[test]
+    fn forged_event_logs_are_ignored() {
+        let fixture = Fixture::new();
+        let alice_id = PeerId::from(link_crypto::SecretKey::new());
+        let bob_id = PeerId::from(link_crypto::SecretKey::new());
+        let mallory = link_crypto::SecretKey::new();
+        let feature = fixture.commit(&[fixture.base], "feature");
+        fixture.patch_tag(Some(bob_id), "feature", feature, "Feature\n");
+
+        fixture.publish(
+            &mallory,
+            Some(bob_id),
+            bob_id,
+            "feature",
+            &Event::SetStatus {
+                status: State::Closed,
+            },
+        );
+
+        let patch = fixture
+            .monorepo(alice_id)
+            .patch(&fixture.project(alice_id), bob_id, "feature")
+            .unwrap()
+            .unwrap();
+        assert_eq!(patch.status.current, State::Open);
+        assert_eq!(patch.last_activity, None);
+    }
+
+    #[test]
+    fn event_message_round_trip() {
+        let envelope = Envelope {
+            peer_id: PeerId::from(link_crypto::SecretKey::new()),
+            identity: git2::Oid::zero().into(),
+            topic: "patch/foo".to_string(),
+            event: serde_json::to_value(comment("hello", 1)).unwrap(),
+            timestamp: None,
+        };
+        let message = envelope_to_message(&envelope);
+        assert!(message.starts_with("radicle upstream event: addComment\n\n"));
+        assert_eq!(envelope_from_message(message.as_bytes()).unwrap(), envelope);
+        assert!(envelope_from_message(b"title\n\ncontent: {}\n").is_err());
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Helpers for tests that create Git objects.
+
+/// Create a commit with an empty tree and `parents`. If `update_ref` is given, the reference is
+/// updated to point to the commit.
+pub fn commit(
+    repo: &git2::Repository,
+    update_ref: Option<&str>,
+    message: &str,
+    parents: &[git2::Oid],
+) -> git2::Oid {
+    let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let parents = parents
+        .iter()
+        .map(|oid| repo.find_commit(*oid).unwrap())
+        .collect::<Vec<_>>();
+    repo.commit(
+        update_ref,
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
@@ -45,6 +45,12 @@ pub struct Args {
     )]
     pub git_seeds: Option<Vec<rad_common::Url>>,
 
+    /// Number of Git+HTTP seeds a project needs to be fetched from successfully. Further seeds are
+    /// only tried if fetching from a seed fails. If not provided, projects are fetched from all
+    /// seeds.
+    #[clap(long, env = "RADICLE_PROXY_GIT_FETCH_QUORUM")]
+    pub git_fetch_quorum: Option<usize>,
+
     /// Interval at which to fetch project updates from Git+HTTP seeds in seconds.
     #[clap(long, default_value_t = 10)]
     pub git_fetch_interval: u64,
@@ -92,10 +92,13 @@ impl From<Sealed> for Context {
 #[derive(Clone)]
 pub struct Unsealed {
     pub peer: crate::peer::Peer,
+    pub blame_cache: crate::source::BlameCache,
     pub event_log: crate::events::EventLog,
     pub git_fetch: crate::git_fetch::Handle,
+    pub inbox: crate::inbox::Handle,
     pub rest: Sealed,
     pub watch_monorepo: crate::watch_monorepo::Handle,
+    pub webhooks: crate::webhooks::Handle,
 }
 
 /// Context for HTTP request if the coco peer APIs have not been initialized yet.
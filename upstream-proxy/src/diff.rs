@@ -0,0 +1,553 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Serializable representation of the changes between two Git trees and between two versions of
+//! a series of commits.
+
+use anyhow::Context as _;
+use serde::Serialize;
+
+/// Changes between two trees.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diff {
+    pub files: Vec<FileDiff>,
+}
+
+/// Changes to a single file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    /// Path of the file in the old tree. `None` if the file was added.
+    pub old_path: Option<String>,
+    /// Path of the file in the new tree. `None` if the file was deleted.
+    pub new_path: Option<String>,
+    pub status: FileStatus,
+    /// `true` if the file contains binary data. Binary files have no hunks.
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Summary of the changes in a [`Diff`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStats {
+    pub files_changed: usize,
+    /// Number of added lines.
+    pub additions: usize,
+    /// Number of deleted lines.
+    pub deletions: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+    TypeChanged,
+}
+
+/// A contiguous region of changes in a file.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hunk {
+    /// Hunk header, for example `@@ -1,4 +1,5 @@`.
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Line {
+    pub kind: LineKind,
+    /// Content of the line without the trailing newline.
+    pub content: String,
+    /// Line number in the old file. `None` for additions.
+    pub old_line_number: Option<u32>,
+    /// Line number in the new file. `None` for deletions.
+    pub new_line_number: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineKind {
+    Context,
+    Addition,
+    Deletion,
+}
+
+impl Diff {
+    /// Compute the changes from the tree of commit `old` to the tree of commit `new`. If `old` is
+    /// `None`, all files in `new` are reported as added. Renames are detected.
+    pub fn between_commits(
+        repo: &git2::Repository,
+        old: Option<git2::Oid>,
+        new: git2::Oid,
+    ) -> anyhow::Result<Self> {
+        let old_tree = match old {
+            Some(old) => Some(
+                repo.find_commit(old)
+                    .context(format!("failed to find commit {old}"))?
+                    .tree()
+                    .context("failed to get commit tree")?,
+            ),
+            None => None,
+        };
+        let new_tree = repo
+            .find_commit(new)
+            .context(format!("failed to find commit {new}"))?
+            .tree()
+            .context("failed to get commit tree")?;
+
+        let mut diff = repo
+            .diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), None)
+            .context("failed to diff trees")?;
+        diff.find_similar(None)
+            .context("failed to detect renamed files")?;
+
+        Self::from_git(&diff)
+    }
+
+    pub fn stats(&self) -> DiffStats {
+        let mut stats = DiffStats {
+            files_changed: self.files.len(),
+            ..DiffStats::default()
+        };
+        let lines = self
+            .files
+            .iter()
+            .flat_map(|file| &file.hunks)
+            .flat_map(|hunk| &hunk.lines);
+        for line in lines {
+            match line.kind {
+                LineKind::Addition => stats.additions += 1,
+                LineKind::Deletion => stats.deletions += 1,
+                LineKind::Context => {},
+            }
+        }
+        stats
+    }
+
+    /// Convert a [`git2::Diff`] into its serializable representation.
+    pub fn from_git(diff: &git2::Diff) -> anyhow::Result<Self> {
+        let mut files = vec![];
+        for delta_index in 0..diff.deltas().len() {
+            let patch = match git2::Patch::from_diff(diff, delta_index)
+                .context("failed to get patch for file")?
+            {
+                Some(patch) => patch,
+                None => continue,
+            };
+            let delta = patch.delta();
+            let status = match delta.status() {
+                git2::Delta::Added => FileStatus::Added,
+                git2::Delta::Deleted => FileStatus::Deleted,
+                git2::Delta::Modified => FileStatus::Modified,
+                git2::Delta::Renamed => FileStatus::Renamed,
+                git2::Delta::Copied => FileStatus::Copied,
+                git2::Delta::Typechange => FileStatus::TypeChanged,
+                _ => continue,
+            };
+            let old_path = match status {
+                FileStatus::Added => None,
+                _ => delta
+                    .old_file()
+                    .path()
+                    .map(|path| path.display().to_string()),
+            };
+            let new_path = match status {
+                FileStatus::Deleted => None,
+                _ => delta
+                    .new_file()
+                    .path()
+                    .map(|path| path.display().to_string()),
+            };
+
+            let mut hunks = vec![];
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index).context("failed to get hunk")?;
+                let mut lines = vec![];
+                for line_index in 0..line_count {
+                    let line = patch
+                        .line_in_hunk(hunk_index, line_index)
+                        .context("failed to get line")?;
+                    let kind = match line.origin_value() {
+                        git2::DiffLineType::Context => LineKind::Context,
+                        git2::DiffLineType::Addition => LineKind::Addition,
+                        git2::DiffLineType::Deletion => LineKind::Deletion,
+                        _ => continue,
+                    };
+                    let content = String::from_utf8_lossy(line.content());
+                    lines.push(Line {
+                        kind,
+                        content: content.trim_end_matches(&['\r', '\n'][..]).to_string(),
+                        old_line_number: line.old_lineno(),
+                        new_line_number: line.new_lineno(),
+                    });
+                }
+                hunks.push(Hunk {
+                    header: String::from_utf8_lossy(hunk.header())
+                        .trim_end()
+                        .to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    lines,
+                });
+            }
+
+            files.push(FileDiff {
+                old_path,
+                new_path,
+                status,
+                binary: delta.flags().is_binary(),
+                hunks,
+            });
+        }
+
+        Ok(Self { files })
+    }
+}
+
+/// Comparison of two versions of a series of commits, similar to `git range-diff`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDiff {
+    /// Commits of both series. Commits of the new series come first in their original order,
+    /// followed by the commits that were removed from the old series.
+    pub commits: Vec<RangeDiffCommit>,
+    /// Changes from the tree of the old series head to the tree of the new series head.
+    pub diff: Diff,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RangeDiffCommit {
+    pub status: RangeDiffStatus,
+    /// Commit in the old series. `None` if the commit was added.
+    pub old: Option<radicle_git_ext::Oid>,
+    /// Commit in the new series. `None` if the commit was removed.
+    pub new: Option<radicle_git_ext::Oid>,
+    /// First line of the commit message.
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RangeDiffStatus {
+    /// Both commits introduce the same changes.
+    Unchanged,
+    /// Both commits have the same summary but introduce different changes.
+    Modified,
+    Added,
+    Removed,
+}
+
+impl RangeDiff {
+    /// Compare the series of commits from `upstream` to `old` with the series of commits from
+    /// `upstream` to `new`. Each series starts at its merge base with `upstream`.
+    ///
+    /// Commits are matched by their patch ID. Commits with different patch IDs are matched by
+    /// their summary.
+    pub fn between(
+        repo: &git2::Repository,
+        upstream: git2::Oid,
+        old: git2::Oid,
+        new: git2::Oid,
+    ) -> anyhow::Result<Self> {
+        let mut old_series = series(repo, upstream, old)?;
+        let new_series = series(repo, upstream, new)?;
+
+        let mut commits = vec![];
+        for new_commit in new_series {
+            let matching_patch_id = old_series
+                .iter()
+                .position(|old_commit| old_commit.patch_id == new_commit.patch_id);
+            let (status, position) = match matching_patch_id {
+                Some(position) => (RangeDiffStatus::Unchanged, Some(position)),
+                None => (
+                    RangeDiffStatus::Modified,
+                    old_series
+                        .iter()
+                        .position(|old_commit| old_commit.summary == new_commit.summary),
+                ),
+            };
+            let entry = match position {
+                Some(position) => {
+                    let old_commit = old_series.remove(position);
+                    RangeDiffCommit {
+                        status,
+                        old: Some(old_commit.id.into()),
+                        new: Some(new_commit.id.into()),
+                        summary: new_commit.summary,
+                    }
+                },
+                None => RangeDiffCommit {
+                    status: RangeDiffStatus::Added,
+                    old: None,
+                    new: Some(new_commit.id.into()),
+                    summary: new_commit.summary,
+                },
+            };
+            commits.push(entry);
+        }
+        commits.extend(old_series.into_iter().map(|old_commit| RangeDiffCommit {
+            status: RangeDiffStatus::Removed,
+            old: Some(old_commit.id.into()),
+            new: None,
+            summary: old_commit.summary,
+        }));
+
+        Ok(Self {
+            commits,
+            diff: Diff::between_commits(repo, Some(old), new)?,
+        })
+    }
+}
+
+/// Commit that is part of a series compared by [`RangeDiff`].
+struct SeriesCommit {
+    id: git2::Oid,
+    summary: String,
+    /// Patch ID of the changes introduced by the commit relative to its first parent.
+    patch_id: git2::Oid,
+}
+
+/// Returns the commits reachable from `head` but not from `upstream` in topological order, oldest
+/// first.
+fn series(
+    repo: &git2::Repository,
+    upstream: git2::Oid,
+    head: git2::Oid,
+) -> anyhow::Result<Vec<SeriesCommit>> {
+    let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+        .context("failed to set revwalk sorting")?;
+    revwalk
+        .push(head)
+        .context(format!("failed to push commit {head} to revwalk"))?;
+    match repo.merge_base(upstream, head) {
+        Ok(merge_base) => revwalk
+            .hide(merge_base)
+            .context("failed to hide merge base")?,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => {},
+        Err(err) => return Err(err).context("failed to determine merge base for commits"),
+    }
+
+    let mut commits = vec![];
+    for oid_result in revwalk {
+        let id = oid_result.context("failed to get commit from revwalk")?;
+        let commit = repo
+            .find_commit(id)
+            .context(format!("failed to find commit {id}"))?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().context("failed to get commit tree")?),
+            Err(_) => None,
+        };
+        let tree = commit.tree().context("failed to get commit tree")?;
+        let patch_id = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .context("failed to diff trees")?
+            .patchid(None)
+            .context("failed to compute patch ID")?;
+        commits.push(SeriesCommit {
+            id,
+            summary: String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default())
+                .to_string(),
+            patch_id,
+        });
+    }
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::commit;
+    use pretty_assertions::assert_eq;
+
+    fn file_summary(diff: &Diff) -> Vec<(FileStatus, Option<&str>, Option<&str>)> {
+        let mut summary = diff
+            .files
+            .iter()
+            .map(|file| {
+                (
+                    file.status,
+                    file.old_path.as_deref(),
+                    file.new_path.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        summary.sort_by_key(|(_, old_path, new_path)| new_path.or(*old_path));
+        summary
+    }
+
+    #[test]
+    fn diff_between_commits() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(temp_dir.path()).unwrap();
+
+        let base = commit(
+            &repo,
+            &[],
+            "base",
+            &[
+                ("modified", Some("one\ntwo\nthree\n")),
+                ("deleted", Some("deleted\n")),
+                ("renamed", Some("renamed\n")),
+            ],
+        );
+        let head = commit(
+            &repo,
+            &[base],
+            "head",
+            &[
+                ("modified", Some("one\n2\nthree\n")),
+                ("deleted", None),
+                ("renamed", None),
+                ("renamed-new", Some("renamed\n")),
+                ("added", Some("added\n")),
+            ],
+        );
+
+        let diff = Diff::between_commits(&repo, Some(base), head).unwrap();
+        assert_eq!(
+            file_summary(&diff),
+            vec![
+                (FileStatus::Added, None, Some("added")),
+                (FileStatus::Deleted, Some("deleted"), None),
+                (FileStatus::Modified, Some("modified"), Some("modified")),
+                (FileStatus::Renamed, Some("renamed"), Some("renamed-new")),
+            ]
+        );
+
+        let modified = diff
+            .files
+            .iter()
+            .find(|file| file.status == FileStatus::Modified)
+            .unwrap();
+        assert_eq!(modified.hunks.len(), 1);
+        let lines = modified.hunks[0]
+            .lines
+            .iter()
+            .map(|line| {
+                (
+                    line.kind,
+                    line.content.as_str(),
+                    line.old_line_number,
+                    line.new_line_number,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (LineKind::Context, "one", Some(1), Some(1)),
+                (LineKind::Deletion, "two", Some(2), None),
+                (LineKind::Addition, "2", None, Some(2)),
+                (LineKind::Context, "three", Some(3), Some(3)),
+            ]
+        );
+
+        assert_eq!(
+            diff.stats(),
+            DiffStats {
+                files_changed: 4,
+                additions: 2,
+                deletions: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn diff_without_old_commit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(temp_dir.path()).unwrap();
+
+        let head = commit(
+            &repo,
+            &[],
+            "head",
+            &[("a", Some("a\n")), ("b", Some("b\n"))],
+        );
+
+        let diff = Diff::between_commits(&repo, None, head).unwrap();
+        assert_eq!(
+            file_summary(&diff),
+            vec![
+                (FileStatus::Added, None, Some("a")),
+                (FileStatus::Added, None, Some("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn range_diff_of_rebased_series() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(temp_dir.path()).unwrap();
+
+        let base = commit(&repo, &[], "base", &[("a", Some("a\n"))]);
+        let old_1 = commit(&repo, &[base], "add b", &[("b", Some("b\n"))]);
+        let old_2 = commit(&repo, &[old_1], "change a", &[("a", Some("a1\n"))]);
+        let old_3 = commit(&repo, &[old_2], "add c", &[("c", Some("c\n"))]);
+
+        let upstream = commit(&repo, &[base], "upstream", &[("z", Some("z\n"))]);
+        let new_1 = commit(&repo, &[upstream], "add b", &[("b", Some("b\n"))]);
+        let new_2 = commit(&repo, &[new_1], "change a", &[("a", Some("a2\n"))]);
+        let new_3 = commit(&repo, &[new_2], "add d", &[("d", Some("d\n"))]);
+
+        let range_diff = RangeDiff::between(&repo, upstream, old_3, new_3).unwrap();
+        let commits = range_diff
+            .commits
+            .iter()
+            .map(|commit| {
+                (
+                    commit.status,
+                    commit.old.map(git2::Oid::from),
+                    commit.new.map(git2::Oid::from),
+                    commit.summary.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commits,
+            vec![
+                (
+                    RangeDiffStatus::Unchanged,
+                    Some(old_1),
+                    Some(new_1),
+                    "add b"
+                ),
+                (
+                    RangeDiffStatus::Modified,
+                    Some(old_2),
+                    Some(new_2),
+                    "change a"
+                ),
+                (RangeDiffStatus::Added, None, Some(new_3), "add d"),
+                (RangeDiffStatus::Removed, Some(old_3), None, "add c"),
+            ]
+        );
+        assert_eq!(
+            file_summary(&range_diff.diff),
+            vec![
+                (FileStatus::Modified, Some("a"), Some("a")),
+                (FileStatus::Deleted, Some("c"), None),
+                (FileStatus::Added, None, Some("d")),
+                (FileStatus::Added, None, Some("z")),
+            ]
+        );
+    }
+}
@@ -7,6 +7,11 @@
 //! Service for reading and publishing events to the event log.
 
 use anyhow::Context as _;
+use futures::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Envelope {
@@ -16,6 +21,14 @@ pub struct Envelope {
     pub identity: radicle_git_ext::Oid,
     pub topic: String,
     pub event: Event,
+    /// Commit time of the event in milliseconds since the Unix epoch. This is only set for
+    /// envelopes that have been read from the event log and is never written to the log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// Commit that holds the event. Like [`Envelope::timestamp`] this is only set for envelopes
+    /// that have been read from the event log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<radicle_git_ext::Oid>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -24,12 +37,191 @@ pub struct Event {
     pub data: serde_json::Value,
 }
 
+/// Result of reading an event log.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Events {
+    /// Valid events. See [`EventLog::get`] for the ordering.
+    pub events: Vec<Envelope>,
+    /// Events that were skipped because they are malformed.
+    pub skipped: Vec<SkippedEvent>,
+    /// Event logs of individual peers that failed verification. None of the events from these
+    /// logs are included in [`Events::events`].
+    pub invalid_logs: Vec<InvalidLog>,
+}
+
+/// Parameters to filter and paginate the events returned by [`EventLog::query`].
+///
+/// The `before` and `after` cursors refer to the position of events in the order returned by
+/// [`EventLog::get`].
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Query {
+    /// Maximum number of events to return. If `after` is set, the events closest to `after` are
+    /// returned.
+    pub limit: Option<usize>,
+    /// Only return events positioned after the event held by this commit, that is older events.
+    pub before: Option<radicle_git_ext::Oid>,
+    /// Only return events positioned before the event held by this commit, that is newer events.
+    pub after: Option<radicle_git_ext::Oid>,
+    /// Only return events of this type.
+    pub r#type: Option<String>,
+    /// Only return events authored by this peer.
+    pub peer_id: Option<librad::PeerId>,
+}
+
+impl Query {
+    fn matches(&self, envelope: &Envelope) -> bool {
+        self.r#type
+            .as_ref()
+            .map_or(true, |event_type| &envelope.event.r#type == event_type)
+            && self
+                .peer_id
+                .map_or(true, |peer_id| envelope.peer_id == peer_id)
+    }
+}
+
+/// Summary of an event log topic returned by [`EventLog::topics`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicSummary {
+    pub topic: String,
+    /// Number of valid events in the topic.
+    pub event_count: usize,
+}
+
+/// Event log of a single peer that failed verification.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InvalidLog {
+    /// Name of the reference pointing to the head of the event log.
+    pub reference: String,
+    /// Reason why the event log is invalid.
+    pub reason: String,
+}
+
+/// Event that was skipped when reading an event log.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SkippedEvent {
+    /// Commit that holds the event.
+    pub commit: radicle_git_ext::Oid,
+    /// Reason why the event was skipped.
+    pub reason: String,
+}
+
+/// Error returned by [`EventLog::query`] when the `before` or `after` cursor does not refer to an
+/// event in the event log.
+#[derive(Debug, thiserror::Error)]
+#[error("event {0} not found in event log")]
+pub struct CursorNotFound(pub radicle_git_ext::Oid);
+
+/// Error returned when an event does not match the schema of the topic it is published to.
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidEvent {
+    #[error("unknown event type `{event_type}` for topic `{topic}`")]
+    UnknownType { topic: String, event_type: String },
+    #[error("invalid data for event type `{event_type}`")]
+    InvalidData {
+        event_type: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Typed events of a [`TopicFamily`].
+///
+/// The serialized representation of a schema value must be compatible with [`Event`].
+pub trait Schema: serde::de::DeserializeOwned {
+    /// Names of all event types that belong to the schema.
+    const TYPES: &'static [&'static str];
+}
+
+/// Families of topics that have a known event [`Schema`]. Events in topics that do not belong to
+/// a known family are not validated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicFamily {
+    /// Topics of the form `patch/<peer id>/<patch name>` holding [`crate::patch::Event`]s.
+    Patch,
+}
+
+impl TopicFamily {
+    /// Returns the family `topic` belongs to.
+    pub fn of(topic: &str) -> Option<Self> {
+        match topic.split_once('/') {
+            Some(("patch", _)) => Some(Self::Patch),
+            _ => None,
+        }
+    }
+
+    fn validate(self, topic: &str, event: &Event) -> Result<(), InvalidEvent> {
+        match self {
+            Self::Patch => parse::<crate::patch::Event>(topic, event).map(|_| ()),
+        }
+    }
+}
+
+/// Check that `event` matches the schema of the family `topic` belongs to.
+pub fn validate_event(topic: &str, event: &Event) -> Result<(), InvalidEvent> {
+    match TopicFamily::of(topic) {
+        Some(family) => family.validate(topic, event),
+        None => Ok(()),
+    }
+}
+
+/// Parse `event` from `topic` into the typed event `T`.
+pub fn parse<T: Schema>(topic: &str, event: &Event) -> Result<T, InvalidEvent> {
+    if !T::TYPES.contains(&event.r#type.as_str()) {
+        return Err(InvalidEvent::UnknownType {
+            topic: topic.to_string(),
+            event_type: event.r#type.clone(),
+        });
+    }
+
+    let value = serde_json::to_value(event).expect("failed to serialize event");
+    serde_json::from_value(value).map_err(|source| InvalidEvent::InvalidData {
+        event_type: event.r#type.clone(),
+        source,
+    })
+}
+
 #[derive(Clone)]
 pub struct EventLog {
     peer: crate::peer::Peer,
     git_fetch: crate::git_fetch::Handle,
     /// Semaphore map for serializing updates to events logs by namespace and topic.
     ref_semaphores: crate::semaphore_map::SemaphoreMap<(radicle_git_ext::Oid, String)>,
+    cache: ReadCache,
+}
+
+/// Results of reading event logs that only depend on the commits the log references point to.
+#[derive(Clone, Default)]
+struct ReadCache {
+    /// Most recent verified commit of every event log by reference name. When a log is updated,
+    /// only the commits that are not reachable from this commit need to be verified again.
+    verified_tips: Arc<parking_lot::Mutex<HashMap<String, git2::Oid>>>,
+    /// Number of valid events of a topic by identity and topic together with the targets of the
+    /// topic’s event log references the count was computed for.
+    event_counts: Arc<parking_lot::Mutex<HashMap<(radicle_git_ext::Oid, String), TopicCount>>>,
+}
+
+struct TopicCount {
+    log_tips: Vec<(String, Option<git2::Oid>)>,
+    event_count: usize,
+}
+
+impl ReadCache {
+    /// Like [`verify_log`] but skips the commits that have already been verified.
+    fn verify_log(
+        &self,
+        repo: &git2::Repository,
+        ref_name: &str,
+        local_peer_id: librad::PeerId,
+        identity: radicle_git_ext::Oid,
+    ) -> anyhow::Result<git2::Oid> {
+        let verified_tip = self.verified_tips.lock().get(ref_name).copied();
+        let tip = verify_log(repo, ref_name, local_peer_id, identity, verified_tip)?;
+        self.verified_tips.lock().insert(ref_name.to_string(), tip);
+        Ok(tip)
+    }
 }
 
 impl EventLog {
@@ -38,6 +230,7 @@ impl EventLog {
             peer,
             git_fetch,
             ref_semaphores: crate::semaphore_map::SemaphoreMap::new(),
+            cache: ReadCache::default(),
         }
     }
 
@@ -49,29 +242,159 @@ impl EventLog {
     /// In addition, if we assume that commit timestamps are monotonic, we are guaranteed that if
     /// event A has a more recent timestamp than event B, then event A is positioned before B in the
     /// returned vector.
+    ///
+    /// Malformed events are not returned but reported in [`Events::skipped`]. Event logs that
+    /// fail verification are reported in [`Events::invalid_logs`]. See [`verify_log`].
     pub async fn get(
         &self,
         identity: radicle_git_ext::Oid,
         topic: String,
-    ) -> anyhow::Result<Vec<Envelope>> {
+    ) -> anyhow::Result<Events> {
+        self.query(identity, topic, Query::default()).await
+    }
+
+    /// Like [`EventLog::get`] but only returns the events selected by `query`.
+    ///
+    /// Returns a [`CursorNotFound`] error if `query.before` or `query.after` does not refer to an
+    /// event in the event log.
+    pub async fn query(
+        &self,
+        identity: radicle_git_ext::Oid,
+        topic: String,
+        query: Query,
+    ) -> anyhow::Result<Events> {
+        let local_peer_id = self.peer.librad_peer().peer_id();
+        let cache = self.cache.clone();
         self.peer
-            .monorepo_unblock(move |repo| read(&repo, identity, &topic))
+            .monorepo_unblock(move |repo| {
+                read(&repo, &cache, local_peer_id, identity, &topic, &query)
+            })
             .await
     }
 
+    /// List all topics for the given identity that we have events for, ordered by topic name.
+    pub async fn topics(
+        &self,
+        identity: radicle_git_ext::Oid,
+    ) -> anyhow::Result<Vec<TopicSummary>> {
+        let local_peer_id = self.peer.librad_peer().peer_id();
+        let cache = self.cache.clone();
+        self.peer
+            .monorepo_unblock(move |repo| {
+                let mut log_tips = std::collections::BTreeMap::<_, Vec<_>>::new();
+                for log_ref_name in log_ref_names(&repo, identity, "*")? {
+                    let topic = topic_of_ref(&log_ref_name)
+                        .context(format!("invalid event log reference {log_ref_name}"))?
+                        .to_string();
+                    let tip = repo.refname_to_id(&log_ref_name).ok();
+                    log_tips.entry(topic).or_default().push((log_ref_name, tip));
+                }
+
+                log_tips
+                    .into_iter()
+                    .map(|(topic, mut log_tips)| {
+                        log_tips.sort();
+                        let key = (identity, topic.clone());
+                        if let Some(count) = cache.event_counts.lock().get(&key) {
+                            if count.log_tips == log_tips {
+                                return Ok(TopicSummary {
+                                    topic,
+                                    event_count: count.event_count,
+                                });
+                            }
+                        }
+
+                        let events = read(
+                            &repo,
+                            &cache,
+                            local_peer_id,
+                            identity,
+                            &topic,
+                            &Query::default(),
+                        )?;
+                        let event_count = events.events.len();
+                        cache.event_counts.lock().insert(
+                            key,
+                            TopicCount {
+                                log_tips,
+                                event_count,
+                            },
+                        );
+                        Ok(TopicSummary { topic, event_count })
+                    })
+                    .collect()
+            })
+            .await
+    }
+
+    /// Stream events for the given identity and topic as they are added to the event log.
+    ///
+    /// The event log is read again whenever `updates` emits an item. New events are emitted in
+    /// chronological order. The stream ends when `updates` ends.
+    ///
+    /// If `since` is `None`, only events added after calling this function are emitted. Otherwise,
+    /// all events that are newer than the event held by the commit `since` are emitted first. If
+    /// `since` is not part of the event log, all events are emitted.
+    pub async fn watch(
+        &self,
+        identity: radicle_git_ext::Oid,
+        topic: String,
+        since: Option<radicle_git_ext::Oid>,
+        updates: impl Stream<Item = ()> + Send + 'static,
+    ) -> anyhow::Result<impl Stream<Item = Envelope> + Send + 'static> {
+        let events = self.get(identity, topic.clone()).await?.events;
+        let seen_from = match since {
+            None => 0,
+            Some(since) => events
+                .iter()
+                .position(|envelope| envelope.commit == Some(since))
+                .unwrap_or(events.len()),
+        };
+        let mut seen = events[seen_from..]
+            .iter()
+            .filter_map(|envelope| envelope.commit)
+            .collect::<HashSet<_>>();
+        let initial = take_unseen(events, &mut seen);
+
+        let event_log = self.clone();
+        let updated = async_stream::stream! {
+            futures::pin_mut!(updates);
+            while updates.next().await.is_some() {
+                match event_log.get(identity, topic.clone()).await {
+                    Ok(events) => {
+                        for envelope in take_unseen(events.events, &mut seen) {
+                            yield envelope;
+                        }
+                    },
+                    Err(err) => {
+                        tracing::warn!(?err, %identity, %topic, "failed to read event log");
+                    },
+                }
+            }
+        };
+
+        Ok(stream::iter(initial).chain(updated))
+    }
+
     /// Write an event for this peer for the given identity and topic. Then, publish the event by
     /// pushing it to the identity’s Git seed if one is known.
+    ///
+    /// Returns an [`InvalidEvent`] error if the event does not match the schema of the topic.
     pub async fn publish(
         &self,
         identity: radicle_git_ext::Oid,
         topic: &str,
         event: Event,
     ) -> anyhow::Result<()> {
+        validate_event(topic, &event)?;
+
         let envelope = Envelope {
             peer_id: self.peer.librad_peer().peer_id(),
             identity,
             topic: topic.to_string(),
             event,
+            timestamp: None,
+            commit: None,
         };
 
         let ref_semaphore = self
@@ -114,50 +437,117 @@ impl EventLog {
 /// Validates all event logs belonging to this identity and returns the reference names of invalid
 /// event logs.
 ///
-/// The following things are validated
-/// * Every commit message contains a properly encoded event envelope.
-/// * Every commit is properly signed by the peer specified in the event envelope.
-/// * Every commit without a parent is holds an `init` event.
+/// See [`verify_log`] for what is validated.
 pub fn validate(
+    repo: &git2::Repository,
+    local_peer_id: librad::PeerId,
+    identity: radicle_git_ext::Oid,
+) -> anyhow::Result<Vec<String>> {
+    let mut invalid_refs = vec![];
+    for ref_name in log_ref_names(repo, identity, "*")? {
+        if let Err(err) = verify_log(repo, &ref_name, local_peer_id, identity, None) {
+            tracing::warn!(?err, %ref_name, "failed to validate event log ref");
+            invalid_refs.push(ref_name)
+        };
+    }
+
+    Ok(invalid_refs)
+}
+
+/// Returns the names of all event log references for `identity` and `topic`, including our own
+/// and the ones of all remotes. `topic` may be a glob pattern.
+fn log_ref_names(
     repo: &git2::Repository,
     identity: radicle_git_ext::Oid,
+    topic: &str,
 ) -> anyhow::Result<Vec<String>> {
     let remote_refs = repo
-        .references_glob(&ref_name(identity, "*", Some("*")))
+        .references_glob(&ref_name(identity, topic, Some("*")))
         .context("failed to list refs")?;
     let own_refs = repo
-        .references_glob(&ref_name(identity, "*", None))
+        .references_glob(&ref_name(identity, topic, None))
         .context("failed to list refs")?;
-    let refs = remote_refs.chain(own_refs);
-    let mut invalid_refs = vec![];
-    for result in refs {
+    let mut ref_names = vec![];
+    for result in remote_refs.chain(own_refs) {
         let reference = result.context("failed to get next reference")?;
         let ref_name = std::str::from_utf8(reference.name_bytes())
             .context("reference name is not valid UTF-8")?;
-        if let Err(err) = validate_ref(repo, ref_name) {
-            tracing::warn!(?err, %ref_name, "failed to validate event log ref");
-            invalid_refs.push(ref_name.to_string())
-        };
+        ref_names.push(ref_name.to_string());
     }
-
-    Ok(invalid_refs)
+    Ok(ref_names)
 }
 
-/// Validate all commits reachable from the commit referenced by `ref_name`.
-fn validate_ref(repo: &git2::Repository, ref_name: &str) -> anyhow::Result<()> {
+/// Verify all commits reachable from the commit referenced by `ref_name` except the commits
+/// reachable from `verified_tip`. Returns the commit referenced by `ref_name`.
+///
+/// The following things are verified
+/// * Every commit message contains a properly encoded event envelope.
+/// * Every commit is properly signed by the peer specified in the event envelope.
+/// * The peer specified in every event envelope is the peer that owns the event log. For remote
+///   event logs this is the peer from the reference namespace. For our own event log this is
+///   `local_peer_id`.
+fn verify_log(
+    repo: &git2::Repository,
+    ref_name: &str,
+    local_peer_id: librad::PeerId,
+    identity: radicle_git_ext::Oid,
+    verified_tip: Option<git2::Oid>,
+) -> anyhow::Result<git2::Oid> {
+    let log_owner = remote_of_ref(ref_name, identity)?.unwrap_or(local_peer_id);
+    let tip = repo
+        .refname_to_id(ref_name)
+        .context(format!("failed to resolve reference {ref_name}"))?;
+    if verified_tip == Some(tip) {
+        return Ok(tip);
+    }
+
     let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
     revwalk
-        .push_ref(ref_name)
+        .push(tip)
         .context("failed to push reference to revwalk")?;
+    if let Some(verified_tip) = verified_tip {
+        // The verified commit may have been removed if the log was rewritten. In that case we
+        // verify the whole log.
+        if let Err(err) = revwalk.hide(verified_tip) {
+            tracing::debug!(?err, %ref_name, "failed to hide verified event log commits");
+        }
+    }
     for result in revwalk {
         let oid = result.context("failed to get next commit")?;
-        validate_commit(repo, oid)?;
+        let envelope = validate_commit(repo, oid)?;
+        if envelope.peer_id != log_owner {
+            anyhow::bail!(
+                "event {oid} authored by {} is stored in event log of {log_owner}",
+                envelope.peer_id
+            );
+        }
     }
-    Ok(())
+    Ok(tip)
 }
 
-/// Validate a commit that carries an event.
-fn validate_commit(repo: &git2::Repository, oid: git2::Oid) -> anyhow::Result<()> {
+/// Returns the peer of an event log reference in a remote namespace. Returns `None` if the
+/// reference is not in a remote namespace.
+fn remote_of_ref(
+    ref_name: &str,
+    identity: radicle_git_ext::Oid,
+) -> anyhow::Result<Option<librad::PeerId>> {
+    let namespace = librad::git::Urn::new(identity).encode_id();
+    let remotes_prefix = format!("refs/namespaces/{namespace}/refs/remotes/");
+    match ref_name.strip_prefix(&remotes_prefix) {
+        Some(remote_ref_name) => {
+            let (peer_id, _) = remote_ref_name
+                .split_once('/')
+                .context("invalid remote reference name")?;
+            let peer_id = librad::PeerId::from_default_encoding(peer_id)
+                .context(format!("invalid peer ID in reference name {ref_name}"))?;
+            Ok(Some(peer_id))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Validate a commit that carries an event and return the event envelope.
+fn validate_commit(repo: &git2::Repository, oid: git2::Oid) -> anyhow::Result<Envelope> {
     let commit = repo
         .find_commit(oid)
         .context(format!("commit {oid} not found when walking revs"))?;
@@ -177,14 +567,15 @@ fn validate_commit(repo: &git2::Repository, oid: git2::Oid) -> anyhow::Result<()
     };
 
     if !signature.verify(&signed, envelope.peer_id.as_public_key()) {
-        anyhow::bail!("signature could not be verified");
+        anyhow::bail!("signature of {oid} could not be verified");
     }
 
-    Ok(())
+    Ok(envelope)
 }
 
 /// Create a commit signed with the `radicle-ed2551` signature scheme and update the given
-/// reference.
+/// reference. Fails if the reference does not point to the first of `parents` or, if there are no
+/// parents, if the reference exists.
 fn commit_signed<'repo>(
     repo: &'repo git2::Repository,
     signer: &link_crypto::BoxedSigner,
@@ -212,8 +603,25 @@ fn commit_signed<'repo>(
         Some("radicle-ed25519"),
     )?;
     let commit = repo.find_commit(commit_id).context("commit not found")?;
-    repo.reference(reference, commit_id, true, "update")?;
-    Ok(commit)
+
+    // Only update the reference if nobody else has updated it since we read it. Otherwise we
+    // would drop the events that were written in the meantime.
+    let result = match parents.first() {
+        Some(parent) => repo.reference_matching(reference, commit_id, true, parent.id(), "update"),
+        None => repo.reference(reference, commit_id, false, "update"),
+    };
+    match result {
+        Ok(_) => Ok(commit),
+        Err(err)
+            if matches!(
+                err.code(),
+                git2::ErrorCode::Modified | git2::ErrorCode::Exists
+            ) =>
+        {
+            Err(anyhow::Error::new(err).context(format!("{reference} was updated concurrently")))
+        },
+        Err(err) => Err(anyhow::Error::new(err).context(format!("failed to update {reference}"))),
+    }
 }
 
 /// Write an event in an envelope to the monorepo by commiting it to Git ref for the event log
@@ -267,52 +675,141 @@ fn write(
 
 /// Get all replicated events for the identity and topic. This includes our events from our own
 /// event log as well as all event logs from other peers that we replicate.
+///
+/// Event logs that fail [`verify_log`] are excluded. Events that cannot be parsed or that do not
+/// match the schema of the topic are skipped. Only events selected by `query` are returned.
+///
+/// Returns a [`CursorNotFound`] error if a cursor of `query` is not part of the event logs.
 fn read(
     repo: &git2::Repository,
+    cache: &ReadCache,
+    local_peer_id: librad::PeerId,
     identity: radicle_git_ext::Oid,
     topic: &str,
-) -> anyhow::Result<Vec<Envelope>> {
-    let my_log_ref_name = ref_name(identity, topic, None);
-    let remote_log_ref_glob = ref_name(identity, topic, Some("*"));
+    query: &Query,
+) -> anyhow::Result<Events> {
+    let mut events = Events::default();
 
     let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
     revwalk
         .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
         .context("failed to set revwalk sorting")?;
 
-    if let Err(err) = revwalk.push_ref(&my_log_ref_name) {
-        // We check the message instead of `err.code()` because the code is
-        // `git2::ErrorCode::Generic`.
-        if !err.message().ends_with("not found") {
-            return Err(anyhow::Error::new(err)
-                .context(format!("failed to push ref `{my_log_ref_name}` to revwalk")));
+    for log_ref_name in log_ref_names(repo, identity, topic)? {
+        match cache.verify_log(repo, &log_ref_name, local_peer_id, identity) {
+            // Push the verified commit instead of the reference in case the reference has been
+            // updated in the meantime.
+            Ok(tip) => revwalk
+                .push(tip)
+                .context(format!("failed to push ref `{log_ref_name}` to revwalk"))?,
+            Err(err) => {
+                tracing::warn!(?err, %log_ref_name, "skipping invalid event log");
+                events.invalid_logs.push(InvalidLog {
+                    reference: log_ref_name,
+                    reason: format!("{err:#}"),
+                });
+            },
         }
     }
-    revwalk.push_glob(&remote_log_ref_glob).context(format!(
-        "failed to push glob `{remote_log_ref_glob}` to revwalk"
-    ))?;
 
-    let envelopes = revwalk.filter_map(|oid_result| -> Option<anyhow::Result<_>> {
-        let go = || -> anyhow::Result<Option<_>> {
-            let oid = oid_result.context("failed to get commit from revwalk")?;
-            let commit = repo
-                .find_commit(oid)
-                .context(format!("commit {oid} not found when walking revs"))?;
+    // If `query.before` is set we skip all events until we encounter the cursor.
+    let mut before_cursor_seen = query.before.is_none();
+    let mut after_cursor_seen = query.after.is_none();
+    for oid_result in revwalk {
+        let oid = oid_result.context("failed to get commit from revwalk")?;
 
-            let envelope = envelope_from_message(commit.message_bytes()).context(format!(
-                "failed to parse commit message as envelope for {oid}"
-            ))?;
+        if query.after.map_or(false, |after| *after == oid) {
+            after_cursor_seen = true;
+            break;
+        }
+        if !before_cursor_seen {
+            before_cursor_seen = query.before.map_or(false, |before| *before == oid);
+            continue;
+        }
+        if query.after.is_none()
+            && query
+                .limit
+                .map_or(false, |limit| events.events.len() >= limit)
+        {
+            break;
+        }
 
-            if envelope.identity == identity && envelope.topic == topic {
-                Ok(Some(envelope))
-            } else {
-                Ok(None)
-            }
+        let commit = repo
+            .find_commit(oid)
+            .context(format!("commit {oid} not found when walking revs"))?;
+
+        let mut envelope = match envelope_from_message(commit.message_bytes()) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                tracing::warn!(?err, %oid, "skipping malformed event");
+                events.skipped.push(SkippedEvent {
+                    commit: oid.into(),
+                    reason: format!("{err:#}"),
+                });
+                continue;
+            },
         };
+        envelope.timestamp = Some(commit_timestamp(&commit));
+        envelope.commit = Some(oid.into());
 
-        go().transpose()
-    });
-    envelopes.collect::<Result<Vec<_>, _>>()
+        if envelope.identity != identity || envelope.topic != topic {
+            continue;
+        }
+
+        if let Err(err) = validate_event(topic, &envelope.event) {
+            let err = anyhow::Error::new(err);
+            tracing::warn!(?err, %oid, "skipping invalid event");
+            events.skipped.push(SkippedEvent {
+                commit: oid.into(),
+                reason: format!("{err:#}"),
+            });
+            continue;
+        }
+
+        if query.matches(&envelope) {
+            events.events.push(envelope);
+        }
+    }
+
+    if let (Some(before), false) = (query.before, before_cursor_seen) {
+        return Err(CursorNotFound(before).into());
+    }
+    if let (Some(after), false) = (query.after, after_cursor_seen) {
+        return Err(CursorNotFound(after).into());
+    }
+
+    if let (Some(limit), Some(_)) = (query.limit, query.after) {
+        let excess = events.events.len().saturating_sub(limit);
+        events.events.drain(..excess);
+    }
+
+    Ok(events)
+}
+
+/// Returns the topic of an event log reference.
+fn topic_of_ref(ref_name: &str) -> Option<&str> {
+    ref_name
+        .split_once(&format!("/{REF_PREFIX}/"))
+        .map(|(_, topic)| topic)
+}
+
+/// Returns the envelopes from `events` that are not in `seen` in chronological order and adds
+/// them to `seen`. `events` must be ordered as returned by [`EventLog::get`].
+fn take_unseen(events: Vec<Envelope>, seen: &mut HashSet<radicle_git_ext::Oid>) -> Vec<Envelope> {
+    let mut unseen = events
+        .into_iter()
+        .filter(|envelope| match envelope.commit {
+            Some(commit) => seen.insert(commit),
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    unseen.reverse();
+    unseen
+}
+
+/// Returns the commit time of `commit` in milliseconds since the Unix epoch.
+fn commit_timestamp(commit: &git2::Commit) -> u64 {
+    u64::try_from(commit.time().seconds()).unwrap_or_default() * 1000
 }
 
 /// Prefix for event log references excluding the leading `refs/`
@@ -394,6 +891,8 @@ mod test {
                 r#type: "foo".to_string(),
                 data: serde_json::json!({}),
             },
+            timestamp: None,
+            commit: None,
         })
         .unwrap();
 
@@ -426,7 +925,7 @@ mod test {
         let topic = "asdf";
 
         let events = event_log.get(identity, topic.to_string()).await.unwrap();
-        assert!(events.is_empty());
+        assert!(events.events.is_empty());
 
         let events_to_publish = (1..10u32)
             .map(|data| Event {
@@ -446,6 +945,7 @@ mod test {
             .get(identity, topic.to_string())
             .await
             .unwrap()
+            .events
             .into_iter()
             .map(|envelope| envelope.event)
             .rev()
@@ -453,10 +953,14 @@ mod test {
 
         assert_eq!(events, events_to_publish);
 
+        let local_peer_id = test_peer.peer.librad_peer().peer_id();
         test_peer
             .peer
             .monorepo_unblock(move |repo| {
-                assert_eq!(validate(&repo, identity).unwrap(), Vec::<String>::new());
+                assert_eq!(
+                    validate(&repo, local_peer_id, identity).unwrap(),
+                    Vec::<String>::new()
+                );
                 Ok(())
             })
             .await
@@ -496,22 +1000,565 @@ mod test {
             .get(identity, topic.to_string())
             .await
             .unwrap()
+            .events
             .into_iter()
             .map(|envelope| envelope.event)
             .collect::<Vec<_>>();
 
         assert_eq!(events, events_to_publish);
 
+        let local_peer_id = test_peer.peer.librad_peer().peer_id();
         test_peer
             .peer
             .monorepo_unblock(move |repo| {
-                assert_eq!(validate(&repo, identity).unwrap(), Vec::<String>::new());
+                assert_eq!(
+                    validate(&repo, local_peer_id, identity).unwrap(),
+                    Vec::<String>::new()
+                );
                 Ok(())
             })
             .await
             .unwrap()
     }
 
+    #[tokio::test]
+    async fn publish_invalid() {
+        let (event_log, _test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "patch/foo/bar";
+
+        let unknown_type = Event {
+            r#type: "foo".to_string(),
+            data: serde_json::json!({}),
+        };
+        let err = event_log
+            .publish(identity, topic, unknown_type)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvalidEvent>(),
+            Some(InvalidEvent::UnknownType { .. })
+        ));
+
+        let invalid_data = Event {
+            r#type: "setStatus".to_string(),
+            data: serde_json::json!({ "status": "foo" }),
+        };
+        let err = event_log
+            .publish(identity, topic, invalid_data)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InvalidEvent>(),
+            Some(InvalidEvent::InvalidData { .. })
+        ));
+
+        let valid = Event {
+            r#type: "setStatus".to_string(),
+            data: serde_json::json!({ "status": "closed" }),
+        };
+        event_log.publish(identity, topic, valid).await.unwrap();
+    }
+
+    /// Assert that events that don’t match the topic schema are skipped when reading.
+    #[tokio::test]
+    async fn read_skips_invalid() {
+        let (event_log, test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "patch/foo/bar";
+
+        let valid = Event {
+            r#type: "setStatus".to_string(),
+            data: serde_json::json!({ "status": "closed" }),
+        };
+        event_log
+            .publish(identity, topic, valid.clone())
+            .await
+            .unwrap();
+
+        // Bypass validation by writing the event directly
+        let envelope = Envelope {
+            peer_id: test_peer.peer.librad_peer().peer_id(),
+            identity,
+            topic: topic.to_string(),
+            event: Event {
+                r#type: "setStatus".to_string(),
+                data: serde_json::json!({ "status": 1 }),
+            },
+            timestamp: None,
+            commit: None,
+        };
+        test_peer
+            .peer
+            .monorepo_unblock({
+                let signer = test_peer.peer.librad_peer().signer().clone();
+                move |repo| write(&repo, &signer, identity, topic, &envelope)
+            })
+            .await
+            .unwrap();
+
+        let events = event_log.get(identity, topic.to_string()).await.unwrap();
+        assert_eq!(
+            events
+                .events
+                .into_iter()
+                .map(|envelope| envelope.event)
+                .collect::<Vec<_>>(),
+            vec![valid]
+        );
+        assert_eq!(events.skipped.len(), 1);
+    }
+
+    /// Assert that event logs with forged signatures or events from the wrong peer are reported
+    /// as invalid and their events are not returned.
+    #[tokio::test]
+    async fn read_rejects_invalid_logs() {
+        let (event_log, test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "asdf";
+        let local_peer_id = test_peer.peer.librad_peer().peer_id();
+        let forged_peer_id = librad::PeerId::from(link_crypto::SecretKey::new());
+        let other_peer_id = librad::PeerId::from(link_crypto::SecretKey::new());
+
+        // Envelope claims to be authored by `forged_peer_id` but is signed by us.
+        let forged_envelope = Envelope {
+            peer_id: forged_peer_id,
+            identity,
+            topic: topic.to_string(),
+            event: Event {
+                r#type: String::default(),
+                data: serde_json::json!(1),
+            },
+            timestamp: None,
+            commit: None,
+        };
+        test_peer
+            .peer
+            .monorepo_unblock({
+                let signer = test_peer.peer.librad_peer().signer().clone();
+                move |repo| {
+                    write(&repo, &signer, identity, topic, &forged_envelope)?;
+                    let mut own_ref = repo.find_reference(&ref_name(identity, topic, None))?;
+                    let target = own_ref.target().unwrap();
+                    repo.reference(
+                        &ref_name(identity, topic, Some(&forged_peer_id.to_string())),
+                        target,
+                        false,
+                        "test",
+                    )?;
+                    own_ref.delete()?;
+                    Ok(())
+                }
+            })
+            .await
+            .unwrap();
+
+        // Properly signed event stored in the log of another peer.
+        let event = Event {
+            r#type: String::default(),
+            data: serde_json::json!(2),
+        };
+        event_log
+            .publish(identity, topic, event.clone())
+            .await
+            .unwrap();
+        test_peer
+            .peer
+            .monorepo_unblock(move |repo| {
+                let target = repo
+                    .find_reference(&ref_name(identity, topic, None))?
+                    .target()
+                    .unwrap();
+                repo.reference(
+                    &ref_name(identity, topic, Some(&other_peer_id.to_string())),
+                    target,
+                    false,
+                    "test",
+                )?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        let events = event_log.get(identity, topic.to_string()).await.unwrap();
+        assert_eq!(
+            events
+                .events
+                .into_iter()
+                .map(|envelope| envelope.event)
+                .collect::<Vec<_>>(),
+            vec![event]
+        );
+        let mut invalid_logs = events
+            .invalid_logs
+            .into_iter()
+            .map(|invalid_log| invalid_log.reference)
+            .collect::<Vec<_>>();
+        invalid_logs.sort();
+        let mut expected_invalid_logs = vec![
+            ref_name(identity, topic, Some(&forged_peer_id.to_string())),
+            ref_name(identity, topic, Some(&other_peer_id.to_string())),
+        ];
+        expected_invalid_logs.sort();
+        assert_eq!(invalid_logs, expected_invalid_logs);
+
+        test_peer
+            .peer
+            .monorepo_unblock(move |repo| {
+                let mut invalid_refs = validate(&repo, local_peer_id, identity).unwrap();
+                invalid_refs.sort();
+                assert_eq!(invalid_refs, expected_invalid_logs);
+                Ok(())
+            })
+            .await
+            .unwrap()
+    }
+
+    /// Assert that events appended to an event log that has already been verified are verified
+    /// too.
+    #[tokio::test]
+    async fn read_verifies_appended_events() {
+        let (event_log, test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "asdf";
+
+        event_log
+            .publish(
+                identity,
+                topic,
+                Event {
+                    r#type: String::default(),
+                    data: serde_json::json!(1),
+                },
+            )
+            .await
+            .unwrap();
+        let events = event_log.get(identity, topic.to_string()).await.unwrap();
+        assert_eq!(events.events.len(), 1);
+        assert!(events.invalid_logs.is_empty());
+        assert_eq!(
+            event_log.topics(identity).await.unwrap(),
+            vec![TopicSummary {
+                topic: topic.to_string(),
+                event_count: 1,
+            }]
+        );
+
+        // Event authored by another peer appended to our own log.
+        let forged_envelope = Envelope {
+            peer_id: librad::PeerId::from(link_crypto::SecretKey::new()),
+            identity,
+            topic: topic.to_string(),
+            event: Event {
+                r#type: String::default(),
+                data: serde_json::json!(2),
+            },
+            timestamp: None,
+            commit: None,
+        };
+        test_peer
+            .peer
+            .monorepo_unblock({
+                let signer = test_peer.peer.librad_peer().signer().clone();
+                move |repo| write(&repo, &signer, identity, topic, &forged_envelope)
+            })
+            .await
+            .unwrap();
+
+        let events = event_log.get(identity, topic.to_string()).await.unwrap();
+        assert!(events.events.is_empty());
+        assert_eq!(
+            events
+                .invalid_logs
+                .into_iter()
+                .map(|invalid_log| invalid_log.reference)
+                .collect::<Vec<_>>(),
+            vec![ref_name(identity, topic, None)]
+        );
+        assert_eq!(
+            event_log.topics(identity).await.unwrap(),
+            vec![TopicSummary {
+                topic: topic.to_string(),
+                event_count: 0,
+            }]
+        );
+    }
+
+    /// Assert that an event log is not updated if it changed after the new event was created.
+    #[tokio::test]
+    async fn commit_signed_rejects_concurrent_update() {
+        let (event_log, test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "asdf";
+        let event = Event {
+            r#type: String::default(),
+            data: serde_json::Value::Null,
+        };
+        event_log
+            .publish(identity, topic, event.clone())
+            .await
+            .unwrap();
+
+        let peer_id = test_peer.peer.librad_peer().peer_id();
+        let signer = test_peer.peer.librad_peer().signer().clone();
+        test_peer
+            .peer
+            .monorepo_unblock(move |repo| {
+                let log_ref_name = ref_name(identity, topic, None);
+                let stale = repo.find_reference(&log_ref_name)?.peel_to_commit()?;
+                let tree = stale.tree()?;
+
+                let mut envelope = Envelope {
+                    peer_id,
+                    identity,
+                    topic: topic.to_string(),
+                    event,
+                    timestamp: None,
+                    commit: None,
+                };
+                write(&repo, &signer, identity, topic, &envelope)?;
+                let head = repo.refname_to_id(&log_ref_name)?;
+
+                envelope.event.data = serde_json::json!(1);
+                let message = envelope_to_message(&envelope);
+                assert!(
+                    commit_signed(&repo, &signer, &log_ref_name, &message, &tree, &[&stale])
+                        .is_err()
+                );
+                assert!(
+                    commit_signed(&repo, &signer, &log_ref_name, &message, &tree, &[]).is_err()
+                );
+                assert_eq!(repo.refname_to_id(&log_ref_name)?, head);
+                Ok(())
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn watch() {
+        let (event_log, _test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "asdf";
+
+        let events_to_publish = (1..4u32)
+            .map(|data| Event {
+                r#type: String::default(),
+                data: serde_json::to_value(data).unwrap(),
+            })
+            .collect::<Vec<_>>();
+
+        event_log
+            .publish(identity, topic, events_to_publish[0].clone())
+            .await
+            .unwrap();
+
+        let (updates_tx, updates_rx) = futures::channel::mpsc::unbounded();
+        let watch = event_log
+            .watch(identity, topic.to_string(), None, updates_rx)
+            .await
+            .unwrap();
+        futures::pin_mut!(watch);
+
+        event_log
+            .publish(identity, topic, events_to_publish[1].clone())
+            .await
+            .unwrap();
+        updates_tx.unbounded_send(()).unwrap();
+        let envelope = watch.next().await.unwrap();
+        assert_eq!(envelope.event, events_to_publish[1]);
+
+        // Resume after the second event
+        let (resumed_updates_tx, resumed_updates_rx) = futures::channel::mpsc::unbounded();
+        let resumed = event_log
+            .watch(
+                identity,
+                topic.to_string(),
+                envelope.commit,
+                resumed_updates_rx,
+            )
+            .await
+            .unwrap();
+        futures::pin_mut!(resumed);
+
+        event_log
+            .publish(identity, topic, events_to_publish[2].clone())
+            .await
+            .unwrap();
+        updates_tx.unbounded_send(()).unwrap();
+        resumed_updates_tx.unbounded_send(()).unwrap();
+        assert_eq!(watch.next().await.unwrap().event, events_to_publish[2]);
+        assert_eq!(resumed.next().await.unwrap().event, events_to_publish[2]);
+
+        // Resume from an unknown commit
+        let (_updates_tx, updates_rx) = futures::channel::mpsc::unbounded();
+        let all = event_log
+            .watch(
+                identity,
+                topic.to_string(),
+                Some(git2::Oid::zero().into()),
+                updates_rx,
+            )
+            .await
+            .unwrap()
+            .take(3)
+            .map(|envelope| envelope.event)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(all, events_to_publish);
+    }
+
+    #[tokio::test]
+    async fn query() {
+        let (event_log, test_peer) = new_test_event_log().await;
+
+        let identity = radicle_git_ext::Oid::from(git2::Oid::zero());
+        let topic = "asdf";
+
+        for data in 0..6u32 {
+            let r#type = if data % 2 == 0 { "even" } else { "odd" };
+            event_log
+                .publish(
+                    identity,
+                    topic,
+                    Event {
+                        r#type: r#type.to_string(),
+                        data: serde_json::to_value(data).unwrap(),
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        event_log
+            .publish(
+                identity,
+                "other/topic",
+                Event {
+                    r#type: String::default(),
+                    data: serde_json::Value::Null,
+                },
+            )
+            .await
+            .unwrap();
+
+        let query_data = |query: Query| {
+            let event_log = event_log.clone();
+            async move {
+                event_log
+                    .query(identity, topic.to_string(), query)
+                    .await
+                    .unwrap()
+                    .events
+                    .into_iter()
+                    .map(|envelope| envelope.event.data.as_u64().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let all = event_log
+            .get(identity, topic.to_string())
+            .await
+            .unwrap()
+            .events;
+        let commit = |data: u64| {
+            all.iter()
+                .find(|envelope| envelope.event.data.as_u64() == Some(data))
+                .unwrap()
+                .commit
+        };
+
+        let page = query_data(Query {
+            limit: Some(2),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page, vec![5, 4]);
+
+        let page = query_data(Query {
+            limit: Some(2),
+            before: commit(4),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page, vec![3, 2]);
+
+        let page = query_data(Query {
+            limit: Some(2),
+            after: commit(1),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page, vec![3, 2]);
+
+        let page = query_data(Query {
+            r#type: Some("odd".to_string()),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page, vec![5, 3, 1]);
+
+        let page = query_data(Query {
+            peer_id: Some(librad::PeerId::from(link_crypto::SecretKey::new())),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page, Vec::<u64>::new());
+
+        let page = query_data(Query {
+            peer_id: Some(test_peer.peer.librad_peer().peer_id()),
+            ..Query::default()
+        })
+        .await;
+        assert_eq!(page.len(), 6);
+
+        let err = event_log
+            .query(
+                identity,
+                topic.to_string(),
+                Query {
+                    before: Some(git2::Oid::zero().into()),
+                    ..Query::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<CursorNotFound>().is_some());
+
+        let err = event_log
+            .query(
+                identity,
+                topic.to_string(),
+                Query {
+                    after: Some(git2::Oid::zero().into()),
+                    ..Query::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<CursorNotFound>().is_some());
+
+        let topics = event_log.topics(identity).await.unwrap();
+        assert_eq!(
+            topics,
+            vec![
+                TopicSummary {
+                    topic: "asdf".to_string(),
+                    event_count: 6,
+                },
+                TopicSummary {
+                    topic: "other/topic".to_string(),
+                    event_count: 1,
+                },
+            ]
+        );
+    }
+
     /// Create a new `EventLog` for testing. The `TestPeer` must live until the end of the test.
     /// Otherwise, the temporary directory is destroyed.
     async fn new_test_event_log() -> (EventLog, crate::peer::test::TestPeer) {
@@ -519,6 +1566,7 @@ mod test {
         let (git_fetch, _) = crate::git_fetch::create(
             test_peer.peer.clone(),
             vec![],
+            None,
             std::time::Duration::from_secs(1000),
             &test_peer.store,
         )
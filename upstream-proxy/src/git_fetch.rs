@@ -6,36 +6,68 @@
 
 //! Service for syncing identities with Radicle Git seeds via Git+HTTPS fetch and push.
 //!
-//! For every project identity, we try to find a seed that replicates that project by querying all
-//! potential seeds. The set of potential seeds is the `seeds` argument to [`create`]. We persist
-//! the project-seed mapping to disk.
+//! For every project identity, we track the set of seeds that replicate that project. The set of
+//! potential seeds is the `seeds` argument to [`create`]. We persist the project-seeds mapping to
+//! disk.
 //!
-//! It is not guaranteed that the found seed is the only seed that replicates the project and has
-//! up-to-date data. We make no effort to get data from multiple seeds.
+//! When fetching a project we first try the seeds known to replicate it and then the remaining
+//! potential seeds. We stop once `fetch_quorum` seeds have provided the project. If no quorum is
+//! configured we fetch from all seeds. Since all seeds are fetched into the same monorepo, the
+//! results are merged. A seed that is down does not prevent us from getting updates from the
+//! other seeds.
 //!
 //! For every project stored on this peer we continously fetch updates in the background at an
-//! interval configured by the `fetch_interval` argument.
+//! interval configured by the `fetch_interval` argument. A project can also be synced on demand
+//! with [`Handle::sync`]. Projects that are not stored on this peer yet can be cloned from a seed
+//! with [`Handle::clone_project`].
+//!
+//! Whenever our own references of a project change we push our signed refs, heads and tags to the
+//! seeds that replicate the project. See [`Handle::push_contributions`]. We also push them when a
+//! project is added, so that changes made while Upstream was not running are published. If fetching
+//! a project from a seed fails, we back off exponentially before trying that seed for the project
+//! again. The outcome of every fetch is recorded in a seed health table that is persisted to disk.
 
 use anyhow::Context as _;
 use futures::prelude::*;
 use radicle_git_ext::Oid;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// Upper bound for the delay after which we retry fetching an identity from a seed that failed.
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Delay before pushing our references after a change so that bursts of changes result in a
+/// single push.
+const PUSH_DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// References of the local peer that we push to seeds for projects we contributed to. See
+/// [`push_refs`] for the format.
+const CONTRIBUTION_REFS: [&str; 3] = ["heads/*", "tags/*", "rad/signed_refs"];
+
 pub async fn create(
     peer: crate::peer::Peer,
     seeds: Vec<rad_common::Url>,
+    fetch_quorum: Option<usize>,
     fetch_interval: std::time::Duration,
     store: &kv::Store,
 ) -> anyhow::Result<(Handle, Runner)> {
     let project_seed_store =
         ProjectSeedStore::new(store).context("failed to get project seed bucket")?;
+    let seed_health_store =
+        SeedHealthStore::new(store).context("failed to get seed health bucket")?;
     let (update_tx, update_rx) = async_broadcast::broadcast(32);
     let (identity_queue, identity_rx) = UniqueDelayQueue::new();
+    let backoff = Backoff::default();
     let handle = Handle {
         peer: peer.clone(),
+        update_tx: update_tx.clone(),
         update_rx: update_rx.deactivate(),
         identity_queue: identity_queue.clone(),
+        seeds: seeds.clone(),
+        fetch_interval,
         project_seed_store: project_seed_store.clone(),
+        seed_health_store: seed_health_store.clone(),
+        backoff: backoff.clone(),
     };
 
     let projects = crate::project::list_link(&peer)
@@ -50,26 +82,46 @@ pub async fn create(
     let runner = Runner {
         peer,
         seeds,
+        fetch_quorum,
         update_tx,
         identity_rx,
         identity_queue,
         fetch_interval,
         project_seed_store,
+        seed_health_store,
+        backoff,
     };
     Ok((handle, runner))
 }
 
+/// References of an identity that changed by fetching from seeds.
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub identity: Oid,
+    /// Changed references in the namespace of the identity, including the references of remote
+    /// peers.
+    pub changes: Vec<crate::watch_monorepo::RefChange>,
+}
+
 #[derive(Clone)]
 pub struct Handle {
     peer: crate::peer::Peer,
-    update_rx: async_broadcast::InactiveReceiver<Oid>,
+    update_tx: async_broadcast::Sender<Update>,
+    update_rx: async_broadcast::InactiveReceiver<Update>,
     identity_queue: UniqueDelayQueue,
+    seeds: Vec<rad_common::Url>,
+    fetch_interval: std::time::Duration,
     project_seed_store: ProjectSeedStore,
+    seed_health_store: SeedHealthStore,
+    backoff: Backoff,
 }
 
 impl Handle {
     /// Add an identity to continuously fetch from the configured seeds. The identity will be
     /// fetched immediately after calling this function, even if it has been added before.
+    ///
+    /// Our event logs and contributions are pushed immediately, too. This publishes changes that
+    /// were made while Upstream was not running.
     pub async fn add(&self, identity: Oid) {
         self.identity_queue
             .add(
@@ -83,18 +135,222 @@ impl Handle {
                 SyncAction::FetchIdentity(identity),
                 std::time::Duration::new(0, 0),
             )
+            .await;
+
+        self.identity_queue
+            .add(
+                SyncAction::PushContributions(identity),
+                std::time::Duration::new(0, 0),
+            )
             .await
     }
 
-    /// Stream that emits the identifier of an identity whenever we’ve fetched new updates for the
-    /// identity from a seed.
-    pub fn updates(&self) -> async_broadcast::Receiver<Oid> {
+    /// Push our signed refs, heads and tags of `identity` to all seeds that replicate the identity.
+    /// Pushes are delayed shortly so that multiple calls in quick succession result in a single
+    /// push. Does nothing if we haven’t contributed to the identity.
+    pub async fn push_contributions(&self, identity: Oid) {
+        self.identity_queue
+            .add(SyncAction::PushContributions(identity), PUSH_DEBOUNCE)
+            .await
+    }
+
+    /// Stream that emits the changed references of an identity whenever we’ve fetched new updates
+    /// for the identity from a seed.
+    pub fn updates(&self) -> async_broadcast::Receiver<Update> {
         self.update_rx.activate_cloned()
     }
 
-    /// Returns the URL of a seed node that replicates `identity`.
+    /// Returns the URL of a seed node that replicates `identity`. This is the first seed we found
+    /// the identity on.
     pub fn get_seed(&self, identity: Oid) -> Option<rad_common::Url> {
-        self.project_seed_store.get(identity)
+        self.project_seed_store.get(identity).into_iter().next()
+    }
+
+    /// Returns all seeds that are known to replicate `identity`, the pinned seed first.
+    pub fn get_seeds(&self, identity: Oid) -> Vec<ProjectSeed> {
+        let pinned = self.project_seed_store.pinned(identity);
+        self.project_seed_store
+            .get(identity)
+            .into_iter()
+            .map(|url| ProjectSeed {
+                pinned: Some(&url) == pinned.as_ref(),
+                url: url.to_string(),
+            })
+            .collect()
+    }
+
+    /// Add a seed for `identity` after checking that the seed provides the identity. If `pinned`
+    /// is `true` the seed is pinned. Otherwise the seed is unpinned if it was pinned before.
+    pub async fn add_seed(
+        &self,
+        identity: Oid,
+        seed_url: rad_common::Url,
+        pinned: bool,
+    ) -> Result<(), AddSeedError> {
+        let provides = seed_provides(identity, &seed_url)
+            .await
+            .map_err(AddSeedError::Unreachable)?;
+        if !provides {
+            return Err(AddSeedError::NotProvided);
+        }
+
+        self.project_seed_store
+            .add_and_pin(identity, &seed_url, pinned);
+        Ok(())
+    }
+
+    /// Remove a seed for `identity` and unpin it if it was pinned. The seed may be added again
+    /// when it is one of the configured seeds and provides the identity.
+    pub fn remove_seed(&self, identity: Oid, seed_url: &rad_common::Url) {
+        self.project_seed_store.remove(identity, seed_url);
+        if self.project_seed_store.pinned(identity).as_ref() == Some(seed_url) {
+            self.project_seed_store.pin(identity, None);
+        }
+    }
+
+    /// Returns the health of all configured seeds and all seeds that replicate an identity.
+    pub fn seed_health(&self) -> anyhow::Result<Vec<SeedHealth>> {
+        let mut seed_health = self.seed_health_store.list()?;
+        for seed in &self.seeds {
+            if !seed_health
+                .iter()
+                .any(|health| health.seed == seed.as_str())
+            {
+                seed_health.push(SeedHealth::new(seed));
+            }
+        }
+        seed_health.sort_by(|a, b| a.seed.cmp(&b.seed));
+        Ok(seed_health)
+    }
+
+    /// Immediately fetch `identity` from all seeds that are known to replicate it and from all
+    /// configured seeds. Then push our heads, tags and event logs to every seed that provides the
+    /// identity.
+    ///
+    /// Unlike the background fetch this ignores the fetch quorum and any backoff. Returns the
+    /// outcome for every seed that was tried.
+    pub async fn sync(&self, identity: Oid) -> anyhow::Result<Vec<SeedSyncResult>> {
+        let mut seeds_to_try = self.project_seed_store.get(identity);
+        for seed in &self.seeds {
+            if !seeds_to_try.contains(seed) {
+                seeds_to_try.push(seed.clone());
+            }
+        }
+
+        let refs_before_sync = namespace_refs(&self.peer, identity).await?;
+        let mut results = vec![];
+        let mut updated = false;
+        for seed in seeds_to_try {
+            let refs_before = namespace_refs(&self.peer, identity).await?;
+            let fetch_result = fetch_from_seed(
+                &self.peer,
+                identity,
+                &seed,
+                &self.project_seed_store,
+                &self.seed_health_store,
+                &self.backoff,
+                self.fetch_interval,
+            )
+            .await;
+            let fetch = match fetch_result {
+                Ok(FetchResult::Updated) => {
+                    updated = true;
+                    let refs_after = namespace_refs(&self.peer, identity).await?;
+                    SeedFetchResult::Updated {
+                        refs: crate::watch_monorepo::ref_changes(&refs_before, &refs_after),
+                    }
+                },
+                Ok(FetchResult::UpToDate) => SeedFetchResult::UpToDate,
+                Ok(FetchResult::NotFound) => SeedFetchResult::NotFound,
+                Err(err) => SeedFetchResult::Error {
+                    message: format!("{err:#}"),
+                },
+            };
+
+            let push = match fetch {
+                SeedFetchResult::Updated { .. } | SeedFetchResult::UpToDate => {
+                    let mut refs = vec![format!("{}/*", crate::events::REF_PREFIX)];
+                    if has_contributed(&self.peer, identity).await? {
+                        refs.extend(CONTRIBUTION_REFS.iter().map(ToString::to_string));
+                    }
+                    let push_result = push_refs_to_seed(&self.peer, identity, &seed, &refs).await;
+                    Some(match push_result {
+                        Ok(rejected) if rejected.is_empty() => SeedPushResult::Pushed,
+                        Ok(rejected) => SeedPushResult::Rejected { refs: rejected },
+                        Err(err) => SeedPushResult::Error {
+                            message: format!("{err:#}"),
+                        },
+                    })
+                },
+                SeedFetchResult::NotFound | SeedFetchResult::Error { .. } => None,
+            };
+
+            results.push(SeedSyncResult {
+                seed: seed.to_string(),
+                fetch,
+                push,
+            });
+        }
+
+        if updated {
+            let refs_after_sync = namespace_refs(&self.peer, identity).await?;
+            broadcast_update(
+                &self.update_tx,
+                Update {
+                    identity,
+                    changes: crate::watch_monorepo::ref_changes(
+                        &refs_before_sync,
+                        &refs_after_sync,
+                    ),
+                },
+            );
+        }
+
+        Ok(results)
+    }
+
+    /// Fetch the project `identity` that may not be stored on this peer yet, verify it and add it
+    /// to the projects that are continuously fetched.
+    ///
+    /// The project identity is verified before any of the project’s remotes are tracked. If
+    /// verification fails nothing is tracked and the seed is not recorded for the project.
+    ///
+    /// If `seed` is given, the project is only fetched from that seed. Otherwise we try the seeds
+    /// known to replicate the project and the configured seeds until one provides the project.
+    /// Returns the seed the project was fetched from.
+    pub async fn clone_project(
+        &self,
+        identity: Oid,
+        seed: Option<rad_common::Url>,
+    ) -> Result<rad_common::Url, CloneError> {
+        let seeds_to_try = match seed {
+            Some(seed) => vec![seed],
+            None => fetch_order(&self.project_seed_store.get(identity), &self.seeds),
+        };
+
+        let seed =
+            clone_from_seeds(
+                seeds_to_try,
+                |seed| async move {
+                    fetch_project_identity_from_seed(&self.peer, identity, &seed).await
+                },
+                || verify_project(&self.peer, identity),
+                |seed| async move {
+                    fetch_from_seed(
+                        &self.peer,
+                        identity,
+                        &seed,
+                        &self.project_seed_store,
+                        &self.seed_health_store,
+                        &self.backoff,
+                        self.fetch_interval,
+                    )
+                    .await
+                },
+            )
+            .await?;
+        self.add(identity).await;
+        Ok(seed)
     }
 
     pub async fn push_event_logs(&self, identity: Oid) -> Result<bool, anyhow::Error> {
@@ -104,10 +360,14 @@ impl Handle {
 
 pub struct Runner {
     peer: crate::peer::Peer,
-    /// List of seed URLs to try to fetch identities from if we don’t know the seed yet.
+    /// List of seed URLs to try to fetch identities from in addition to the seeds known to
+    /// replicate an identity.
     seeds: Vec<rad_common::Url>,
+    /// Number of seeds an identity needs to be fetched from successfully. If `None`, we fetch
+    /// from all seeds.
+    fetch_quorum: Option<usize>,
     /// Inform subscribers that an identity has been updated
-    update_tx: async_broadcast::Sender<Oid>,
+    update_tx: async_broadcast::Sender<Update>,
     /// Stream of queued identities to fetch updates for
     identity_rx: futures_delay_queue::Receiver<SyncAction>,
     /// Queue of identities to fetch updates for
@@ -115,6 +375,8 @@ pub struct Runner {
     /// Time after which project updates are fetched again.
     fetch_interval: std::time::Duration,
     project_seed_store: ProjectSeedStore,
+    seed_health_store: SeedHealthStore,
+    backoff: Backoff,
 }
 
 impl Runner {
@@ -122,32 +384,65 @@ impl Runner {
         let Self {
             peer,
             seeds,
+            fetch_quorum,
             update_tx,
             identity_rx,
             identity_queue,
             fetch_interval,
             project_seed_store,
+            seed_health_store,
+            backoff,
         } = self;
 
         let identity_rx = identity_rx.into_stream().take_until(shutdown_signal);
         futures::pin_mut!(identity_rx);
 
         while let Some(entry) = identity_rx.next().await {
+            let mut delay = fetch_interval;
             match entry {
                 SyncAction::FetchIdentity(identity) => {
-                    match fetch_project(&peer, &seeds, identity, &project_seed_store).await {
-                        Ok(true) => {
-                            let result = update_tx.try_broadcast(identity);
-                            match result {
-                                Err(err) if !err.is_disconnected() => {
-                                    tracing::warn!(?err, "failed to broadcast Git fetch result")
+                    let refs_before = match namespace_refs(&peer, identity).await {
+                        Ok(refs) => refs,
+                        Err(err) => {
+                            tracing::warn!(?err, ?identity, "failed to get project references");
+                            Default::default()
+                        },
+                    };
+                    match fetch_project(
+                        &peer,
+                        &seeds,
+                        fetch_quorum,
+                        identity,
+                        &project_seed_store,
+                        &seed_health_store,
+                        &backoff,
+                        fetch_interval,
+                    )
+                    .await
+                    {
+                        Ok(true) => match namespace_refs(&peer, identity).await {
+                            Ok(refs_after) => broadcast_update(
+                                &update_tx,
+                                Update {
+                                    identity,
+                                    changes: crate::watch_monorepo::ref_changes(
+                                        &refs_before,
+                                        &refs_after,
+                                    ),
                                 },
-                                _ => {},
-                            };
+                            ),
+                            Err(err) => {
+                                tracing::warn!(?err, ?identity, "failed to get project references")
+                            },
                         },
                         Ok(false) => {},
                         Err(errs) => {
                             tracing::warn!(?errs, ?identity, "failed to fetch project with git");
+                            if let Some(retry_at) = backoff.next_retry(identity) {
+                                delay = delay.max(
+                                    retry_at.saturating_duration_since(std::time::Instant::now()),
+                                );
+                            }
                         },
                     };
                 },
@@ -159,9 +454,17 @@ impl Runner {
                         },
                     };
                 },
+                SyncAction::PushContributions(identity) => {
+                    if let Err(err) = push_contributions(&peer, identity, &project_seed_store).await
+                    {
+                        tracing::warn!(?err, ?identity, "failed to push contributions");
+                    }
+                    // Contributions are only pushed when they change.
+                    continue;
+                },
             }
 
-            identity_queue.add(entry, fetch_interval).await;
+            identity_queue.add(entry, delay).await;
         }
     }
 }
@@ -170,6 +473,7 @@ impl Runner {
 enum SyncAction {
     FetchIdentity(Oid),
     PushEvents(Oid),
+    PushContributions(Oid),
 }
 
 /// Queue for [`Oid`]s that will be emitted by a receiver after a delay.
@@ -222,33 +526,213 @@ impl UniqueDelayQueue {
     }
 }
 
+/// Tracks failures to fetch identities from seeds and determines when to try a seed again.
+#[derive(Debug, Clone, Default)]
+struct Backoff {
+    entries: Arc<dashmap::DashMap<(rad_common::Url, Oid), BackoffEntry>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BackoffEntry {
+    /// Number of consecutive failures.
+    failures: u32,
+    /// Time before which we don’t try to fetch the identity from the seed.
+    retry_at: std::time::Instant,
+}
+
+impl Backoff {
+    /// Returns `true` if we should not try to fetch `identity` from `seed` yet.
+    fn is_backing_off(&self, seed: &rad_common::Url, identity: Oid) -> bool {
+        match self.entries.get(&(seed.clone(), identity)) {
+            Some(entry) => entry.retry_at > std::time::Instant::now(),
+            None => false,
+        }
+    }
+
+    /// Record a failure to fetch `identity` from `seed`. The delay before the next try doubles
+    /// with every consecutive failure, starting at `base_delay` and capped at [`MAX_BACKOFF`]. The
+    /// delay is randomized to between half and all of that value so that retries for different
+    /// identities don’t line up.
+    fn record_failure(
+        &self,
+        seed: &rad_common::Url,
+        identity: Oid,
+        base_delay: std::time::Duration,
+    ) {
+        let mut entry = self
+            .entries
+            .entry((seed.clone(), identity))
+            .or_insert(BackoffEntry {
+                failures: 0,
+                retry_at: std::time::Instant::now(),
+            });
+        entry.failures = entry.failures.saturating_add(1);
+        let exponent = (entry.failures - 1).min(16);
+        let delay = base_delay.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+        let jitter = rand::Rng::gen_range(&mut rand::thread_rng(), 0.5..=1.0);
+        entry.retry_at = std::time::Instant::now() + delay.mul_f64(jitter);
+    }
+
+    fn record_success(&self, seed: &rad_common::Url, identity: Oid) {
+        self.entries.remove(&(seed.clone(), identity));
+    }
+
+    /// Returns the earliest time at which we may try to fetch `identity` from a seed that failed
+    /// before.
+    fn next_retry(&self, identity: Oid) -> Option<std::time::Instant> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.key().1 == identity)
+            .map(|entry| entry.retry_at)
+            .min()
+    }
+}
+
+/// Health of a seed derived from the outcome of fetches from the seed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedHealth {
+    /// URL of the seed.
+    pub seed: String,
+    /// Unix timestamp in seconds of the last successful fetch.
+    pub last_success: Option<u64>,
+    /// The last failed fetch.
+    pub last_error: Option<SeedError>,
+    /// Duration of the last successful fetch in milliseconds.
+    pub latency: Option<u64>,
+    /// Number of failed fetches since the last successful fetch.
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedError {
+    /// Unix timestamp in seconds of the failed fetch.
+    pub timestamp: u64,
+    pub message: String,
+}
+
+impl SeedHealth {
+    fn new(seed: &rad_common::Url) -> Self {
+        Self {
+            seed: seed.to_string(),
+            last_success: None,
+            last_error: None,
+            latency: None,
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Persistent mapping from seed URLs to [`SeedHealth`].
+#[derive(Clone)]
+struct SeedHealthStore {
+    bucket: kv::Bucket<'static, String, kv::Json<SeedHealth>>,
+}
+
+impl SeedHealthStore {
+    fn new(store: &kv::Store) -> Result<Self, kv::Error> {
+        let bucket = store.bucket(Some("seeds_health"))?;
+        Ok(Self { bucket })
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<SeedHealth>> {
+        self.bucket
+            .iter()
+            .map(|item_result| {
+                let item = item_result.context("failed to read seed health")?;
+                let health = item
+                    .value::<kv::Json<SeedHealth>>()
+                    .context("failed to parse seed health")?;
+                Ok(health.0)
+            })
+            .collect()
+    }
+
+    fn record_success(&self, seed: &rad_common::Url, latency: std::time::Duration) {
+        self.update(seed, |health| {
+            health.last_success = Some(unix_timestamp());
+            health.latency = Some(latency.as_millis().try_into().unwrap_or(u64::MAX));
+            health.consecutive_failures = 0;
+        })
+    }
+
+    fn record_failure(&self, seed: &rad_common::Url, err: &anyhow::Error) {
+        self.update(seed, |health| {
+            health.last_error = Some(SeedError {
+                timestamp: unix_timestamp(),
+                message: format!("{err:#}"),
+            });
+            health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        })
+    }
+
+    fn update(&self, seed: &rad_common::Url, f: impl FnOnce(&mut SeedHealth)) {
+        let mut health = match self.bucket.get(seed.to_string()) {
+            Ok(Some(health)) => health.0,
+            Ok(None) => SeedHealth::new(seed),
+            Err(err) => {
+                tracing::error!(?err, "could not get value from kv bucket");
+                SeedHealth::new(seed)
+            },
+        };
+        f(&mut health);
+        if let Err(err) = self.bucket.set(seed.to_string(), kv::Json(health)) {
+            tracing::error!(?err, "could not store seed health in kv store");
+        }
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system time is before the Unix epoch")
+        .as_secs()
+}
+
+/// Persistent mapping from identities to the seeds that replicate them.
+///
+/// Values are JSON arrays of seed URLs in the order in which the seeds were found. Values written
+/// by older versions contain a single seed URL and are still understood.
+///
+/// In addition, a user may pin a seed for an identity. The pinned seed is always the first seed
+/// for the identity and is never removed automatically.
 #[derive(Clone)]
 struct ProjectSeedStore {
     bucket: kv::Bucket<'static, String, String>,
+    pinned_bucket: kv::Bucket<'static, String, String>,
 }
 
 impl ProjectSeedStore {
     fn new(store: &kv::Store) -> Result<Self, kv::Error> {
         let bucket = store.bucket(Some("projects_seeds"))?;
-        Ok(Self { bucket })
+        let pinned_bucket = store.bucket(Some("projects_pinned_seeds"))?;
+        Ok(Self {
+            bucket,
+            pinned_bucket,
+        })
     }
 
-    fn get(&self, identity: Oid) -> Option<rad_common::Url> {
-        let result = self.bucket.get(identity.to_string());
+    /// Returns the seeds for `identity` with the pinned seed first.
+    fn get(&self, identity: Oid) -> Vec<rad_common::Url> {
+        let mut seed_urls = self.list(identity);
+        if let Some(pinned) = self.pinned(identity) {
+            seed_urls.retain(|url| *url != pinned);
+            seed_urls.insert(0, pinned);
+        }
+        seed_urls
+    }
 
-        let maybe_value = match result {
-            Ok(maybe_value) => maybe_value,
+    /// Returns the seed pinned for `identity`.
+    fn pinned(&self, identity: Oid) -> Option<rad_common::Url> {
+        let value = match self.pinned_bucket.get(identity.to_string()) {
+            Ok(value) => value?,
             Err(err) => {
                 tracing::error!(?err, "could not get value from kv bucket");
                 return None;
             },
         };
 
-        let value = match maybe_value {
-            Some(value) => value,
-            None => return None,
-        };
-
         match rad_common::Url::parse(&value) {
             Ok(url) => Some(url),
             Err(err) => {
@@ -258,13 +742,105 @@ impl ProjectSeedStore {
         }
     }
 
-    fn set(&self, identity: Oid, seed_url: rad_common::Url) {
-        let result = self.bucket.set(identity.to_string(), seed_url.to_string());
+    /// Pin `seed_url` for `identity`, replacing any previously pinned seed. If `seed_url` is
+    /// `None`, the pinned seed is removed.
+    fn pin(&self, identity: Oid, seed_url: Option<&rad_common::Url>) {
+        let result = match seed_url {
+            Some(seed_url) => self
+                .pinned_bucket
+                .set(identity.to_string(), seed_url.to_string())
+                .map(|_| ()),
+            None => self.pinned_bucket.remove(identity.to_string()).map(|_| ()),
+        };
 
         if let Err(err) = result {
-            tracing::error!(?err, "could not store project seed in kv store");
+            tracing::error!(?err, "could not store pinned project seed in kv store");
         };
     }
+
+    /// Returns the seeds for `identity` in the order in which they were found, ignoring the pinned
+    /// seed.
+    fn list(&self, identity: Oid) -> Vec<rad_common::Url> {
+        let result = self.bucket.get(identity.to_string());
+
+        let maybe_value = match result {
+            Ok(maybe_value) => maybe_value,
+            Err(err) => {
+                tracing::error!(?err, "could not get value from kv bucket");
+                return vec![];
+            },
+        };
+
+        let value = match maybe_value {
+            Some(value) => value,
+            None => return vec![],
+        };
+
+        let urls = if value.starts_with('[') {
+            match serde_json::from_str::<Vec<String>>(&value) {
+                Ok(urls) => urls,
+                Err(err) => {
+                    tracing::error!(?err, "could not parse project seeds");
+                    return vec![];
+                },
+            }
+        } else {
+            vec![value]
+        };
+
+        urls.into_iter()
+            .filter_map(|url| match rad_common::Url::parse(&url) {
+                Ok(url) => Some(url),
+                Err(err) => {
+                    tracing::error!(?err, "could not parse url");
+                    None
+                },
+            })
+            .collect()
+    }
+
+    fn set(&self, identity: Oid, seed_urls: &[rad_common::Url]) {
+        let urls = seed_urls
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let value = serde_json::to_string(&urls).expect("failed to serialize URLs");
+        let result = self.bucket.set(identity.to_string(), value);
+
+        if let Err(err) = result {
+            tracing::error!(?err, "could not store project seeds in kv store");
+        };
+    }
+
+    /// Record that `seed_url` provides `identity`.
+    fn add(&self, identity: Oid, seed_url: &rad_common::Url) {
+        let mut seed_urls = self.list(identity);
+        if !seed_urls.contains(seed_url) {
+            seed_urls.push(seed_url.clone());
+            self.set(identity, &seed_urls);
+        }
+    }
+
+    /// Record that `seed_url` provides `identity`. If `pinned` is `true` the seed is pinned.
+    /// Otherwise the seed is unpinned if it was pinned before.
+    fn add_and_pin(&self, identity: Oid, seed_url: &rad_common::Url, pinned: bool) {
+        self.add(identity, seed_url);
+        if pinned {
+            self.pin(identity, Some(seed_url));
+        } else if self.pinned(identity).as_ref() == Some(seed_url) {
+            self.pin(identity, None);
+        }
+    }
+
+    /// Record that `seed_url` does not provide `identity` anymore. This does not unpin the seed.
+    fn remove(&self, identity: Oid, seed_url: &rad_common::Url) {
+        let mut seed_urls = self.list(identity);
+        let len = seed_urls.len();
+        seed_urls.retain(|url| url != seed_url);
+        if seed_urls.len() != len {
+            self.set(identity, &seed_urls);
+        }
+    }
 }
 
 async fn push_event_logs(
@@ -282,8 +858,8 @@ async fn push_event_logs(
     .context("failed to push refs/notes/upstream")
 }
 
-/// Push all `refs` under the given identity to a Radicle Git seed
-/// if one is known and return `true`. If no seed is known, return `false`.
+/// Push all `refs` under the given identity to all Radicle Git seeds that are known to replicate
+/// the identity and return `true`. If no seed is known, return `false`.
 ///
 /// `refs` is the refname excluding the leading namespace and "refs" at the beginning. For
 /// instance, "heads/*" will push all heads for the identity to the seed.
@@ -293,24 +869,105 @@ async fn push_refs(
     refs: &str,
     project_seed_store: &ProjectSeedStore,
 ) -> anyhow::Result<bool> {
-    let urn = link_identities::Urn::new(identity);
-    let monorepo_path = peer.paths().git_dir().to_owned();
+    let seed_urls = project_seed_store.get(identity);
+    if seed_urls.is_empty() {
+        return Ok(false);
+    }
 
-    let seed_url = match project_seed_store.get(identity) {
-        Some(seed_url) => seed_url,
-        None => return Ok(false),
-    };
+    for seed_url in seed_urls {
+        match push_refs_to_seed(peer, identity, &seed_url, &[refs]).await {
+            Ok(rejected) if rejected.is_empty() => {},
+            Ok(rejected) => tracing::warn!(
+                identity = %link_identities::Urn::new(identity),
+                seed = %seed_url,
+                ?rejected,
+                "seed rejected refs"
+            ),
+            Err(err) => tracing::error!(
+                identity = %link_identities::Urn::new(identity),
+                seed = %seed_url,
+                %refs,
+                ?err,
+                "failed to push refs to seed with git"
+            ),
+        }
+    }
+    Ok(true)
+}
+
+/// Push our [`CONTRIBUTION_REFS`] for `identity` to all seeds that are known to replicate the
+/// identity. Does nothing if we haven’t contributed to the identity.
+async fn push_contributions(
+    peer: &crate::peer::Peer,
+    identity: Oid,
+    project_seed_store: &ProjectSeedStore,
+) -> anyhow::Result<()> {
+    if !has_contributed(peer, identity).await? {
+        return Ok(());
+    }
+
+    for seed_url in project_seed_store.get(identity) {
+        match push_refs_to_seed(peer, identity, &seed_url, &CONTRIBUTION_REFS).await {
+            Ok(rejected) if rejected.is_empty() => {},
+            Ok(rejected) => tracing::warn!(
+                identity = %link_identities::Urn::new(identity),
+                seed = %seed_url,
+                ?rejected,
+                "seed rejected contributions"
+            ),
+            Err(err) => tracing::warn!(
+                identity = %link_identities::Urn::new(identity),
+                seed = %seed_url,
+                ?err,
+                "failed to push contributions to seed"
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if the local peer has signed refs for `identity`.
+async fn has_contributed(peer: &crate::peer::Peer, identity: Oid) -> anyhow::Result<bool> {
+    let signed_refs = format!(
+        "refs/namespaces/{}/refs/rad/signed_refs",
+        link_identities::Urn::new(identity).encode_id()
+    );
+    peer.monorepo_unblock(move |repo| match repo.find_reference(&signed_refs) {
+        Ok(_) => Ok(true),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(false),
+        Err(err) => Err(err).context("failed to get signed refs"),
+    })
+    .await
+}
 
+/// Push all `refs` under the given identity to the Radicle Git seed at `seed_url` with a single
+/// atomic, signed push.
+///
+/// Returns the references rejected by the seed. If any reference is rejected no reference is
+/// updated.
+///
+/// See [`push_refs`] for the format of `refs`.
+async fn push_refs_to_seed(
+    peer: &crate::peer::Peer,
+    identity: Oid,
+    seed_url: &rad_common::Url,
+    refs: &[impl AsRef<str>],
+) -> anyhow::Result<Vec<RejectedRef>> {
+    let urn = link_identities::Urn::new(identity);
+    let monorepo_path = peer.paths().git_dir().to_owned();
     let id = urn.encode_id();
     let proj_seed_url = seed_url.join(&id).expect("invalid Project URN");
     let this_peer_id = peer.librad_peer().peer_id();
+
+    let refspecs = refs.iter().map(|refs| {
+        let refs = refs.as_ref();
+        format!("+refs/namespaces/{id}/refs/{refs}:refs/remotes/{this_peer_id}/{refs}")
+    });
     let child = tokio::process::Command::new("git")
         .current_dir(monorepo_path)
-        .args(["push", "--signed", "--atomic"])
+        .args(["push", "--signed", "--atomic", "--porcelain"])
         .arg(proj_seed_url.to_string())
-        .arg(format!(
-            "+refs/namespaces/{id}/refs/{refs}:refs/remotes/{this_peer_id}/{refs}",
-        ))
+        .args(refspecs)
         .stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -320,74 +977,404 @@ async fn push_refs(
         .wait_with_output()
         .await
         .context("`git push` failed")?;
-    if output.status.success() {
-        tracing::debug!(
-            identity = %link_identities::Urn::new(identity),
-            seed = %proj_seed_url,
-            %refs,
-            "pushed refs to seed"
+    if !output.status.success() {
+        let rejected = rejected_refs(&String::from_utf8_lossy(&output.stdout));
+        if !rejected.is_empty() {
+            return Ok(rejected);
+        }
+        anyhow::bail!(
+            "`git push` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
         );
-    } else {
-        tracing::error!(
-            identity = %link_identities::Urn::new(identity),
-            seed = %proj_seed_url,
-            %refs,
-            exit_status = %output.status,
-            stdout = ?std::str::from_utf8(&output.stdout),
-            stderr = ?std::str::from_utf8(&output.stderr),
-            "failed to push refs to seed with git"
-        )
     }
-    Ok(true)
+
+    tracing::debug!(
+        identity = %urn,
+        seed = %proj_seed_url,
+        refs = ?refs.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+        "pushed refs to seed"
+    );
+    Ok(vec![])
+}
+
+/// Parse the references rejected by the remote from the output of `git push --porcelain`.
+///
+/// Each reference is reported on a line with the format `<flag>\t<from>:<to>\t<summary>`. The
+/// flag `!` marks rejected references.
+fn rejected_refs(output: &str) -> Vec<RejectedRef> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            if fields.next()? != "!" {
+                return None;
+            }
+            let (from, _to) = fields.next()?.split_once(':')?;
+            let summary = fields.next().unwrap_or_default();
+            let name = match from.strip_prefix("refs/namespaces/") {
+                Some(rest) => rest.split_once('/').map_or(from, |(_, name)| name),
+                None => from,
+            };
+            let reason = match (summary.find('('), summary.rfind(')')) {
+                (Some(start), Some(end)) if start < end => &summary[start + 1..end],
+                _ => summary,
+            };
+            Some(RejectedRef {
+                name: name.to_string(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect()
 }
 
 /// Try to fetch a project from one or more seeds.
 ///
-/// Returns `true` if the project refernces were updated and `false` otherwise. Also returns
-/// `false` if the project was not found on any of the seeds tried.
+/// Returns `true` if the project references were updated by any seed and `false` otherwise. Also
+/// returns `false` if the project was not found on any of the seeds tried. Errors are only
+/// returned if no seed provided the project.
+///
+/// We first try the seeds that are known to replicate the project, followed by the remaining
+/// `seeds`. We stop once `fetch_quorum` seeds provided the project or try all seeds if
+/// `fetch_quorum` is `None`. Seeds that provide the project are added to `project_seed_store`,
+/// known seeds that don’t provide it anymore are removed.
 ///
-/// If the Project URN is present in `identity_providers`, then we only fetch it from that seed.
-/// Otherwise, we try to fetch the projects from each of the `seeds`. If we find the project, we
-/// update `identity_providers`.
+/// Seeds that failed to provide the project recently are skipped according to `backoff`. The
+/// outcome of every fetch is recorded in `seed_health_store`.
+#[allow(clippy::too_many_arguments)]
 async fn fetch_project(
     peer: &crate::peer::Peer,
     seeds: &[rad_common::Url],
+    fetch_quorum: Option<usize>,
     identity: Oid,
     project_seed_store: &ProjectSeedStore,
+    seed_health_store: &SeedHealthStore,
+    backoff: &Backoff,
+    fetch_interval: std::time::Duration,
 ) -> Result<bool, Vec<anyhow::Error>> {
+    let seeds_to_try = fetch_order(&project_seed_store.get(identity), seeds)
+        .into_iter()
+        .filter(|seed| {
+            let backing_off = backoff.is_backing_off(seed, identity);
+            if backing_off {
+                tracing::debug!(identity = %link_identities::Urn::new(identity), seed = %seed, "skipping seed after previous failures");
+            }
+            !backing_off
+        })
+        .collect::<Vec<_>>();
+
+    let result = fetch_until_quorum(seeds_to_try, fetch_quorum, |seed| async move {
+        fetch_from_seed(
+            peer,
+            identity,
+            &seed,
+            project_seed_store,
+            seed_health_store,
+            backoff,
+            fetch_interval,
+        )
+        .await
+    })
+    .await;
+    if let Ok((_, errors)) = &result {
+        if !errors.is_empty() {
+            tracing::warn!(
+                ?errors,
+                identity = %link_identities::Urn::new(identity),
+                "failed to fetch project from some seeds"
+            );
+        }
+    }
+    result.map(|(updated, _)| updated)
+}
+
+/// Returns the seeds to fetch a project from in order: first the `known_seeds` that replicate the
+/// project, followed by the remaining configured `seeds`.
+fn fetch_order(known_seeds: &[rad_common::Url], seeds: &[rad_common::Url]) -> Vec<rad_common::Url> {
+    let mut seeds_to_try = known_seeds.to_vec();
+    for seed in seeds {
+        if !seeds_to_try.contains(seed) {
+            seeds_to_try.push(seed.clone());
+        }
+    }
+    seeds_to_try
+}
+
+/// Call `fetch` for every seed in `seeds` in order until `fetch_quorum` seeds provided the
+/// project. Failing seeds are skipped. Tries all seeds if `fetch_quorum` is `None`.
+///
+/// Returns whether any seed updated the project and the errors of failed seeds. Errors are only
+/// returned as `Err` if no seed provided the project.
+async fn fetch_until_quorum<F, Fut>(
+    seeds: Vec<rad_common::Url>,
+    fetch_quorum: Option<usize>,
+    mut fetch: F,
+) -> Result<(bool, Vec<anyhow::Error>), Vec<anyhow::Error>>
+where
+    F: FnMut(rad_common::Url) -> Fut,
+    Fut: Future<Output = anyhow::Result<FetchResult>>,
+{
     let mut errors = vec![];
+    let mut provided_count = 0;
+    let mut updated = false;
 
-    let seeds_to_try = match project_seed_store.get(identity) {
-        Some(seed_url) => vec![seed_url],
-        None => seeds.to_owned(),
-    };
+    for seed in seeds {
+        if let Some(fetch_quorum) = fetch_quorum {
+            if provided_count >= fetch_quorum {
+                break;
+            }
+        }
 
-    for seed in seeds_to_try {
-        let result = fetch_project_from_seed(peer, identity, &seed)
-            .await
-            .context(format!("failed to fetch project from seed {}", &seed));
-        tracing::debug!(identity = %link_identities::Urn::new(identity), seed = %seed, ?result, "fetched identity from git seed");
-        match result {
+        match fetch(seed).await {
             Ok(FetchResult::NotFound) => {},
             Ok(FetchResult::UpToDate) => {
-                project_seed_store.set(identity, seed.clone());
-                return Ok(false);
+                provided_count += 1;
             },
             Ok(FetchResult::Updated) => {
-                project_seed_store.set(identity, seed.clone());
-                return Ok(true);
+                provided_count += 1;
+                updated = true;
             },
             Err(err) => errors.push(err),
         };
     }
 
-    if errors.is_empty() {
-        Ok(false)
+    if provided_count > 0 || errors.is_empty() {
+        Ok((updated, errors))
     } else {
         Err(errors)
     }
 }
 
+/// Fetch a project from a single seed and record the outcome.
+///
+/// Seeds that provide the project are added to `project_seed_store`, seeds that don’t provide it
+/// anymore are removed. Failures are recorded in `backoff` and all outcomes are recorded in
+/// `seed_health_store`.
+async fn fetch_from_seed(
+    peer: &crate::peer::Peer,
+    identity: Oid,
+    seed: &rad_common::Url,
+    project_seed_store: &ProjectSeedStore,
+    seed_health_store: &SeedHealthStore,
+    backoff: &Backoff,
+    fetch_interval: std::time::Duration,
+) -> anyhow::Result<FetchResult> {
+    let started_at = std::time::Instant::now();
+    let result = fetch_project_from_seed(peer, identity, seed)
+        .await
+        .context(format!("failed to fetch project from seed {}", seed));
+    tracing::debug!(identity = %link_identities::Urn::new(identity), seed = %seed, ?result, "fetched identity from git seed");
+    match &result {
+        Ok(fetch_result) => {
+            backoff.record_success(seed, identity);
+            seed_health_store.record_success(seed, started_at.elapsed());
+            match fetch_result {
+                FetchResult::NotFound => project_seed_store.remove(identity, seed),
+                FetchResult::UpToDate | FetchResult::Updated => {
+                    project_seed_store.add(identity, seed)
+                },
+            }
+        },
+        Err(err) => {
+            backoff.record_failure(seed, identity, fetch_interval);
+            seed_health_store.record_failure(seed, err);
+        },
+    }
+    result
+}
+
+/// Seed that replicates a project.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectSeed {
+    pub url: String,
+    /// `true` if the user pinned the seed for the project.
+    pub pinned: bool,
+}
+
+/// Error returned by [`Handle::add_seed`].
+#[derive(Debug, thiserror::Error)]
+pub enum AddSeedError {
+    #[error("seed does not provide the project")]
+    NotProvided,
+    #[error("failed to query seed")]
+    Unreachable(#[source] anyhow::Error),
+}
+
+/// Returns `true` if the seed at `seed_url` provides `identity`. Unlike fetching, this does not
+/// change the monorepo.
+async fn seed_provides(identity: Oid, seed_url: &rad_common::Url) -> anyhow::Result<bool> {
+    let id = link_identities::Urn::new(identity).encode_id();
+    let proj_seed_url = seed_url.join(&id).expect("invalid Project URN");
+    let output = tokio::process::Command::new("git")
+        .args(["ls-remote", "--exit-code"])
+        .arg(proj_seed_url.to_string())
+        .arg("refs/rad/id")
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .context("failed to run `git ls-remote`")?;
+
+    match output.status.code() {
+        Some(0) => Ok(true),
+        // `git ls-remote --exit-code` exits with 2 if the remote has no matching refs.
+        Some(2) => Ok(false),
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("couldn't find remote ref") || stderr.contains("not found") {
+                Ok(false)
+            } else {
+                anyhow::bail!(
+                    "`git ls-remote` exited with {}: {}",
+                    output.status,
+                    stderr.trim()
+                )
+            }
+        },
+    }
+}
+
+/// Error returned by [`Handle::clone_project`].
+#[derive(Debug, thiserror::Error)]
+pub enum CloneError {
+    /// None of the seeds provided the project. Contains the errors of seeds that failed.
+    #[error("project not found on any seed")]
+    NotFound { errors: Vec<String> },
+    #[error("failed to verify project")]
+    Verification(#[source] anyhow::Error),
+}
+
+/// Try `seeds` in order until one provides a project and return that seed.
+///
+/// For every seed we first fetch only the project identity with `fetch_identity` and `verify` it.
+/// Only if the identity is valid, `fetch` tracks and fetches the project’s remotes. Verification
+/// failures are returned immediately, seeds that fail or don’t provide the project are skipped.
+async fn clone_from_seeds<I, IFut, V, VFut, F, FFut>(
+    seeds: Vec<rad_common::Url>,
+    mut fetch_identity: I,
+    mut verify: V,
+    mut fetch: F,
+) -> Result<rad_common::Url, CloneError>
+where
+    I: FnMut(rad_common::Url) -> IFut,
+    IFut: Future<Output = anyhow::Result<bool>>,
+    V: FnMut() -> VFut,
+    VFut: Future<Output = anyhow::Result<()>>,
+    F: FnMut(rad_common::Url) -> FFut,
+    FFut: Future<Output = anyhow::Result<FetchResult>>,
+{
+    let mut errors = vec![];
+    for seed in seeds {
+        match fetch_identity(seed.clone()).await {
+            Ok(true) => {},
+            Ok(false) => continue,
+            Err(err) => {
+                errors.push(format!("{err:#}"));
+                continue;
+            },
+        }
+
+        verify().await.map_err(CloneError::Verification)?;
+
+        match fetch(seed.clone()).await {
+            Ok(FetchResult::NotFound) => {},
+            Ok(FetchResult::UpToDate | FetchResult::Updated) => return Ok(seed),
+            Err(err) => errors.push(format!("{err:#}")),
+        }
+    }
+
+    Err(CloneError::NotFound { errors })
+}
+
+/// Verify the identity document history of the project `identity`.
+async fn verify_project(peer: &crate::peer::Peer, identity: Oid) -> anyhow::Result<()> {
+    let urn = link_identities::Urn::new(identity);
+    peer.librad_peer()
+        .using_storage(move |storage| {
+            librad::git::identities::project::verify(storage, &urn)
+                .context("invalid project identity")?
+                .context("project identity not found")?;
+            Ok(())
+        })
+        .await
+        .context("failed to access storage")?
+}
+
+/// Outcome of syncing an identity with a single seed using [`Handle::sync`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedSyncResult {
+    /// URL of the seed.
+    pub seed: String,
+    pub fetch: SeedFetchResult,
+    /// Outcome of pushing our references. `None` if we did not push to the seed because the seed
+    /// does not provide the identity or fetching from the seed failed.
+    pub push: Option<SeedPushResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SeedFetchResult {
+    /// Updates for the identity have been fetched from the seed.
+    Updated {
+        refs: Vec<crate::watch_monorepo::RefChange>,
+    },
+    /// The identity was found but our data is up-to-date.
+    UpToDate,
+    /// The seed does not provide the identity.
+    NotFound,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SeedPushResult {
+    Pushed,
+    /// The seed rejected the push. Since pushes are atomic, none of the references were updated.
+    Rejected {
+        refs: Vec<RejectedRef>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Reference that a seed refused to update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedRef {
+    /// Name of the local reference without the namespace prefix, for example `refs/heads/main`.
+    #[serde(rename = "ref")]
+    pub name: String,
+    /// Reason given by Git, for example `non-fast-forward`.
+    pub reason: String,
+}
+
+/// Broadcast `update` to all subscribers of [`Handle::updates`].
+fn broadcast_update(update_tx: &async_broadcast::Sender<Update>, update: Update) {
+    match update_tx.try_broadcast(update) {
+        Err(err) if !err.is_disconnected() => {
+            tracing::warn!(?err, "failed to broadcast Git fetch result")
+        },
+        _ => {},
+    };
+}
+
+/// Returns the targets of all direct references in the namespace of `identity`, keyed by their
+/// name without the namespace prefix.
+async fn namespace_refs(
+    peer: &crate::peer::Peer,
+    identity: Oid,
+) -> anyhow::Result<std::collections::BTreeMap<String, Oid>> {
+    let namespace = link_identities::Urn::new(identity).encode_id();
+    peer.monorepo_unblock(move |repo| {
+        crate::watch_monorepo::namespace_refs(&repo, &namespace, true)
+    })
+    .await
+}
+
 /// Result of fetching a project from a Git seed.
 #[derive(Debug, Copy, Clone)]
 enum FetchResult {
@@ -399,6 +1386,57 @@ enum FetchResult {
     NotFound,
 }
 
+/// Fetch the identity of a project and the identities of its delegates from the Git seed without
+/// tracking any remotes. Returns `false` if the seed does not provide the project.
+async fn fetch_project_identity_from_seed(
+    peer: &crate::peer::Peer,
+    project_id: Oid,
+    seed_url: &rad_common::Url,
+) -> anyhow::Result<bool> {
+    let monorepo_path = peer.paths().git_dir().to_owned();
+    let project_urn = link_identities::Urn::new(project_id);
+    let project_seed_url = seed_url
+        .join(&project_urn.encode_id())
+        .expect("invalid Project URN");
+
+    peer.librad_peer()
+        .using_storage(move |storage| {
+            fetch_project_identity(storage, &monorepo_path, &project_seed_url, &project_urn)
+        })
+        .await
+        .context("failed to access storage")?
+}
+
+/// Fetch the identity of the project `project_urn` and the identities of its delegates from
+/// `project_seed_url`. Returns `false` if the seed does not provide the project.
+fn fetch_project_identity(
+    storage: &librad::git::storage::Storage,
+    monorepo_path: &std::path::Path,
+    project_seed_url: &rad_common::Url,
+    project_urn: &link_identities::Urn,
+) -> anyhow::Result<bool> {
+    match rad_common::seed::fetch_identity(monorepo_path, project_seed_url, project_urn) {
+        Ok(_) => {},
+        Err(err) => {
+            if err.root_cause().to_string() == "fatal: couldn't find remote ref refs/rad/id\n" {
+                return Ok(false);
+            } else {
+                return Err(err.context("failed to fetch project identity"));
+            }
+        },
+    };
+
+    let project =
+        rad_common::project::get(storage, project_urn)?.context("failed to get project")?;
+
+    for delegate in &project.delegates {
+        rad_common::seed::fetch_identity(monorepo_path, project_seed_url, delegate).context(
+            format!("failed to fetch identity for delegate {}", delegate),
+        )?;
+    }
+    Ok(true)
+}
+
 /// Try to fetch a project and all references of all the delegates from the Git seed.
 async fn fetch_project_from_seed(
     peer: &crate::peer::Peer,
@@ -413,31 +1451,13 @@ async fn fetch_project_from_seed(
 
     peer.librad_peer()
         .using_storage(move |storage| {
-            match rad_common::seed::fetch_identity(&monorepo_path, &project_seed_url, &project_urn)
-            {
-                Ok(_) => {},
-                Err(err) => {
-                    if err.root_cause().to_string()
-                        == "fatal: couldn't find remote ref refs/rad/id\n"
-                    {
-                        return Ok(FetchResult::NotFound);
-                    } else {
-                        return Err(err.context("failed to fetch project identity"));
-                    }
-                },
-            };
+            if !fetch_project_identity(storage, &monorepo_path, &project_seed_url, &project_urn)? {
+                return Ok(FetchResult::NotFound);
+            }
 
             let project = rad_common::project::get(storage, &project_urn)?
                 .context("failed to get project")?;
 
-            for delegate in &project.delegates {
-                rad_common::seed::fetch_identity(&monorepo_path, &project_seed_url, delegate)
-                    .context(format!(
-                        "failed to fetch identity for delegate {}",
-                        delegate
-                    ))?;
-            }
-
             let tracking_config = Default::default();
             let tracking_actions = project
                 .remotes
@@ -472,7 +1492,7 @@ async fn fetch_project_from_seed(
             .context("failed to fetch remotes")?;
 
             let repo = git2::Repository::open(&monorepo_path).context("failed to open monorepo")?;
-            let invalid_refs = crate::events::validate(&repo, project_urn.id)
+            let invalid_refs = crate::events::validate(&repo, this_peer_id, project_urn.id)
                 .context("failed to run event log validation")?;
             if !invalid_refs.is_empty() {
                 tracing::warn!(?invalid_refs, "invalid event logs refs detected");
@@ -550,3 +1570,476 @@ async fn fetch_project_from_seed(
         .await
         .context("failed to access storage")?
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn url(url: &str) -> rad_common::Url {
+        rad_common::Url::parse(url).unwrap()
+    }
+
+    fn identity() -> Oid {
+        Oid::from(git2::Oid::zero())
+    }
+
+    /// Fake outcome of fetching a project from `seed` determined by the seed’s host name.
+    fn fake_fetch(seed: &rad_common::Url) -> anyhow::Result<FetchResult> {
+        match seed.host_str().unwrap_or_default() {
+            host if host.starts_with("failing") => Err(anyhow::anyhow!("connection refused")),
+            host if host.starts_with("missing") => Ok(FetchResult::NotFound),
+            host if host.starts_with("updated") => Ok(FetchResult::Updated),
+            _ => Ok(FetchResult::UpToDate),
+        }
+    }
+
+    /// Run [`fetch_until_quorum`] with [`fake_fetch`] and return the result together with the
+    /// seeds that were tried.
+    async fn fetch_fake_seeds(
+        seeds: &[&str],
+        fetch_quorum: Option<usize>,
+    ) -> (Result<(bool, usize), usize>, Vec<String>) {
+        let mut tried = vec![];
+        let result = fetch_until_quorum(
+            seeds.iter().map(|seed| url(seed)).collect(),
+            fetch_quorum,
+            |seed| {
+                tried.push(seed.host_str().unwrap_or_default().to_string());
+                future::ready(fake_fetch(&seed))
+            },
+        )
+        .await;
+        (
+            result
+                .map(|(updated, errors)| (updated, errors.len()))
+                .map_err(|errors| errors.len()),
+            tried,
+        )
+    }
+
+    #[test]
+    fn project_seed_store_reads_legacy_and_json_values() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let project_seed_store = ProjectSeedStore::new(&store).unwrap();
+        let bucket = store
+            .bucket::<String, String>(Some("projects_seeds"))
+            .unwrap();
+        assert_eq!(project_seed_store.get(identity()), vec![]);
+
+        bucket
+            .set(
+                identity().to_string(),
+                "https://legacy.example.com".to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            project_seed_store.get(identity()),
+            vec![url("https://legacy.example.com")]
+        );
+
+        bucket
+            .set(
+                identity().to_string(),
+                r#"["https://a.example.com","not a url","https://b.example.com"]"#.to_string(),
+            )
+            .unwrap();
+        assert_eq!(
+            project_seed_store.get(identity()),
+            vec![url("https://a.example.com"), url("https://b.example.com")]
+        );
+
+        project_seed_store.add(identity(), &url("https://c.example.com"));
+        project_seed_store.add(identity(), &url("https://a.example.com"));
+        project_seed_store.remove(identity(), &url("https://b.example.com"));
+        assert_eq!(
+            bucket.get(identity().to_string()).unwrap(),
+            Some(r#"["https://a.example.com/","https://c.example.com/"]"#.to_string())
+        );
+    }
+
+    #[test]
+    fn project_seed_store_puts_pinned_seed_first() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let project_seed_store = ProjectSeedStore::new(&store).unwrap();
+        let a = url("https://a.example.com");
+        let b = url("https://b.example.com");
+        let c = url("https://c.example.com");
+        for seed in [&a, &b, &c] {
+            project_seed_store.add(identity(), seed);
+        }
+
+        project_seed_store.pin(identity(), Some(&b));
+        assert_eq!(project_seed_store.pinned(identity()), Some(b.clone()));
+        assert_eq!(
+            project_seed_store.get(identity()),
+            vec![b.clone(), a.clone(), c.clone()]
+        );
+
+        // The pinned seed is kept if the seed stops providing the project.
+        project_seed_store.remove(identity(), &b);
+        assert_eq!(
+            project_seed_store.get(identity()),
+            vec![b.clone(), a.clone(), c.clone()]
+        );
+
+        project_seed_store.pin(identity(), None);
+        assert_eq!(project_seed_store.pinned(identity()), None);
+        assert_eq!(project_seed_store.get(identity()), vec![a, c]);
+    }
+
+    #[test]
+    fn project_seed_store_add_and_pin() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let project_seed_store = ProjectSeedStore::new(&store).unwrap();
+        let a = url("https://a.example.com");
+        let b = url("https://b.example.com");
+
+        project_seed_store.add_and_pin(identity(), &a, false);
+        project_seed_store.add_and_pin(identity(), &b, true);
+        assert_eq!(
+            project_seed_store.get(identity()),
+            vec![b.clone(), a.clone()]
+        );
+
+        // Adding another seed without pinning it keeps the pinned seed.
+        project_seed_store.add_and_pin(identity(), &a, false);
+        assert_eq!(project_seed_store.pinned(identity()), Some(b.clone()));
+
+        // Adding the pinned seed without pinning it unpins it.
+        project_seed_store.add_and_pin(identity(), &b, false);
+        assert_eq!(project_seed_store.pinned(identity()), None);
+        assert_eq!(project_seed_store.get(identity()), vec![a, b]);
+    }
+
+    /// Returns the time until `backoff` allows fetching `identity` from `seed` again.
+    fn retry_delay(
+        backoff: &Backoff,
+        seed: &rad_common::Url,
+        identity: Oid,
+    ) -> std::time::Duration {
+        let retry_at = backoff
+            .entries
+            .get(&(seed.clone(), identity))
+            .unwrap()
+            .retry_at;
+        retry_at.saturating_duration_since(std::time::Instant::now())
+    }
+
+    #[test]
+    fn backoff_delay_doubles_until_cap() {
+        let backoff = Backoff::default();
+        let seed = url("https://a.example.com");
+        let base_delay = std::time::Duration::from_secs(10);
+        assert!(!backoff.is_backing_off(&seed, identity()));
+        assert_eq!(backoff.next_retry(identity()), None);
+
+        // After 10 failures the delay of 10s * 2^9 exceeds the cap.
+        for failures in 1..=12u32 {
+            backoff.record_failure(&seed, identity(), base_delay);
+            let max_delay = base_delay
+                .saturating_mul(1 << (failures - 1))
+                .min(MAX_BACKOFF);
+            let delay = retry_delay(&backoff, &seed, identity());
+            assert!(
+                delay <= max_delay && delay + std::time::Duration::from_secs(1) >= max_delay / 2,
+                "delay {delay:?} after {failures} failures"
+            );
+            assert!(backoff.is_backing_off(&seed, identity()));
+        }
+        assert!(retry_delay(&backoff, &seed, identity()) <= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn backoff_resets_on_success() {
+        let backoff = Backoff::default();
+        let a = url("https://a.example.com");
+        let b = url("https://b.example.com");
+        let base_delay = std::time::Duration::from_secs(10);
+        for _ in 0..5 {
+            backoff.record_failure(&a, identity(), base_delay);
+        }
+        backoff.record_failure(&b, identity(), base_delay);
+        let other_identity =
+            Oid::from(git2::Oid::hash_object(git2::ObjectType::Blob, b"other").unwrap());
+        assert!(!backoff.is_backing_off(&a, other_identity));
+        assert_eq!(
+            backoff.next_retry(identity()),
+            backoff
+                .entries
+                .get(&(b.clone(), identity()))
+                .map(|entry| entry.retry_at)
+        );
+
+        backoff.record_success(&a, identity());
+        assert!(!backoff.is_backing_off(&a, identity()));
+        assert!(backoff.is_backing_off(&b, identity()));
+
+        backoff.record_success(&b, identity());
+        assert_eq!(backoff.next_retry(identity()), None);
+
+        // The delay starts over after a success.
+        backoff.record_failure(&a, identity(), base_delay);
+        assert!(retry_delay(&backoff, &a, identity()) <= base_delay);
+    }
+
+    #[test]
+    fn seed_health_store_round_trip() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let seed_health_store = SeedHealthStore::new(&store).unwrap();
+        let a = url("https://a.example.com");
+        let b = url("https://b.example.com");
+        assert!(seed_health_store.list().unwrap().is_empty());
+
+        seed_health_store.record_failure(&a, &anyhow::anyhow!("connection refused"));
+        seed_health_store.record_failure(&a, &anyhow::anyhow!("timed out"));
+        seed_health_store.record_success(&b, std::time::Duration::from_millis(250));
+        seed_health_store.record_failure(&b, &anyhow::anyhow!("connection refused"));
+
+        // The health is persisted.
+        let seed_health_store = SeedHealthStore::new(&store).unwrap();
+        let mut healths = seed_health_store.list().unwrap();
+        healths.sort_by(|x, y| x.seed.cmp(&y.seed));
+        assert_eq!(
+            healths
+                .iter()
+                .map(|health| (
+                    health.seed.as_str(),
+                    health.last_success.is_some(),
+                    health.latency,
+                    health.consecutive_failures,
+                    health.last_error.as_ref().map(|err| err.message.as_str()),
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://a.example.com/", false, None, 2, Some("timed out")),
+                (
+                    "https://b.example.com/",
+                    true,
+                    Some(250),
+                    1,
+                    Some("connection refused")
+                ),
+            ]
+        );
+
+        // A success resets the failure count but keeps the last error.
+        seed_health_store.record_success(&a, std::time::Duration::from_millis(100));
+        let health = seed_health_store
+            .list()
+            .unwrap()
+            .into_iter()
+            .find(|health| health.seed == a.to_string())
+            .unwrap();
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.latency, Some(100));
+        assert_eq!(health.last_error.unwrap().message, "timed out");
+    }
+
+    #[test]
+    fn fetch_order_prefers_known_seeds() {
+        assert_eq!(
+            fetch_order(
+                &[
+                    url("https://known.example.com"),
+                    url("https://b.example.com")
+                ],
+                &[
+                    url("https://a.example.com"),
+                    url("https://b.example.com"),
+                    url("https://known.example.com"),
+                ]
+            ),
+            vec![
+                url("https://known.example.com"),
+                url("https://b.example.com"),
+                url("https://a.example.com"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_fails_over_until_quorum() {
+        let seeds = [
+            "https://failing",
+            "https://missing",
+            "https://one",
+            "https://two",
+        ];
+
+        let (result, tried) = fetch_fake_seeds(&seeds, Some(1)).await;
+        assert_eq!(result, Ok((false, 1)));
+        assert_eq!(tried, vec!["failing", "missing", "one"]);
+
+        let (result, tried) = fetch_fake_seeds(&seeds, Some(2)).await;
+        assert_eq!(result, Ok((false, 1)));
+        assert_eq!(tried, vec!["failing", "missing", "one", "two"]);
+
+        let (result, tried) = fetch_fake_seeds(&["https://updated", "https://one"], None).await;
+        assert_eq!(result, Ok((true, 0)));
+        assert_eq!(tried, vec!["updated", "one"]);
+    }
+
+    #[tokio::test]
+    async fn fetch_fails_if_no_seed_provides_project() {
+        let (result, _) = fetch_fake_seeds(&["https://failing", "https://missing"], Some(1)).await;
+        assert_eq!(result, Err(1));
+
+        let (result, _) = fetch_fake_seeds(&["https://missing"], Some(1)).await;
+        assert_eq!(result, Ok((false, 0)));
+
+        let (result, tried) = fetch_fake_seeds(&[], Some(1)).await;
+        assert_eq!(result, Ok((false, 0)));
+        assert!(tried.is_empty());
+    }
+
+    /// Run [`clone_from_seeds`] with fake steps determined by the seeds’ host names and return
+    /// the result together with the steps that were run. Fetching the identity behaves like
+    /// [`fake_fetch`].
+    async fn clone_fake_seeds(
+        seeds: &[&str],
+        valid_identity: bool,
+    ) -> (Result<String, CloneError>, Vec<String>) {
+        let steps = std::cell::RefCell::new(vec![]);
+        let result = clone_from_seeds(
+            seeds.iter().map(|seed| url(seed)).collect(),
+            |seed| {
+                let host = seed.host_str().unwrap_or_default().to_string();
+                steps.borrow_mut().push(format!("identity {host}"));
+                future::ready(
+                    fake_fetch(&seed).map(|result| !matches!(result, FetchResult::NotFound)),
+                )
+            },
+            || {
+                steps.borrow_mut().push("verify".to_string());
+                future::ready(if valid_identity {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("invalid project identity"))
+                })
+            },
+            |seed| {
+                let host = seed.host_str().unwrap_or_default().to_string();
+                steps.borrow_mut().push(format!("fetch {host}"));
+                future::ready(fake_fetch(&seed))
+            },
+        )
+        .await;
+        (
+            result.map(|seed| seed.host_str().unwrap_or_default().to_string()),
+            steps.into_inner(),
+        )
+    }
+
+    #[tokio::test]
+    async fn clone_verifies_identity_before_fetching_remotes() {
+        let (result, steps) = clone_fake_seeds(
+            &[
+                "https://failing",
+                "https://missing",
+                "https://one",
+                "https://two",
+            ],
+            true,
+        )
+        .await;
+        assert_eq!(result.unwrap(), "one");
+        assert_eq!(
+            steps,
+            vec![
+                "identity failing",
+                "identity missing",
+                "identity one",
+                "verify",
+                "fetch one"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn clone_does_not_track_invalid_project() {
+        let (result, steps) = clone_fake_seeds(&["https://one", "https://two"], false).await;
+        assert!(matches!(result, Err(CloneError::Verification(_))));
+        assert_eq!(steps, vec!["identity one", "verify"]);
+    }
+
+    #[tokio::test]
+    async fn clone_fails_if_no_seed_provides_project() {
+        let (result, steps) = clone_fake_seeds(&["https://failing", "https://missing"], true).await;
+        match result {
+            Err(CloneError::NotFound { errors }) => assert_eq!(errors, vec!["connection refused"]),
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert_eq!(steps, vec!["identity failing", "identity missing"]);
+    }
+
+    #[tokio::test]
+    async fn clone_project_not_found() {
+        let test_peer = crate::peer::test::TestPeer::new();
+        let (handle, _) = create(
+            test_peer.peer.clone(),
+            vec![url("http://127.0.0.1:1/")],
+            None,
+            std::time::Duration::from_secs(1000),
+            &test_peer.store,
+        )
+        .await
+        .unwrap();
+
+        match handle.clone_project(identity(), None).await {
+            Err(CloneError::NotFound { errors }) => assert_eq!(errors.len(), 1),
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(handle.get_seeds(identity()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn add_pushes_contributions() {
+        let test_peer = crate::peer::test::TestPeer::new();
+        let (handle, _) = create(
+            test_peer.peer.clone(),
+            vec![],
+            None,
+            std::time::Duration::from_secs(1000),
+            &test_peer.store,
+        )
+        .await
+        .unwrap();
+
+        handle.add(identity()).await;
+        for action in [
+            SyncAction::PushEvents(identity()),
+            SyncAction::FetchIdentity(identity()),
+            SyncAction::PushContributions(identity()),
+        ] {
+            assert!(handle.identity_queue.handles.contains_key(&action));
+        }
+    }
+
+    #[test]
+    fn rejected_refs_from_porcelain_output() {
+        let output = "To https://seed.example.com/hnrk\n\
+            !\trefs/namespaces/hnrk/refs/heads/main:refs/remotes/hyn/heads/main\t[rejected] (non-fast-forward)\n\
+            !\trefs/namespaces/hnrk/refs/tags/v1:refs/remotes/hyn/tags/v1\t[rejected] (atomic push failed)\n\
+            =\trefs/namespaces/hnrk/refs/rad/signed_refs:refs/remotes/hyn/rad/signed_refs\t[up to date]\n\
+            Done\n";
+
+        assert_eq!(
+            rejected_refs(output),
+            vec![
+                RejectedRef {
+                    name: "refs/heads/main".to_string(),
+                    reason: "non-fast-forward".to_string(),
+                },
+                RejectedRef {
+                    name: "refs/tags/v1".to_string(),
+                    reason: "atomic push failed".to_string(),
+                },
+            ]
+        );
+    }
+}
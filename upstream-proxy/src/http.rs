@@ -172,16 +172,23 @@ pub fn guard_self_revision(
     peer: &crate::peer::Peer,
     revision: Option<radicle_source::Revision<PeerId>>,
 ) -> Option<radicle_source::Revision<PeerId>> {
-    revision.map(|r| {
-        if let radicle_source::Revision::Branch { name, peer_id } = r {
-            radicle_source::Revision::Branch {
-                name,
-                peer_id: guard_self_peer_id(peer, peer_id),
-            }
-        } else {
-            r
+    revision.map(|r| guard_revision_peer_id(peer, r))
+}
+
+/// Like [`guard_self_revision`] for a revision that is always given.
+#[must_use]
+pub fn guard_revision_peer_id(
+    peer: &crate::peer::Peer,
+    revision: radicle_source::Revision<PeerId>,
+) -> radicle_source::Revision<PeerId> {
+    if let radicle_source::Revision::Branch { name, peer_id } = revision {
+        radicle_source::Revision::Branch {
+            name,
+            peer_id: guard_self_peer_id(peer, peer_id),
         }
-    })
+    } else {
+        revision
+    }
 }
 
 #[cfg(test)]
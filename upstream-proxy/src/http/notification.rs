@@ -40,27 +40,12 @@ mod handler {
             new: current_status,
         }]);
 
-        let peer_notifications = ctx
-            .peer_events()
-            .filter_map(|event| future::ready(crate::notification::from_peer_event(event)));
-
-        let git_fetch_notifications =
-            ctx.git_fetch
-                .updates()
-                .map(|id| Notification::ProjectUpdated {
-                    urn: link_identities::Urn::new(id),
-                });
-
-        let monorepo_local_update_notifications = ctx
-            .watch_monorepo
-            .updates()
-            .map(|id| Notification::ProjectUpdated { urn: id });
-
-        let notifications = futures::stream::select_all(vec![
-            peer_notifications.boxed(),
-            git_fetch_notifications.boxed(),
-            monorepo_local_update_notifications.boxed(),
-        ]);
+        let notifications = crate::notification::local_peer_notifications(
+            ctx.peer_events(),
+            &ctx.git_fetch,
+            &ctx.watch_monorepo,
+            ctx.peer.librad_peer().peer_id(),
+        );
 
         Ok(sse::reply(
             sse::keep_alive().stream(
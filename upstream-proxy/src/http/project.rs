@@ -238,21 +238,23 @@ mod handler {
 
     /// List all failed projects.
     pub async fn list_failed(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
-        let projects = project::Projects::list(&ctx.peer).await?;
+        let projects = project::Projects::list(&ctx.peer, &ctx.git_fetch).await?;
 
         Ok(reply::json(&projects.failures))
     }
 
     /// List all projects the current user has contributed to.
     pub async fn list_owner_contributed(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
-        let projects = project::Projects::list(&ctx.peer).await?;
+        let projects = project::Projects::list(&ctx.peer, &ctx.git_fetch).await?;
 
         Ok(reply::json(&projects.contributed))
     }
 
     /// List all projects tracked by the current user.
     pub async fn list_owner_tracked(ctx: context::Unsealed) -> Result<impl Reply, Rejection> {
-        let projects = project::Projects::list(&ctx.peer).await?.tracked;
+        let projects = project::Projects::list(&ctx.peer, &ctx.git_fetch)
+            .await?
+            .tracked;
 
         Ok(reply::json(&projects))
     }
@@ -310,7 +312,7 @@ mod handler {
         project_urn: Urn,
         ctx: context::Unsealed,
     ) -> Result<impl Reply, Rejection> {
-        let patches = patch::list(&ctx.peer, project_urn)
+        let patches = patch::list(&ctx.peer, &ctx.event_log, project_urn)
             .await
             .map_err(Error::from)?;
 
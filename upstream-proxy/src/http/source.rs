@@ -17,16 +17,47 @@ use crate::{context, http};
 
 /// Combination of all source filters.
 pub fn filters(ctx: context::Context) -> BoxedFilter<(impl Reply,)> {
-    blob_filter(ctx.clone())
+    archive_filter(ctx.clone())
+        .or(blame_filter(ctx.clone()))
+        .or(blob_filter(ctx.clone()))
         .or(branches_filter(ctx.clone()))
         .or(commit_filter(ctx.clone()))
         .or(commits_filter(ctx.clone()))
+        .or(compare_filter(ctx.clone()))
         .or(local_state_filter())
+        .or(raw_filter(ctx.clone()))
+        .or(search_filter(ctx.clone()))
         .or(tags_filter(ctx.clone()))
         .or(tree_filter(ctx))
         .boxed()
 }
 
+/// `GET /archive/<project_urn>?revision=<revision>&format=<tar.gz|zip>`
+fn archive_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("archive")
+        .and(path::param::<Urn>())
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_qs::<ArchiveQuery>())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::archive)
+}
+
+/// `GET /blame/<project_urn>?revision=<revision>&path=<path>`
+fn blame_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("blame")
+        .and(path::param::<Urn>())
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_qs::<BlameQuery>())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::blame)
+}
+
 /// `GET /blob/<project_urn>?revision=<revision>&path=<path>`
 fn blob_filter(
     ctx: context::Context,
@@ -65,7 +96,7 @@ fn commit_filter(
         .and_then(handler::commit)
 }
 
-/// `GET /commits/<project_urn>?revision=<revision>`
+/// `GET /commits/<project_urn>?revision=<revision>&path=<path>&limit=<limit>&after=<sha1>`
 fn commits_filter(
     ctx: context::Context,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -77,6 +108,19 @@ fn commits_filter(
         .and_then(handler::commits)
 }
 
+/// `GET /compare/<project_urn>?base=<revision>&head=<revision>`
+fn compare_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("compare")
+        .and(path::param::<Urn>())
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_qs::<CompareQuery>())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::compare)
+}
+
 /// `GET /local-state?path=<path>`
 fn local_state_filter() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path("local-state")
@@ -85,6 +129,32 @@ fn local_state_filter() -> impl Filter<Extract = impl Reply, Error = Rejection>
         .and_then(handler::local_state)
 }
 
+/// `GET /raw/<project_urn>?revision=<revision>&path=<path>`
+fn raw_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("raw")
+        .and(path::param::<Urn>())
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_qs::<RawQuery>())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::raw)
+}
+
+/// `GET /search/<project_urn>?revision=<revision>&q=<query>&pathPrefix=<path>`
+fn search_filter(
+    ctx: context::Context,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path("search")
+        .and(path::param::<Urn>())
+        .and(path::end())
+        .and(warp::get())
+        .and(http::with_qs::<SearchQuery>())
+        .and(http::with_context_unsealed(ctx))
+        .and_then(handler::search)
+}
+
 /// `GET /tags/<project_urn>?peer_id=<peer_id>`
 fn tags_filter(
     ctx: context::Context,
@@ -113,14 +183,124 @@ fn tree_filter(
 
 /// Source handlers for conversion between core domain and http request fullfilment.
 mod handler {
-    use warp::{reply, Rejection, Reply};
-
+    use anyhow::Context as _;
+    use warp::{
+        http::header::{
+            CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE,
+            X_CONTENT_TYPE_OPTIONS,
+        },
+        reply, Rejection, Reply,
+    };
+
+    use librad::git::types::{Reference, Single};
+    use link_crypto::PeerId;
     use link_identities::git::Urn;
     use radicle_git_ext::Oid;
-    use radicle_source::surf::vcs::git::RefScope;
+    use radicle_source::surf::vcs::git::{self, RefScope};
 
     use crate::{browser, context, error};
 
+    /// Maximum number of bytes in a chunk of a streamed response body.
+    const BODY_CHUNK_SIZE: usize = 64 * 1024;
+
+    /// Fetch an archive of the tree of a revision.
+    pub async fn archive(
+        project_urn: Urn,
+        super::ArchiveQuery {
+            format,
+            peer_id,
+            revision,
+        }: super::ArchiveQuery,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        let peer_id = super::http::guard_self_peer_id(&ctx.peer, peer_id);
+        let revision = super::http::guard_self_revision(&ctx.peer, revision);
+
+        let project =
+            crate::daemon::state::get_project(ctx.peer.librad_peer(), project_urn.clone())
+                .await
+                .map_err(error::Error::from)?
+                .ok_or(error::Error::ProjectNotFound)?;
+        let branch = crate::daemon::state::get_branch(
+            ctx.peer.librad_peer(),
+            project_urn.clone(),
+            peer_id,
+            None,
+        )
+        .await
+        .map_err(error::Error::from)?;
+        let commit = resolve_revision(&ctx, project_urn, branch, revision).await?;
+
+        // The name is used in a header value and as a directory name, so we only keep characters
+        // that are safe in both.
+        let name = project
+            .subject()
+            .name
+            .to_string()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect::<String>();
+        let prefix = format!("{name}-{}", &commit.to_string()[..7]);
+        let body = stream_body(&ctx.peer, {
+            let prefix = prefix.clone();
+            move |repo, writer| crate::source::archive(repo, commit, format, &prefix, writer)
+        });
+
+        Ok(reply::with_header(
+            reply::with_header(
+                reply::Response::new(body),
+                CONTENT_TYPE,
+                format.content_type(),
+            ),
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{prefix}.{}\"", format.extension()),
+        ))
+    }
+
+    /// Fetch the [`crate::source::Blame`] of a file.
+    pub async fn blame(
+        project_urn: Urn,
+        super::BlameQuery {
+            path,
+            peer_id,
+            revision,
+        }: super::BlameQuery,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        let peer_id = super::http::guard_self_peer_id(&ctx.peer, peer_id);
+        let revision = super::http::guard_self_revision(&ctx.peer, revision);
+
+        let branch = crate::daemon::state::get_branch(
+            ctx.peer.librad_peer(),
+            project_urn.clone(),
+            peer_id,
+            None,
+        )
+        .await
+        .map_err(error::Error::from)?;
+        let commit = resolve_revision(&ctx, project_urn, branch, revision).await?;
+        let blame = ctx
+            .peer
+            .monorepo_unblock({
+                let cache = ctx.blame_cache.clone();
+                let path = path.clone();
+                move |repo| crate::source::Blame::compute(&repo, &cache, commit, &path)
+            })
+            .await
+            .map_err(error::Error::from)?;
+
+        match blame {
+            Some(blame) => Ok(reply::json(&*blame)),
+            None => Err(not_found(format!("File {path} not found"))),
+        }
+    }
+
     /// Fetch a [`radicle_source::Blob`].
     pub async fn blob(
         project_urn: Urn,
@@ -190,24 +370,79 @@ mod handler {
         Ok(reply::json(&commit))
     }
 
-    /// Fetch the list of [`radicle_source::Commit`] from a branch.
+    /// Fetch a page of the commit history of a revision, optionally restricted to a path.
     pub async fn commits(
         ctx: context::Unsealed,
         project_urn: Urn,
-        super::CommitsQuery { revision }: super::CommitsQuery,
+        super::CommitsQuery {
+            revision,
+            path,
+            limit,
+            after,
+        }: super::CommitsQuery,
     ) -> Result<impl Reply, Rejection> {
         let revision = super::http::guard_self_revision(&ctx.peer, revision);
 
         let default_branch =
-            crate::daemon::state::find_default_branch(ctx.peer.librad_peer(), project_urn)
+            crate::daemon::state::find_default_branch(ctx.peer.librad_peer(), project_urn.clone())
                 .await
                 .map_err(error::Error::from)?;
-        let commits = browser::using(&ctx.peer, default_branch, |browser| {
-            radicle_source::commits(browser, revision)
+        let head =
+            resolve_revision(&ctx, project_urn, default_branch.clone(), revision.clone()).await?;
+        // Like the commits of `radicle_source`, the stats are computed from the history of the
+        // revision.
+        let stats = browser::using(&ctx.peer, default_branch, |browser| {
+            if let Some(revision) = revision {
+                browser.rev(git::Rev::try_from(revision)?)?;
+            }
+            Ok(browser.get_stats()?)
         })
         .map_err(error::Error::from)?;
+        let history = ctx
+            .peer
+            .monorepo_unblock(move |repo| {
+                crate::source::History::walk(
+                    &repo,
+                    head,
+                    path.as_deref(),
+                    after.map(|after| *after),
+                    limit,
+                )
+            })
+            .await
+            .map_err(error::Error::from)?;
+
+        Ok(reply::json(&super::Commits { history, stats }))
+    }
+
+    /// Compare two revisions and fetch the [`crate::source::Comparison`].
+    pub async fn compare(
+        project_urn: Urn,
+        super::CompareQuery { base, head }: super::CompareQuery,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        let base = super::http::guard_revision_peer_id(&ctx.peer, base);
+        let head = super::http::guard_revision_peer_id(&ctx.peer, head);
+
+        let default_branch =
+            crate::daemon::state::find_default_branch(ctx.peer.librad_peer(), project_urn.clone())
+                .await
+                .map_err(error::Error::from)?;
+        let base = resolve_revision(
+            &ctx,
+            project_urn.clone(),
+            default_branch.clone(),
+            Some(base),
+        )
+        .await?;
+        let head = resolve_revision(&ctx, project_urn, default_branch, Some(head)).await?;
+        let comparison = ctx
+            .peer
+            .monorepo_unblock(move |repo| crate::source::Comparison::between(&repo, base, head))
+            .await
+            .map_err(error::Error::from)?;
 
-        Ok(reply::json(&commits))
+        Ok(reply::json(&comparison))
     }
 
     /// Fetch the list [`radicle_source::Branch`] for a local repository.
@@ -220,6 +455,109 @@ mod handler {
         Ok(reply::json(&state))
     }
 
+    /// Fetch the content of a file.
+    pub async fn raw(
+        project_urn: Urn,
+        super::RawQuery {
+            path,
+            peer_id,
+            revision,
+        }: super::RawQuery,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        let peer_id = super::http::guard_self_peer_id(&ctx.peer, peer_id);
+        let revision = super::http::guard_self_revision(&ctx.peer, revision);
+
+        let branch = crate::daemon::state::get_branch(
+            ctx.peer.librad_peer(),
+            project_urn.clone(),
+            peer_id,
+            None,
+        )
+        .await
+        .map_err(error::Error::from)?;
+        let commit = resolve_revision(&ctx, project_urn, branch, revision).await?;
+        let blob = ctx
+            .peer
+            .monorepo_unblock({
+                let path = path.clone();
+                move |repo| crate::source::RawBlob::find(&repo, commit, &path)
+            })
+            .await
+            .map_err(error::Error::from)?
+            .ok_or_else(|| not_found(format!("File {path} not found")))?;
+        let content_type = blob.content_type.clone();
+        let size = blob.size;
+        let body = stream_body(&ctx.peer, move |repo, writer| blob.write(repo, writer));
+
+        // Files are served from the origin of the API. We prevent browsers from rendering them
+        // as active content.
+        Ok(reply::with_header(
+            reply::with_header(
+                reply::with_header(
+                    reply::with_header(reply::Response::new(body), CONTENT_TYPE, content_type),
+                    CONTENT_LENGTH,
+                    size.to_string(),
+                ),
+                X_CONTENT_TYPE_OPTIONS,
+                "nosniff",
+            ),
+            CONTENT_SECURITY_POLICY,
+            "sandbox",
+        ))
+    }
+
+    /// Search the files of a revision and fetch the [`crate::source::SearchResults`].
+    pub async fn search(
+        project_urn: Urn,
+        super::SearchQuery {
+            q,
+            path_prefix,
+            peer_id,
+            revision,
+        }: super::SearchQuery,
+        ctx: context::Unsealed,
+    ) -> Result<impl Reply, Rejection> {
+        if q.trim().is_empty() {
+            return Err(warp::reject::custom(super::http::error::Response {
+                status_code: warp::http::StatusCode::BAD_REQUEST,
+                variant: "INVALID_SEARCH_QUERY",
+                message: "Search query must not be empty".to_string(),
+            }));
+        }
+
+        let peer_id = super::http::guard_self_peer_id(&ctx.peer, peer_id);
+        let revision = super::http::guard_self_revision(&ctx.peer, revision);
+
+        let branch = crate::daemon::state::get_branch(
+            ctx.peer.librad_peer(),
+            project_urn.clone(),
+            peer_id,
+            None,
+        )
+        .await
+        .map_err(error::Error::from)?;
+        let commit = resolve_revision(&ctx, project_urn, branch, revision).await?;
+        let results = ctx
+            .peer
+            .monorepo_unblock({
+                let path_prefix = path_prefix.clone();
+                move |repo| {
+                    crate::source::SearchResults::search(&repo, commit, &q, path_prefix.as_deref())
+                }
+            })
+            .await
+            .map_err(error::Error::from)?;
+
+        match results {
+            Some(results) => Ok(reply::json(&results)),
+            None => Err(not_found(format!(
+                "Directory {} not found",
+                path_prefix.unwrap_or_default()
+            ))),
+        }
+    }
+
     /// Fetch the list [`radicle_source::Tag`].
     pub async fn tags(
         project_urn: Urn,
@@ -258,6 +596,98 @@ mod handler {
 
         Ok(reply::json(&tree))
     }
+
+    /// Resolve `revision` with [`crate::source::resolve_revision`]. Rejects with `NOT_FOUND` if
+    /// the revision does not exist.
+    async fn resolve_revision(
+        ctx: &context::Unsealed,
+        project_urn: Urn,
+        branch: Reference<Single>,
+        revision: Option<radicle_source::Revision<PeerId>>,
+    ) -> Result<git2::Oid, Rejection> {
+        ctx.peer
+            .monorepo_unblock(move |repo| {
+                crate::source::resolve_revision(&repo, &project_urn, &branch, revision)
+            })
+            .await
+            .map_err(error::Error::from)?
+            .ok_or_else(|| not_found("Revision not found".to_string()))
+    }
+
+    /// Create a response body that is streamed from the output of `write`.
+    ///
+    /// `write` runs on a blocking task with the monorepo and writes into a channel that feeds the
+    /// body. If `write` fails, the response is aborted.
+    fn stream_body<F>(peer: &crate::peer::Peer, write: F) -> hyper::Body
+    where
+        F: FnOnce(&git2::Repository, &mut dyn std::io::Write) -> anyhow::Result<()>
+            + Send
+            + 'static,
+    {
+        // The channel is bounded so that a slow client slows down the writer.
+        let (chunk_tx, chunk_rx) = tokio::sync::mpsc::channel(4);
+        let peer = peer.clone();
+        tokio::spawn(async move {
+            let result = peer
+                .monorepo_unblock({
+                    let chunk_tx = chunk_tx.clone();
+                    move |repo| {
+                        let mut writer = std::io::BufWriter::with_capacity(
+                            BODY_CHUNK_SIZE,
+                            BodyWriter { chunk_tx },
+                        );
+                        write(&repo, &mut writer)?;
+                        std::io::Write::flush(&mut writer).context("failed to write response body")
+                    }
+                })
+                .await;
+            if let Err(err) = result {
+                tracing::warn!(?err, "failed to stream response body");
+                // Sending only fails if the client is gone.
+                let _ = chunk_tx
+                    .send(Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("{err:#}"),
+                    )))
+                    .await;
+            }
+        });
+
+        hyper::Body::wrap_stream(futures::stream::unfold(
+            chunk_rx,
+            |mut chunk_rx| async move { chunk_rx.recv().await.map(|chunk| (chunk, chunk_rx)) },
+        ))
+    }
+
+    /// Writer that sends chunks of at most [`BODY_CHUNK_SIZE`] bytes to a body created with
+    /// [`stream_body`].
+    struct BodyWriter {
+        chunk_tx: tokio::sync::mpsc::Sender<std::io::Result<hyper::body::Bytes>>,
+    }
+
+    impl std::io::Write for BodyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(BODY_CHUNK_SIZE);
+            self.chunk_tx
+                .blocking_send(Ok(hyper::body::Bytes::copy_from_slice(&buf[..len])))
+                .map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "response body was dropped")
+                })?;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn not_found(message: String) -> Rejection {
+        warp::reject::custom(super::http::error::Response {
+            status_code: warp::http::StatusCode::NOT_FOUND,
+            variant: "NOT_FOUND",
+            message,
+        })
+    }
 }
 
 /// Query parameters for [`handler::local_state`]
@@ -274,6 +704,82 @@ pub struct LocalStateQuery {
 pub struct CommitsQuery {
     /// Revision to query at.
     revision: Option<radicle_source::Revision<PeerId>>,
+    /// Only include commits that changed this file or directory.
+    path: Option<String>,
+    /// Maximum number of commits to return.
+    limit: Option<usize>,
+    /// Return the commits after this commit. Used to fetch the next page.
+    after: Option<Oid>,
+}
+
+/// Response of [`handler::commits`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commits {
+    #[serde(flatten)]
+    history: crate::source::History,
+    /// Statistics of the whole revision.
+    stats: radicle_source::surf::vcs::git::Stats,
+}
+
+/// Query params for [`handler::compare`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareQuery {
+    /// Revision to compare against.
+    base: radicle_source::Revision<PeerId>,
+    /// Revision to compare.
+    head: radicle_source::Revision<PeerId>,
+}
+
+/// Query params for [`handler::archive`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveQuery {
+    /// File format of the archive.
+    format: crate::source::ArchiveFormat,
+    /// PeerId to scope the query by.
+    peer_id: Option<PeerId>,
+    /// Revision to query at.
+    revision: Option<radicle_source::Revision<PeerId>>,
+}
+
+/// Query params for [`handler::blame`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameQuery {
+    /// Location of the file in tree.
+    path: String,
+    /// PeerId to scope the query by.
+    peer_id: Option<PeerId>,
+    /// Revision to query at.
+    revision: Option<radicle_source::Revision<PeerId>>,
+}
+
+/// Query params for [`handler::raw`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawQuery {
+    /// Location of the file in tree.
+    path: String,
+    /// PeerId to scope the query by.
+    peer_id: Option<PeerId>,
+    /// Revision to query at.
+    revision: Option<radicle_source::Revision<PeerId>>,
+}
+
+/// Query params for [`handler::search`].
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    /// Text to search for.
+    q: String,
+    /// Only search below this directory.
+    path_prefix: Option<String>,
+    /// PeerId to scope the query by.
+    peer_id: Option<PeerId>,
+    /// Revision to query at.
+    revision: Option<radicle_source::Revision<PeerId>>,
 }
 
 /// Bundled query params to pass to the blob handler.
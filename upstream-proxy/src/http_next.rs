@@ -8,9 +8,12 @@ use futures::prelude::*;
 
 mod diagnostics;
 mod identity;
+mod inbox;
 mod keystore;
 mod project;
+mod seeds;
 mod session;
+mod webhooks;
 
 pub fn serve(
     ctx: crate::context::Context,
@@ -72,8 +75,11 @@ fn make_router(ctx: crate::context::Context) -> axum::Router {
         .merge(keystore::router())
         .merge(diagnostics::router())
         .merge(identity::router())
+        .merge(inbox::router())
         .merge(session::router())
         .merge(project::router())
+        .merge(seeds::router())
+        .merge(webhooks::router())
         .layer(axum::Extension(ctx));
 
     axum::Router::new()
@@ -0,0 +1,62 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+use axum::extract::Path;
+use serde::Deserialize;
+
+/// Provides the following endpoints:
+///
+/// * `GET /inbox` to list the inbox items, the most recent item first
+/// * `GET /inbox/count` to get the number of all and of unread inbox items
+/// * `POST /inbox/:id/read` to mark an inbox item as read
+pub fn router() -> axum::Router {
+    axum::Router::new()
+        .route("/inbox", axum::routing::get(list))
+        .route("/inbox/count", axum::routing::get(count))
+        .route("/inbox/:id/read", axum::routing::post(mark_read))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    /// Only return items that have not been read.
+    #[serde(default)]
+    unread: bool,
+}
+
+async fn list(
+    axum::extract::Query(query): axum::extract::Query<ListQuery>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let mut items = ctx.inbox.list()?;
+    if query.unread {
+        items.retain(|item| !item.read);
+    }
+    Ok(axum::response::Json(items))
+}
+
+async fn count(
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let count = ctx.inbox.count()?;
+    Ok(axum::response::Json(count))
+}
+
+async fn mark_read(
+    Path(id): Path<String>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    if ctx.inbox.mark_read(&id)? {
+        Ok(http::StatusCode::NO_CONTENT)
+    } else {
+        Err(super::Error::Custom {
+            status_code: http::StatusCode::NOT_FOUND,
+            variant: "NOT_FOUND",
+            message: "Inbox item not found".to_string(),
+            details: None,
+        })
+    }
+}
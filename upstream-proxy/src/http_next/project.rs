@@ -6,18 +6,104 @@
 
 use anyhow::Context as _;
 use axum::extract::Path;
+use futures::prelude::*;
 
 /// Provides the following endpoints:
-/// * `GET /projects/:urn/patches/:patch_id/events`
-/// * `PUT /projects/:urn/patches/:patch_id/events`
+/// * `POST /projects/clone` fetch a project that is not stored locally from a seed
+/// * `GET /projects/:urn/events` list of topics with event counts
+/// * `GET /projects/:urn/events/:topic?limit=&before=&after=&type=&peerId=`
+/// * `PUT /projects/:urn/events/:topic`
+/// * `GET /projects/:urn/events/:topic/diagnostics` skipped events and invalid event logs
+/// * `GET /projects/:urn/events/:topic/stream?since=<commit>` SSE stream of new events
+/// * `GET /projects/:urn/patches/:peer_id/:patch_name`
+/// * `GET /projects/:urn/patches/:peer_id/:patch_name/diff`
+/// * `GET /projects/:urn/patches/:peer_id/:patch_name/revisions`
+/// * `POST /projects/:urn/patches/:peer_id/:patch_name/merge`
+/// * `POST /projects/:urn/sync` fetch from and push to the project’s seeds
+/// * `GET /projects/:urn/seeds` list the seeds of the project
+/// * `PUT /projects/:urn/seeds` add or pin a seed for the project
+/// * `DELETE /projects/:urn/seeds?url=<seed>` remove a seed from the project
 pub fn router() -> axum::Router {
-    axum::Router::new().route(
-        "/projects/:urn/events/:topic",
-        axum::routing::get(get_event).put(publish_event),
-    )
+    axum::Router::new()
+        .route("/projects/clone", axum::routing::post(clone_project))
+        .route("/projects/:urn/events", axum::routing::get(list_topics))
+        .route(
+            "/projects/:urn/events/:topic",
+            axum::routing::get(get_event).put(publish_event),
+        )
+        .route(
+            "/projects/:urn/events/:topic/diagnostics",
+            axum::routing::get(get_event_diagnostics),
+        )
+        .route(
+            "/projects/:urn/events/:topic/stream",
+            axum::routing::get(stream_events),
+        )
+        .route(
+            "/projects/:urn/patches/:peer_id/:patch_name",
+            axum::routing::get(get_patch),
+        )
+        .route(
+            "/projects/:urn/patches/:peer_id/:patch_name/diff",
+            axum::routing::get(get_patch_diff),
+        )
+        .route(
+            "/projects/:urn/patches/:peer_id/:patch_name/revisions",
+            axum::routing::get(get_patch_revisions),
+        )
+        .route(
+            "/projects/:urn/patches/:peer_id/:patch_name/merge",
+            axum::routing::post(merge_patch),
+        )
+        .route("/projects/:urn/sync", axum::routing::post(sync))
+        .route(
+            "/projects/:urn/seeds",
+            axum::routing::get(list_seeds)
+                .put(add_seed)
+                .delete(remove_seed),
+        )
+}
+
+async fn list_topics(
+    Path(urn): Path<librad::git::Urn>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let topics = ctx
+        .event_log
+        .topics(urn.id)
+        .await
+        .context("failed to list event log topics")?;
+
+    Ok(axum::response::Json(topics))
 }
 
 async fn get_event(
+    Path((urn, topic)): Path<(librad::git::Urn, String)>,
+    axum::extract::Query(query): axum::extract::Query<crate::events::Query>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    match ctx.event_log.query(urn.id, topic, query).await {
+        Ok(events) => Ok(axum::response::Json(events.events)),
+        Err(err) => match err.downcast::<crate::events::CursorNotFound>() {
+            Ok(err) => Err(super::Error::Custom {
+                status_code: http::StatusCode::NOT_FOUND,
+                variant: "CURSOR_NOT_FOUND",
+                message: err.to_string(),
+                details: None,
+            }),
+            Err(err) => Err(err.context("failed to get event log").into()),
+        },
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EventDiagnostics {
+    skipped: Vec<crate::events::SkippedEvent>,
+    invalid_logs: Vec<crate::events::InvalidLog>,
+}
+
+async fn get_event_diagnostics(
     Path((urn, topic)): Path<(librad::git::Urn, String)>,
     super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
 ) -> Result<impl axum::response::IntoResponse, super::Error> {
@@ -27,7 +113,55 @@ async fn get_event(
         .await
         .context("failed to get event log")?;
 
-    Ok(axum::response::Json(events))
+    Ok(axum::response::Json(EventDiagnostics {
+        skipped: events.skipped,
+        invalid_logs: events.invalid_logs,
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StreamEventsQuery {
+    /// Only emit events that are newer than the event held by this commit.
+    since: Option<radicle_git_ext::Oid>,
+}
+
+async fn stream_events(
+    Path((urn, topic)): Path<(librad::git::Urn, String)>,
+    axum::extract::Query(query): axum::extract::Query<StreamEventsQuery>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let identity = urn.id;
+    let local_updates = ctx
+        .watch_monorepo
+        .updates()
+        .filter(move |update| future::ready(update.urn.id == identity))
+        .map(|_| ());
+    let fetched_updates = ctx
+        .git_fetch
+        .updates()
+        .filter(move |update| future::ready(update.identity == identity))
+        .map(|_| ());
+    let shutdown = ctx.rest.shutdown.clone();
+    let updates = stream::select(local_updates, fetched_updates)
+        .take_until(async move { shutdown.notified().await });
+
+    let envelopes = ctx
+        .event_log
+        .watch(identity, topic, query.since, updates)
+        .await
+        .context("failed to get event log")?;
+
+    let sse_events = envelopes.map(|envelope| {
+        let sse_event = axum::response::sse::Event::default();
+        let sse_event = match envelope.commit {
+            Some(commit) => sse_event.id(commit.to_string()),
+            None => sse_event,
+        };
+        sse_event.json_data(envelope)
+    });
+
+    Ok(axum::response::sse::Sse::new(sse_events)
+        .keep_alive(axum::response::sse::KeepAlive::default()))
 }
 
 async fn publish_event(
@@ -35,9 +169,231 @@ async fn publish_event(
     event: axum::extract::Json<crate::events::Event>,
     super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
 ) -> Result<impl axum::response::IntoResponse, super::Error> {
-    ctx.event_log
-        .publish(urn.id, &topic, event.0)
+    match ctx.event_log.publish(urn.id, &topic, event.0).await {
+        Ok(()) => Ok(http::StatusCode::CREATED),
+        Err(err) => match err.downcast::<crate::events::InvalidEvent>() {
+            Ok(err) => Err(super::Error::Custom {
+                status_code: http::StatusCode::BAD_REQUEST,
+                variant: "INVALID_EVENT",
+                message: err.to_string(),
+                details: std::error::Error::source(&err).map(ToString::to_string),
+            }),
+            Err(err) => Err(err.context("failed to publish event").into()),
+        },
+    }
+}
+
+async fn get_patch(
+    Path((urn, peer_id, patch_name)): Path<(librad::git::Urn, librad::PeerId, String)>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<axum::response::Json<crate::patch::Patch>, super::Error> {
+    let maybe_patch = crate::patch::get(&ctx.peer, &ctx.event_log, urn, peer_id, &patch_name)
+        .await
+        .context("failed to get patch")?;
+
+    match maybe_patch {
+        Some(patch) => Ok(axum::response::Json(patch)),
+        None => Err(super::Error::Custom {
+            status_code: http::StatusCode::NOT_FOUND,
+            variant: "NOT_FOUND",
+            message: "Patch not found".to_string(),
+            details: None,
+        }),
+    }
+}
+
+async fn get_patch_diff(
+    Path((urn, peer_id, patch_name)): Path<(librad::git::Urn, librad::PeerId, String)>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<axum::response::Json<crate::patch::PatchDiff>, super::Error> {
+    let maybe_diff = crate::patch::diff(&ctx.peer, urn, peer_id, &patch_name)
+        .await
+        .context("failed to get patch diff")?;
+
+    match maybe_diff {
+        Some(diff) => Ok(axum::response::Json(diff)),
+        None => Err(super::Error::Custom {
+            status_code: http::StatusCode::NOT_FOUND,
+            variant: "NOT_FOUND",
+            message: "Patch not found".to_string(),
+            details: None,
+        }),
+    }
+}
+
+async fn get_patch_revisions(
+    Path((urn, peer_id, patch_name)): Path<(librad::git::Urn, librad::PeerId, String)>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<axum::response::Json<Vec<crate::patch::RevisionDiff>>, super::Error> {
+    let maybe_revisions =
+        crate::patch::revisions(&ctx.peer, &ctx.event_log, urn, peer_id, &patch_name)
+            .await
+            .context("failed to get patch revisions")?;
+
+    match maybe_revisions {
+        Some(revisions) => Ok(axum::response::Json(revisions)),
+        None => Err(super::Error::Custom {
+            status_code: http::StatusCode::NOT_FOUND,
+            variant: "NOT_FOUND",
+            message: "Patch not found".to_string(),
+            details: None,
+        }),
+    }
+}
+
+async fn merge_patch(
+    Path((urn, peer_id, patch_name)): Path<(librad::git::Urn, librad::PeerId, String)>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<axum::response::Json<crate::patch::MergeOutcome>, super::Error> {
+    use crate::patch::MergeError;
+
+    match crate::patch::merge(&ctx.peer, &ctx.event_log, urn, peer_id, &patch_name).await {
+        Ok(outcome) => Ok(axum::response::Json(outcome)),
+        Err(err) => {
+            let message = err.to_string();
+            let (status_code, variant, details) = match err {
+                MergeError::Internal(err) => {
+                    return Err(super::Error::Internal(err.context("failed to merge patch")))
+                },
+                MergeError::NotFound => (http::StatusCode::NOT_FOUND, "NOT_FOUND", None),
+                MergeError::NotDelegate => (http::StatusCode::FORBIDDEN, "NOT_DELEGATE", None),
+                MergeError::Conflicts { paths } => (
+                    http::StatusCode::CONFLICT,
+                    "MERGE_CONFLICT",
+                    Some(serde_json::to_string(&paths).context("failed to serialize paths")?),
+                ),
+                MergeError::DefaultBranchChanged => {
+                    (http::StatusCode::CONFLICT, "DEFAULT_BRANCH_CHANGED", None)
+                },
+            };
+            Err(super::Error::Custom {
+                status_code,
+                variant,
+                message,
+                details,
+            })
+        },
+    }
+}
+
+async fn sync(
+    Path(urn): Path<librad::git::Urn>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let results = ctx
+        .git_fetch
+        .sync(urn.id)
+        .await
+        .context("failed to sync project")?;
+
+    Ok(axum::response::Json(results))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloneBody {
+    urn: librad::git::Urn,
+    /// Seed to clone the project from. If not provided, all configured seeds are tried.
+    seed: Option<String>,
+}
+
+async fn clone_project(
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+    body: axum::extract::Json<CloneBody>,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    use crate::git_fetch::CloneError;
+
+    let CloneBody { urn, seed } = body.0;
+    let seed = seed.as_deref().map(parse_seed_url).transpose()?;
+    let seed = match ctx.git_fetch.clone_project(urn.id, seed).await {
+        Ok(seed) => seed,
+        Err(CloneError::NotFound { errors }) => {
+            return Err(super::Error::Custom {
+                status_code: http::StatusCode::NOT_FOUND,
+                variant: "NOT_FOUND",
+                message: "The project was not found on any seed".to_string(),
+                details: Some(
+                    serde_json::to_string(&errors).context("failed to serialize errors")?,
+                ),
+            })
+        },
+        Err(CloneError::Verification(err)) => {
+            return Err(super::Error::Custom {
+                status_code: http::StatusCode::UNPROCESSABLE_ENTITY,
+                variant: "PROJECT_VERIFICATION_FAILED",
+                message: "The project identity could not be verified".to_string(),
+                details: Some(format!("{err:#}")),
+            })
+        },
+    };
+
+    let project = crate::project::get(&ctx.peer, urn, Some(seed))
         .await
-        .context("failed to publish event")?;
-    Ok(http::StatusCode::CREATED)
+        .context("failed to get project")?;
+    Ok((http::StatusCode::CREATED, axum::response::Json(project)))
+}
+
+async fn list_seeds(
+    Path(urn): Path<librad::git::Urn>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    Ok(axum::response::Json(ctx.git_fetch.get_seeds(urn.id)))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddSeedBody {
+    url: String,
+    /// Pin the seed so that it is always tried first and never removed automatically.
+    #[serde(default)]
+    pinned: bool,
+}
+
+async fn add_seed(
+    Path(urn): Path<librad::git::Urn>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+    body: axum::extract::Json<AddSeedBody>,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    use crate::git_fetch::AddSeedError;
+
+    let seed_url = parse_seed_url(&body.url)?;
+    match ctx.git_fetch.add_seed(urn.id, seed_url, body.pinned).await {
+        Ok(()) => Ok(axum::response::Json(ctx.git_fetch.get_seeds(urn.id))),
+        Err(AddSeedError::NotProvided) => Err(super::Error::Custom {
+            status_code: http::StatusCode::UNPROCESSABLE_ENTITY,
+            variant: "SEED_DOES_NOT_PROVIDE_PROJECT",
+            message: format!("The seed {} does not provide the project", body.url),
+            details: None,
+        }),
+        Err(AddSeedError::Unreachable(err)) => Err(super::Error::Custom {
+            status_code: http::StatusCode::BAD_GATEWAY,
+            variant: "SEED_UNREACHABLE",
+            message: format!("Failed to query the seed {}", body.url),
+            details: Some(format!("{err:#}")),
+        }),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RemoveSeedQuery {
+    url: String,
+}
+
+async fn remove_seed(
+    Path(urn): Path<librad::git::Urn>,
+    axum::extract::Query(query): axum::extract::Query<RemoveSeedQuery>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let seed_url = parse_seed_url(&query.url)?;
+    ctx.git_fetch.remove_seed(urn.id, &seed_url);
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+fn parse_seed_url(url: &str) -> Result<rad_common::Url, super::Error> {
+    rad_common::Url::parse(url).map_err(|err| super::Error::Custom {
+        status_code: http::StatusCode::BAD_REQUEST,
+        variant: "INVALID_SEED_URL",
+        message: format!("Invalid seed URL {url}"),
+        details: Some(err.to_string()),
+    })
 }
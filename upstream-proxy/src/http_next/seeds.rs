@@ -0,0 +1,17 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+/// Provides `GET /seeds` endpoint that returns the health of the Git+HTTP seeds.
+pub fn router() -> axum::Router {
+    axum::Router::new().route("/seeds", axum::routing::get(list))
+}
+
+async fn list(
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let seeds = ctx.git_fetch.seed_health()?;
+    Ok(axum::response::Json(seeds))
+}
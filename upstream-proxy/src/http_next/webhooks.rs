@@ -0,0 +1,121 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+use axum::extract::Path;
+use serde::{Deserialize, Serialize};
+
+/// Provides the following endpoints:
+///
+/// * `GET /webhooks` to list all webhooks
+/// * `POST /webhooks` to add a webhook
+/// * `DELETE /webhooks/:id` to remove a webhook
+/// * `GET /webhooks/:id/deliveries` to get the delivery log of a webhook
+/// * `POST /webhooks/:id/test` to send a `ping` event to a webhook
+pub fn router() -> axum::Router {
+    axum::Router::new()
+        .route("/webhooks", axum::routing::get(list).post(add))
+        .route("/webhooks/:id", axum::routing::delete(remove))
+        .route("/webhooks/:id/deliveries", axum::routing::get(deliveries))
+        .route("/webhooks/:id/test", axum::routing::post(test))
+}
+
+async fn list(
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let webhooks = ctx.webhooks.list()?;
+    Ok(axum::response::Json(webhooks))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddWebhook {
+    url: String,
+    /// Key for signing payloads. A random secret is generated if not provided.
+    secret: Option<String>,
+    /// Event types to deliver. If empty, all events are delivered.
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AddedWebhook {
+    #[serde(flatten)]
+    webhook: crate::webhooks::Webhook,
+    /// Key for signing payloads. This is the only time the secret is returned.
+    secret: String,
+}
+
+async fn add(
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+    axum::extract::Json(params): axum::extract::Json<AddWebhook>,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    let url = match url::Url::parse(&params.url) {
+        Ok(url) if url.scheme() == "http" => url,
+        Ok(_) => {
+            return Err(super::Error::Custom {
+                status_code: http::StatusCode::BAD_REQUEST,
+                variant: "INVALID_WEBHOOK_URL",
+                message: format!("Invalid webhook URL {}", params.url),
+                details: Some("only http URLs are supported".to_string()),
+            })
+        },
+        Err(err) => {
+            return Err(super::Error::Custom {
+                status_code: http::StatusCode::BAD_REQUEST,
+                variant: "INVALID_WEBHOOK_URL",
+                message: format!("Invalid webhook URL {}", params.url),
+                details: Some(err.to_string()),
+            })
+        },
+    };
+
+    let (webhook, secret) = ctx.webhooks.add(url, params.secret, params.events)?;
+    Ok((
+        http::StatusCode::CREATED,
+        axum::response::Json(AddedWebhook { webhook, secret }),
+    ))
+}
+
+async fn remove(
+    Path(id): Path<String>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    if ctx.webhooks.remove(&id)? {
+        Ok(http::StatusCode::NO_CONTENT)
+    } else {
+        Err(not_found())
+    }
+}
+
+async fn deliveries(
+    Path(id): Path<String>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    match ctx.webhooks.deliveries(&id)? {
+        Some(deliveries) => Ok(axum::response::Json(deliveries)),
+        None => Err(not_found()),
+    }
+}
+
+async fn test(
+    Path(id): Path<String>,
+    super::extract::UnsealedContext(ctx): super::extract::UnsealedContext,
+) -> Result<impl axum::response::IntoResponse, super::Error> {
+    match ctx.webhooks.test(&id).await? {
+        Some(delivery) => Ok(axum::response::Json(delivery)),
+        None => Err(not_found()),
+    }
+}
+
+fn not_found() -> super::Error {
+    super::Error::Custom {
+        status_code: http::StatusCode::NOT_FOUND,
+        variant: "NOT_FOUND",
+        message: "Webhook not found".to_string(),
+        details: None,
+    }
+}
@@ -0,0 +1,664 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Persistent inbox of notifications that are relevant to the user.
+//!
+//! The [`Runner`] observes the [`Notification`]s of the local peer and stores an [`Item`] in the
+//! kv store when
+//!
+//! * another peer created a patch on a project we are a delegate of,
+//! * another peer commented on one of our patches,
+//! * a project request was cloned, or
+//! * another peer updated branches of a project we are a delegate of.
+//!
+//! The same change may be observed multiple times, for example through gossip and through a fetch
+//! from a Git seed. Item IDs are derived from the content of the item so that such a change
+//! results in a single item.
+//!
+//! Comments that were published before the inbox was created are ignored so that the inbox is not
+//! flooded when it is used for the first time. Only the most recent items are kept.
+
+use anyhow::Context as _;
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use link_crypto::PeerId;
+use link_identities::git::Urn;
+
+use crate::{notification::Notification, watch_monorepo::RefChange};
+
+/// Key in the state bucket that holds the time the inbox was created.
+const SINCE_KEY: &str = "since";
+
+/// Number of items kept in the inbox. The oldest items are removed when more items are added.
+const MAX_ITEMS: usize = 500;
+
+pub fn create(
+    peer: crate::peer::Peer,
+    event_log: crate::events::EventLog,
+    store: &kv::Store,
+) -> anyhow::Result<(Handle, Runner)> {
+    let handle = Handle::open(store)?;
+    let state = store
+        .bucket::<&'static str, kv::Json<u64>>(Some("inbox_state"))
+        .context("failed to get inbox state bucket")?;
+
+    let since = match state.get(SINCE_KEY).context("failed to read inbox state")? {
+        Some(since) => since.0,
+        None => {
+            let now = unix_timestamp_ms();
+            state
+                .set(SINCE_KEY, kv::Json(now))
+                .context("failed to write inbox state")?;
+            now
+        },
+    };
+
+    let runner = Runner {
+        peer,
+        event_log,
+        handle: handle.clone(),
+        since,
+    };
+    Ok((handle, runner))
+}
+
+/// A notification stored in the inbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    /// Hex-encoded hash of the item content.
+    pub id: String,
+    /// Time the item was added to the inbox in milliseconds since the Unix epoch.
+    pub received_at: u64,
+    pub read: bool,
+    #[serde(flatten)]
+    pub kind: ItemKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ItemKind {
+    /// A peer created a patch on a project we are a delegate of.
+    #[serde(rename_all = "camelCase")]
+    PatchCreated {
+        urn: Urn,
+        /// Author of the patch.
+        peer: PeerId,
+        patch_id: String,
+    },
+    /// A peer commented on one of our patches.
+    #[serde(rename_all = "camelCase")]
+    PatchCommented {
+        urn: Urn,
+        patch_id: String,
+        /// Author of the comment.
+        peer: PeerId,
+        comment: String,
+        /// Time the comment was created in milliseconds since the Unix epoch as claimed by the
+        /// author.
+        timestamp: u64,
+    },
+    /// A project request was cloned from a peer.
+    #[serde(rename_all = "camelCase")]
+    RequestCloned { urn: Urn, peer: PeerId },
+    /// A peer updated branches of a project we are a delegate of.
+    #[serde(rename_all = "camelCase")]
+    PeerContribution {
+        urn: Urn,
+        peer: PeerId,
+        /// Changed branches relative to the peer, for example `refs/heads/main`.
+        changes: Vec<RefChange>,
+    },
+}
+
+impl ItemKind {
+    /// Returns the ID of an item with this content.
+    ///
+    /// The reference targets of [`ItemKind::PeerContribution`] before the change are not part of
+    /// the ID because they are unknown if we learn about the change through gossip.
+    fn id(&self) -> String {
+        let key = match self {
+            Self::PatchCreated {
+                urn,
+                peer,
+                patch_id,
+            } => format!("patchCreated/{}/{peer}/{patch_id}", urn.id),
+            Self::PatchCommented {
+                urn,
+                patch_id,
+                peer,
+                comment,
+                timestamp,
+            } => format!(
+                "patchCommented/{}/{patch_id}/{peer}/{timestamp}/{comment}",
+                urn.id
+            ),
+            Self::RequestCloned { urn, peer } => format!("requestCloned/{}/{peer}", urn.id),
+            Self::PeerContribution { urn, peer, changes } => {
+                let mut key = format!("peerContribution/{}/{peer}", urn.id);
+                for change in changes {
+                    let new = change.new.map(|new| new.to_string()).unwrap_or_default();
+                    key.push_str(&format!("/{}={new}", change.name));
+                }
+                key
+            },
+        };
+        git2::Oid::hash_object(git2::ObjectType::Blob, key.as_bytes())
+            .expect("hashing in memory never fails")
+            .to_string()
+    }
+}
+
+/// Number of items in the inbox.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Count {
+    pub total: usize,
+    pub unread: usize,
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    items: kv::Bucket<'static, String, kv::Json<Item>>,
+    /// IDs of the items that have not been read. Values are empty.
+    unread: kv::Bucket<'static, String, String>,
+}
+
+impl Handle {
+    fn open(store: &kv::Store) -> anyhow::Result<Self> {
+        let items = store
+            .bucket(Some("inbox"))
+            .context("failed to get inbox bucket")?;
+        let unread = store
+            .bucket(Some("inbox_unread"))
+            .context("failed to get inbox unread bucket")?;
+        Ok(Self { items, unread })
+    }
+
+    /// Returns all items in the inbox, the most recent item first.
+    pub fn list(&self) -> anyhow::Result<Vec<Item>> {
+        let mut items = self
+            .items
+            .iter()
+            .map(|item_result| {
+                let item = item_result.context("failed to read inbox item")?;
+                let value = item
+                    .value::<kv::Json<Item>>()
+                    .context("failed to parse inbox item")?;
+                Ok(value.0)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        items.sort_by(|a, b| b.received_at.cmp(&a.received_at).then(a.id.cmp(&b.id)));
+        Ok(items)
+    }
+
+    /// Returns the number of items. Only the keys of the buckets are read.
+    pub fn count(&self) -> anyhow::Result<Count> {
+        let total = self
+            .items
+            .iter()
+            .try_fold(0, |count, item_result| item_result.map(|_| count + 1))
+            .context("failed to read inbox items")?;
+        let unread = self
+            .unread
+            .iter()
+            .try_fold(0, |count, item_result| item_result.map(|_| count + 1))
+            .context("failed to read unread inbox items")?;
+        Ok(Count { total, unread })
+    }
+
+    /// Mark the item `id` as read. Returns `false` if there is no such item.
+    pub fn mark_read(&self, id: &str) -> anyhow::Result<bool> {
+        let mut item = match self
+            .items
+            .get(id.to_string())
+            .context("failed to read inbox item")?
+        {
+            Some(item) => item.0,
+            None => return Ok(false),
+        };
+        if !item.read {
+            item.read = true;
+            self.items
+                .set(id.to_string(), kv::Json(item))
+                .context("failed to write inbox item")?;
+            self.unread
+                .remove(id.to_string())
+                .context("failed to write inbox unread item")?;
+        }
+        Ok(true)
+    }
+
+    /// Store an unread item with the given content unless the inbox already has such an item.
+    fn insert(&self, kind: ItemKind) -> anyhow::Result<()> {
+        let id = kind.id();
+        if self
+            .items
+            .get(id.clone())
+            .context("failed to read inbox item")?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let item = Item {
+            id: id.clone(),
+            received_at: unix_timestamp_ms(),
+            read: false,
+            kind,
+        };
+        self.items
+            .set(id.clone(), kv::Json(item))
+            .context("failed to write inbox item")?;
+        self.unread
+            .set(id, String::new())
+            .context("failed to write inbox unread item")?;
+        self.prune(MAX_ITEMS)
+    }
+
+    /// Remove the oldest items that exceed `max_items`.
+    fn prune(&self, max_items: usize) -> anyhow::Result<()> {
+        for outdated in self.list()?.into_iter().skip(max_items) {
+            self.items
+                .remove(outdated.id.clone())
+                .context("failed to remove inbox item")?;
+            self.unread
+                .remove(outdated.id)
+                .context("failed to remove inbox unread item")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Runner {
+    peer: crate::peer::Peer,
+    event_log: crate::events::EventLog,
+    handle: Handle,
+    /// Time the inbox was created in milliseconds since the Unix epoch.
+    since: u64,
+}
+
+impl Runner {
+    /// Add items for `notifications` to the inbox until the stream ends.
+    pub async fn run(self, notifications: impl Stream<Item = Notification>) {
+        futures::pin_mut!(notifications);
+        while let Some(notification) = notifications.next().await {
+            let item_kinds = match self.item_kinds(notification).await {
+                Ok(item_kinds) => item_kinds,
+                Err(err) => {
+                    tracing::warn!(?err, "failed to get inbox items for notification");
+                    continue;
+                },
+            };
+            for kind in item_kinds {
+                if let Err(err) = self.handle.insert(kind) {
+                    tracing::error!(?err, "failed to add item to inbox");
+                }
+            }
+        }
+    }
+
+    /// Returns the content of the inbox items for `notification`.
+    async fn item_kinds(&self, notification: Notification) -> anyhow::Result<Vec<ItemKind>> {
+        let local_peer_id = self.peer.librad_peer().peer_id();
+        match notification {
+            Notification::RequestCloned { peer, urn } => {
+                Ok(vec![ItemKind::RequestCloned { urn, peer }])
+            },
+            Notification::RefsUpdated { urn, peer, changes } if peer != local_peer_id => {
+                let mut item_kinds = self.patch_comments(&urn, &changes).await?;
+
+                let patch_ids = changes
+                    .iter()
+                    .filter(|change| change.new.is_some())
+                    .filter_map(|change| crate::patch::id_from_tag_ref(&change.name))
+                    .collect::<Vec<_>>();
+                let branch_changes = changes
+                    .iter()
+                    .filter(|change| change.name.starts_with("refs/heads/"))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if (patch_ids.is_empty() && branch_changes.is_empty())
+                    || !self.is_delegate(&urn).await?
+                {
+                    return Ok(item_kinds);
+                }
+
+                item_kinds.extend(
+                    patch_ids
+                        .into_iter()
+                        .map(|patch_id| ItemKind::PatchCreated {
+                            urn: urn.clone(),
+                            peer,
+                            patch_id: patch_id.to_string(),
+                        }),
+                );
+                if !branch_changes.is_empty() {
+                    item_kinds.push(ItemKind::PeerContribution {
+                        urn,
+                        peer,
+                        changes: branch_changes,
+                    });
+                }
+                Ok(item_kinds)
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Returns the comments by other peers on our patches whose event logs are among `changes`.
+    async fn patch_comments(
+        &self,
+        urn: &Urn,
+        changes: &[RefChange],
+    ) -> anyhow::Result<Vec<ItemKind>> {
+        let local_peer_id = self.peer.librad_peer().peer_id();
+        let log_ref_prefix = format!("refs/{}/", crate::events::REF_PREFIX);
+        let our_patch_topic_prefix = crate::patch::event_topic(local_peer_id, "");
+
+        let mut item_kinds = vec![];
+        for change in changes {
+            let topic = match change.name.strip_prefix(&log_ref_prefix) {
+                Some(topic) => topic,
+                None => continue,
+            };
+            let patch_id = match topic.strip_prefix(&our_patch_topic_prefix) {
+                Some(patch_id) => patch_id,
+                None => continue,
+            };
+
+            let envelopes = self
+                .event_log
+                .get(urn.id, topic.to_string())
+                .await
+                .context("failed to get patch events")?
+                .events;
+            item_kinds.extend(envelopes.iter().filter_map(|envelope| {
+                comment_item(urn, patch_id, topic, local_peer_id, self.since, envelope)
+            }));
+        }
+        Ok(item_kinds)
+    }
+
+    /// Returns `true` if the local peer is a delegate of the project `urn`.
+    async fn is_delegate(&self, urn: &Urn) -> anyhow::Result<bool> {
+        let local_peer_id = self.peer.librad_peer().peer_id();
+        let project = self
+            .peer
+            .librad_peer()
+            .using_storage({
+                let urn = urn.clone();
+                move |storage| librad::git::identities::project::get(storage, &urn)
+            })
+            .await
+            .context("failed to access storage")?
+            .context("failed to get project")?;
+        Ok(project.map_or(false, |project| {
+            crate::patch::delegates(&project).contains(&local_peer_id)
+        }))
+    }
+}
+
+/// Returns the inbox item for the patch comment in `envelope` unless the comment was made by the
+/// local peer or published before `since`.
+fn comment_item(
+    urn: &Urn,
+    patch_id: &str,
+    topic: &str,
+    local_peer_id: PeerId,
+    since: u64,
+    envelope: &crate::events::Envelope,
+) -> Option<ItemKind> {
+    if envelope.peer_id == local_peer_id || envelope.timestamp.unwrap_or_default() < since {
+        return None;
+    }
+    match crate::events::parse(topic, &envelope.event) {
+        Ok(crate::patch::Event::AddComment { comment, timestamp }) => {
+            Some(ItemKind::PatchCommented {
+                urn: urn.clone(),
+                patch_id: patch_id.to_string(),
+                peer: envelope.peer_id,
+                comment,
+                timestamp,
+            })
+        },
+        _ => None,
+    }
+}
+
+fn unix_timestamp_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system time is before the Unix epoch")
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn new_peer_id() -> PeerId {
+        PeerId::from(link_crypto::SecretKey::new())
+    }
+
+    fn new_urn() -> Urn {
+        Urn::new(
+            git2::Oid::hash_object(git2::ObjectType::Blob, b"project")
+                .unwrap()
+                .into(),
+        )
+    }
+
+    fn oid(content: &str) -> radicle_git_ext::Oid {
+        git2::Oid::hash_object(git2::ObjectType::Blob, content.as_bytes())
+            .unwrap()
+            .into()
+    }
+
+    fn comment_envelope(peer_id: PeerId, comment: &str, timestamp: u64) -> crate::events::Envelope {
+        crate::events::Envelope {
+            peer_id,
+            identity: git2::Oid::zero().into(),
+            topic: "patch".to_string(),
+            event: crate::patch::Event::AddComment {
+                comment: comment.to_string(),
+                timestamp,
+            }
+            .into(),
+            timestamp: Some(timestamp),
+            commit: None,
+        }
+    }
+
+    #[test]
+    fn item_id_ignores_old_ref_targets() {
+        let urn = new_urn();
+        let peer = new_peer_id();
+        let contribution = |old: Option<&str>, new: &str| ItemKind::PeerContribution {
+            urn: urn.clone(),
+            peer,
+            changes: vec![RefChange {
+                name: "refs/heads/main".to_string(),
+                old: old.map(oid),
+                old_unknown: false,
+                new: Some(oid(new)),
+            }],
+        };
+
+        assert_eq!(
+            contribution(None, "2").id(),
+            contribution(Some("1"), "2").id()
+        );
+        assert_ne!(contribution(None, "2").id(), contribution(None, "3").id());
+
+        let patch_created = |patch_id: &str| ItemKind::PatchCreated {
+            urn: urn.clone(),
+            peer,
+            patch_id: patch_id.to_string(),
+        };
+        assert_eq!(patch_created("a").id(), patch_created("a").id());
+        assert_ne!(patch_created("a").id(), patch_created("b").id());
+        assert_ne!(
+            ItemKind::RequestCloned {
+                urn: urn.clone(),
+                peer
+            }
+            .id(),
+            ItemKind::RequestCloned {
+                urn: urn.clone(),
+                peer: new_peer_id()
+            }
+            .id()
+        );
+    }
+
+    #[test]
+    fn insert_deduplicates_items() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::open(&store).unwrap();
+        let urn = new_urn();
+        let peer = new_peer_id();
+
+        handle
+            .insert(ItemKind::RequestCloned {
+                urn: urn.clone(),
+                peer,
+            })
+            .unwrap();
+        handle
+            .insert(ItemKind::RequestCloned {
+                urn: urn.clone(),
+                peer,
+            })
+            .unwrap();
+        handle
+            .insert(ItemKind::PatchCreated {
+                urn,
+                peer,
+                patch_id: "a".to_string(),
+            })
+            .unwrap();
+
+        let items = handle.list().unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| !item.read));
+        let count = handle.count().unwrap();
+        assert_eq!((count.total, count.unread), (2, 2));
+    }
+
+    #[test]
+    fn prune_removes_oldest_items() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::open(&store).unwrap();
+        let urn = new_urn();
+        let peer = new_peer_id();
+
+        for patch_id in ["a", "b", "c"] {
+            handle
+                .insert(ItemKind::PatchCreated {
+                    urn: urn.clone(),
+                    peer,
+                    patch_id: patch_id.to_string(),
+                })
+                .unwrap();
+        }
+        let items = handle.list().unwrap();
+        handle.mark_read(&items[0].id).unwrap();
+
+        handle.prune(2).unwrap();
+        assert_eq!(
+            handle
+                .list()
+                .unwrap()
+                .into_iter()
+                .map(|item| item.id)
+                .collect::<Vec<_>>(),
+            items
+                .iter()
+                .take(2)
+                .map(|item| item.id.clone())
+                .collect::<Vec<_>>()
+        );
+        let count = handle.count().unwrap();
+        assert_eq!((count.total, count.unread), (2, 1));
+    }
+
+    #[test]
+    fn mark_read() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::open(&store).unwrap();
+        let urn = new_urn();
+        let peer = new_peer_id();
+
+        let kind = ItemKind::RequestCloned { urn, peer };
+        let id = kind.id();
+        handle.insert(kind.clone()).unwrap();
+
+        assert!(!handle.mark_read("unknown").unwrap());
+        assert!(handle.mark_read(&id).unwrap());
+        assert!(handle.mark_read(&id).unwrap());
+
+        let items = handle.list().unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(items[0].read);
+        let count = handle.count().unwrap();
+        assert_eq!((count.total, count.unread), (1, 0));
+
+        // Observing the same change again does not mark the item as unread.
+        handle.insert(kind).unwrap();
+        let count = handle.count().unwrap();
+        assert_eq!((count.total, count.unread), (1, 0));
+    }
+
+    #[test]
+    fn comment_item_since_cutoff() {
+        let urn = new_urn();
+        let local_peer_id = new_peer_id();
+        let other = new_peer_id();
+        let topic = crate::patch::event_topic(local_peer_id, "a");
+        let since = 1000;
+
+        let item = comment_item(
+            &urn,
+            "a",
+            &topic,
+            local_peer_id,
+            since,
+            &comment_envelope(other, "new", 1000),
+        );
+        assert!(matches!(
+            item,
+            Some(ItemKind::PatchCommented { comment, timestamp: 1000, .. }) if comment == "new"
+        ));
+
+        let item = comment_item(
+            &urn,
+            "a",
+            &topic,
+            local_peer_id,
+            since,
+            &comment_envelope(other, "old", 999),
+        );
+        assert!(item.is_none());
+
+        let item = comment_item(
+            &urn,
+            "a",
+            &topic,
+            local_peer_id,
+            since,
+            &comment_envelope(local_peer_id, "own", 2000),
+        );
+        assert!(item.is_none());
+    }
+}
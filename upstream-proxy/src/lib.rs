@@ -20,6 +20,7 @@ mod cli;
 mod config;
 mod context;
 mod daemon;
+mod diff;
 mod error;
 mod events;
 mod git_fetch;
@@ -35,6 +36,7 @@ pub mod dev_cli;
 mod http;
 mod identifier;
 mod identity;
+mod inbox;
 mod keystore;
 mod notification;
 mod patch;
@@ -42,7 +44,11 @@ mod process;
 mod project;
 mod service;
 mod session;
+mod source;
+#[cfg(test)]
+mod test_util;
 mod watch_monorepo;
+mod webhooks;
 
 pub use cli::Args;
 pub use process::run;
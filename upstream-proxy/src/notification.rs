@@ -6,10 +6,18 @@
 
 //! Machinery to signal significant events to clients.
 
+use futures::prelude::*;
 use serde::Serialize;
 use std::{collections::HashMap, time::SystemTime};
 
-use crate::daemon::request::{RequestState, SomeRequest, Status as PeerRequestStatus};
+use crate::{
+    daemon::request::{RequestState, SomeRequest, Status as PeerRequestStatus},
+    watch_monorepo::RefChange,
+};
+use librad::net::protocol::{
+    broadcast::PutResult,
+    gossip::{Payload, Rev},
+};
 use link_crypto::PeerId;
 use link_identities::git::Urn;
 use radicle_git_ext::Oid;
@@ -22,6 +30,16 @@ pub enum Notification {
         /// URN of the project that was updated
         urn: Urn,
     },
+    /// References of a peer in a project changed.
+    #[serde(rename_all = "camelCase")]
+    RefsUpdated {
+        /// URN of the project without a path.
+        urn: Urn,
+        /// Peer that owns the changed references.
+        peer: PeerId,
+        /// Changed references relative to the peer, for example `refs/heads/main`.
+        changes: Vec<RefChange>,
+    },
     /// A request for a project was created and is pending submission to the network
     #[serde(rename_all = "camelCase")]
     RequestCreated {
@@ -96,6 +114,120 @@ pub fn from_peer_event(event: crate::daemon::PeerEvent) -> Option<Notification>
     }
 }
 
+/// Returns the stream of notifications about the local peer that are derived from `peer_events`,
+/// fetches from Git seeds and changes to the monorepo.
+pub fn local_peer_notifications(
+    peer_events: impl Stream<Item = crate::daemon::PeerEvent> + Send + 'static,
+    git_fetch: &crate::git_fetch::Handle,
+    watch_monorepo: &crate::watch_monorepo::Handle,
+    local_peer_id: PeerId,
+) -> impl Stream<Item = Notification> + Send + 'static {
+    let peer_notifications = peer_events.flat_map(|event| {
+        let refs_notification = refs_updated_from_peer_event(&event);
+        stream::iter(from_peer_event(event).into_iter().chain(refs_notification))
+    });
+
+    let git_fetch_notifications = git_fetch.updates().flat_map(move |update| {
+        let urn = Urn::new(update.identity);
+        let refs_notifications = refs_updated(&urn, local_peer_id, update.changes);
+        stream::iter(
+            std::iter::once(Notification::ProjectUpdated { urn }).chain(refs_notifications),
+        )
+    });
+
+    let monorepo_local_update_notifications = watch_monorepo.updates().flat_map(move |update| {
+        let refs_notifications = refs_updated(&update.urn, local_peer_id, update.changes);
+        stream::iter(
+            std::iter::once(Notification::ProjectUpdated { urn: update.urn })
+                .chain(refs_notifications),
+        )
+    });
+
+    stream::select_all(vec![
+        peer_notifications.boxed(),
+        git_fetch_notifications.boxed(),
+        monorepo_local_update_notifications.boxed(),
+    ])
+}
+
+/// Returns a [`Notification::RefsUpdated`] for a reference that was fetched because of a gossip
+/// message.
+pub fn refs_updated_from_peer_event(event: &crate::daemon::PeerEvent) -> Option<Notification> {
+    match event {
+        crate::daemon::PeerEvent::GossipFetched {
+            provider,
+            gossip,
+            result: PutResult::Applied(_),
+        } => refs_updated_from_gossip(provider.peer_id, gossip),
+        _ => None,
+    }
+}
+
+/// Returns a [`Notification::RefsUpdated`] for the reference in `gossip` that was fetched from
+/// `provider`. Returns `None` if `gossip` does not name a reference.
+///
+/// Gossip messages only carry the new target of the reference. The change is marked with
+/// [`RefChange::old_unknown`].
+fn refs_updated_from_gossip(provider: PeerId, gossip: &Payload) -> Option<Notification> {
+    let name = gossip.urn.path.as_ref()?.to_string();
+    let new = gossip.rev.as_ref().map(|rev| match rev {
+        Rev::Git(oid) => Oid::from(*oid),
+    });
+    Some(Notification::RefsUpdated {
+        urn: Urn::new(gossip.urn.id),
+        peer: gossip.origin.unwrap_or(provider),
+        changes: vec![RefChange {
+            name,
+            old: None,
+            old_unknown: true,
+            new,
+        }],
+    })
+}
+
+/// Returns a [`Notification::RefsUpdated`] for every peer that owns some of the changed
+/// references in the namespace of `urn`.
+///
+/// References under `refs/remotes/<peer>/` belong to that peer and are reported relative to it.
+/// All other references belong to `local_peer_id`.
+pub fn refs_updated(
+    urn: &Urn,
+    local_peer_id: PeerId,
+    changes: Vec<RefChange>,
+) -> Vec<Notification> {
+    let mut changes_by_peer: Vec<(PeerId, Vec<RefChange>)> = vec![];
+    for mut change in changes {
+        let mut peer = local_peer_id;
+        if let Some((remote_peer, name)) = change
+            .name
+            .strip_prefix("refs/remotes/")
+            .and_then(|name| name.split_once('/'))
+        {
+            if let Ok(remote_peer) = remote_peer.parse::<PeerId>() {
+                peer = remote_peer;
+                change.name = format!("refs/{name}");
+            }
+        }
+
+        match changes_by_peer
+            .iter_mut()
+            .find(|(other_peer, _)| *other_peer == peer)
+        {
+            Some((_, peer_changes)) => peer_changes.push(change),
+            None => changes_by_peer.push((peer, vec![change])),
+        }
+    }
+
+    changes_by_peer
+        .into_iter()
+        .map(|(peer, changes)| Notification::RefsUpdated {
+            urn: Urn::new(urn.id),
+            peer,
+            changes,
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct SerializableWaitingRoomState(HashMap<String, SerializedRequestState>);
 
@@ -122,3 +254,144 @@ impl From<HashMap<Oid, SomeRequest<SystemTime>>> for SerializableWaitingRoomStat
         Self(inner)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn oid(content: &str) -> Oid {
+        git2::Oid::hash_object(git2::ObjectType::Blob, content.as_bytes())
+            .unwrap()
+            .into()
+    }
+
+    fn peer_id() -> PeerId {
+        PeerId::from(link_crypto::SecretKey::new())
+    }
+
+    fn change(name: &str, new: &str) -> RefChange {
+        RefChange {
+            name: name.to_string(),
+            old: None,
+            old_unknown: false,
+            new: Some(oid(new)),
+        }
+    }
+
+    fn refs_updated_changes(notification: &Notification) -> (PeerId, Vec<RefChange>) {
+        match notification {
+            Notification::RefsUpdated { peer, changes, .. } => (*peer, changes.clone()),
+            other => panic!("unexpected notification {other:?}"),
+        }
+    }
+
+    #[test]
+    fn refs_updated_groups_changes_by_peer() {
+        let urn = Urn::new(oid("project"));
+        let local_peer_id = peer_id();
+        let remote_a = peer_id();
+        let remote_b = peer_id();
+
+        let notifications = refs_updated(
+            &urn,
+            local_peer_id,
+            vec![
+                change("refs/heads/main", "1"),
+                change(&format!("refs/remotes/{remote_a}/heads/main"), "2"),
+                change(&format!("refs/remotes/{remote_b}/tags/v1"), "3"),
+                change(&format!("refs/remotes/{remote_a}/heads/dev"), "4"),
+                change("refs/remotes/not-a-peer/heads/main", "5"),
+            ],
+        );
+
+        assert_eq!(
+            notifications
+                .iter()
+                .map(refs_updated_changes)
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    local_peer_id,
+                    vec![
+                        change("refs/heads/main", "1"),
+                        change("refs/remotes/not-a-peer/heads/main", "5"),
+                    ]
+                ),
+                (
+                    remote_a,
+                    vec![
+                        change("refs/heads/main", "2"),
+                        change("refs/heads/dev", "4"),
+                    ]
+                ),
+                (remote_b, vec![change("refs/tags/v1", "3")]),
+            ]
+        );
+        for notification in notifications {
+            match notification {
+                Notification::RefsUpdated { urn: updated, .. } => assert_eq!(updated, urn),
+                other => panic!("unexpected notification {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn refs_updated_strips_path_from_urn() {
+        let urn = Urn::new(oid("project")).with_path(librad::reflike!("refs/heads/main"));
+        let notifications = refs_updated(&urn, peer_id(), vec![change("refs/heads/main", "1")]);
+        match &notifications[..] {
+            [Notification::RefsUpdated { urn: updated, .. }] => {
+                assert_eq!(*updated, Urn::new(urn.id))
+            },
+            other => panic!("unexpected notifications {other:?}"),
+        }
+    }
+
+    #[test]
+    fn refs_updated_from_gossip_old_unknown() {
+        let provider = peer_id();
+        let origin = peer_id();
+        let urn = Urn::new(oid("project"));
+        let gossip = Payload {
+            urn: urn.clone().with_path(librad::reflike!("refs/heads/main")),
+            rev: Some(Rev::Git(oid("1").into())),
+            origin: Some(origin),
+        };
+
+        let notification = refs_updated_from_gossip(provider, &gossip).unwrap();
+        match notification {
+            Notification::RefsUpdated {
+                urn: updated,
+                peer,
+                changes,
+            } => {
+                assert_eq!(updated, urn);
+                assert_eq!(peer, origin);
+                assert_eq!(
+                    changes,
+                    vec![RefChange {
+                        name: "refs/heads/main".to_string(),
+                        old: None,
+                        old_unknown: true,
+                        new: Some(oid("1")),
+                    }]
+                );
+            },
+            other => panic!("unexpected notification {other:?}"),
+        }
+
+        let gossip = Payload {
+            origin: None,
+            ..gossip
+        };
+        let (peer, _) = refs_updated_changes(&refs_updated_from_gossip(provider, &gossip).unwrap());
+        assert_eq!(peer, provider);
+
+        let gossip = Payload {
+            urn: urn.clone(),
+            ..gossip
+        };
+        assert!(refs_updated_from_gossip(provider, &gossip).is_none());
+    }
+}
@@ -4,12 +4,12 @@
 // with Radicle Linking Exception. For full terms see the included
 // LICENSE file.
 
-//! [`list`] all the [`Patch`]es for project.
+//! [`list`] all the [`Patch`]es for project and [`merge`] them.
 
 use anyhow::Context as _;
 use either::Either;
 use radicle_git_ext::Oid;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use link_identities::git::Urn;
 
@@ -20,7 +20,9 @@ const TAG_PREFIX: &str = "radicle-patch/";
 /// A patch is a change set that a user wants the delegate to merge into a projects default
 /// branch.
 ///
-/// A patch is represented by an annotated tag, prefixed with `radicle-patch/`.
+/// A patch is represented by an annotated tag, prefixed with `radicle-patch/`. The status and the
+/// discussion of a patch are obtained from the events published to the patch’s event topic. See
+/// [`event_topic`].
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Patch {
@@ -35,6 +37,83 @@ pub struct Patch {
     /// The merge base of [`Patch::commit`] and the head commit of the first delegate's default
     /// branch.
     pub merge_base: Option<Oid>,
+    /// Current status of the patch.
+    pub status: Status,
+    /// Comments on the patch in chronological order.
+    pub comments: Vec<Comment>,
+    /// Time of the most recent event for the patch in milliseconds since the Unix epoch. `None` if
+    /// no events were published for the patch.
+    pub last_activity: Option<u64>,
+}
+
+/// Status of a [`Patch`] and the peer that set it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub current: State,
+    /// Peer that published the `setStatus` event the current state is derived from. `None` if
+    /// the state was not set by an event.
+    pub by_peer_id: Option<librad::PeerId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum State {
+    Open,
+    Closed,
+    Merged,
+}
+
+/// A comment on a patch published with an `addComment` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    /// Author of the comment
+    pub peer_id: librad::PeerId,
+    pub comment: String,
+    /// Time the comment was created in milliseconds since the Unix epoch as claimed by the
+    /// author.
+    pub timestamp: u64,
+}
+
+/// A version of a patch, that is the state of the patch tag at some point in time. Revisions are
+/// recorded by the patch author with `addRevision` events. See [`record_revision`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    /// Commit the patch tag pointed to.
+    pub commit: Oid,
+    /// Message of the patch tag.
+    pub message: Option<String>,
+    /// Time the revision was created in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Events that can be published to the event topic of a patch.
+///
+/// The serialized representation is compatible with [`crate::events::Event`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "data")]
+pub enum Event {
+    SetStatus { status: State },
+    AddComment { comment: String, timestamp: u64 },
+    AddRevision(Revision),
+}
+
+impl crate::events::Schema for Event {
+    const TYPES: &'static [&'static str] = &["setStatus", "addComment", "addRevision"];
+}
+
+impl From<Event> for crate::events::Event {
+    fn from(event: Event) -> Self {
+        let value = serde_json::to_value(event).expect("patch events are always serializable");
+        serde_json::from_value(value).expect("patch events are valid events")
+    }
+}
+
+/// Returns the event log topic for the patch `id` authored by `peer_id`.
+pub fn event_topic(peer_id: librad::PeerId, id: &str) -> String {
+    format!("patch/{peer_id}/{id}")
 }
 
 /// List all patches for the given project.
@@ -42,8 +121,562 @@ pub struct Patch {
 /// # Errors
 /// * Cannot access the monorepo
 /// * Cannot find references within the monorepo
-pub async fn list(peer: &crate::peer::Peer, project_urn: Urn) -> anyhow::Result<Vec<Patch>> {
-    let default_branch_head_commit_id = {
+/// * Cannot read the event logs of the patches
+pub async fn list(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+    project_urn: Urn,
+) -> anyhow::Result<Vec<Patch>> {
+    let project_info = ProjectInfo::load(peer, project_urn.clone()).await?;
+
+    let mut patches = Vec::new();
+
+    for project_peer in
+        crate::daemon::state::list_project_peers(peer.librad_peer(), project_urn.clone()).await?
+    {
+        for tag in list_tags(peer, &project_urn, &project_peer, &project_info, "*").await? {
+            patches.push(
+                tag.into_patch(event_log, &project_urn, &project_info)
+                    .await?,
+            );
+        }
+    }
+
+    Ok(patches)
+}
+
+/// Get the patch with the given `id` authored by `peer_id`. Returns `None` if the patch does not
+/// exist or if `peer_id` is not a peer of the project.
+///
+/// # Errors
+/// * Cannot access the monorepo
+/// * Cannot find references within the monorepo
+/// * Cannot read the event log of the patch
+pub async fn get(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+    project_urn: Urn,
+    peer_id: librad::PeerId,
+    id: &str,
+) -> anyhow::Result<Option<Patch>> {
+    let project_info = ProjectInfo::load(peer, project_urn.clone()).await?;
+    match find_tag(peer, &project_urn, &project_info, peer_id, id).await? {
+        Some(tag) => Ok(Some(
+            tag.into_patch(event_log, &project_urn, &project_info)
+                .await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Changes introduced by a patch and whether it can be merged.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchDiff {
+    /// Commit the diff is computed from. This is [`Patch::merge_base`].
+    pub base: Option<Oid>,
+    /// Commit the diff is computed to. This is [`Patch::commit`].
+    pub head: Oid,
+    /// `true` if the patch can be merged into the head of the first delegate’s default branch
+    /// without conflicts.
+    pub mergeable: bool,
+    /// Paths of the files that conflict with the default branch.
+    pub conflicts: Vec<String>,
+    pub diff: crate::diff::Diff,
+}
+
+/// Get the changes of the patch with the given `id` authored by `peer_id` relative to its merge
+/// base. Returns `None` if the patch does not exist.
+///
+/// Whether the patch is mergeable is determined by merging it in memory with the head of the
+/// first delegate’s default branch.
+///
+/// # Errors
+/// * Cannot access the monorepo
+/// * Cannot find references or objects within the monorepo
+pub async fn diff(
+    peer: &crate::peer::Peer,
+    project_urn: Urn,
+    peer_id: librad::PeerId,
+    id: &str,
+) -> anyhow::Result<Option<PatchDiff>> {
+    let project_info = ProjectInfo::load(peer, project_urn.clone()).await?;
+    let tag = match find_tag(peer, &project_urn, &project_info, peer_id, id).await? {
+        Some(tag) => tag,
+        None => return Ok(None),
+    };
+
+    let default_branch_head = project_info.default_branch_head;
+    peer.monorepo_unblock(move |repo| {
+        let diff = crate::diff::Diff::between_commits(
+            &repo,
+            tag.merge_base.map(git2::Oid::from),
+            *tag.commit,
+        )?;
+
+        let conflicts = merge_conflicts(&repo, default_branch_head, *tag.commit)?;
+
+        Ok(Some(PatchDiff {
+            base: tag.merge_base,
+            head: tag.commit,
+            mergeable: conflicts.is_empty(),
+            conflicts,
+            diff,
+        }))
+    })
+    .await
+}
+
+/// A [`Revision`] of a patch and its changes relative to the previous revision.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionDiff {
+    #[serde(flatten)]
+    pub revision: Revision,
+    /// Comparison with the previous revision. `None` for the first revision or if the commits of
+    /// either revision are not available.
+    pub range_diff: Option<crate::diff::RangeDiff>,
+}
+
+/// Publish an `addRevision` event for the local peer’s patch `id` if the patch tag points to a
+/// commit that is not the latest recorded revision. Returns `true` if a revision was recorded.
+///
+/// This should be called whenever the patch tag is updated so that previous revisions of the
+/// patch are still available after the tag has been force-pushed.
+///
+/// # Errors
+/// * Cannot access the monorepo
+/// * Cannot read or publish the event log of the patch
+pub async fn record_revision(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+    project_urn: Urn,
+    id: &str,
+) -> anyhow::Result<bool> {
+    let local_peer_id = peer.librad_peer().peer_id();
+    let ref_name = format!(
+        "refs/namespaces/{}/refs/tags/{TAG_PREFIX}{id}",
+        project_urn.encode_id()
+    );
+    let maybe_revision = peer
+        .monorepo_unblock(move |repo| {
+            let reference = match repo.find_reference(&ref_name) {
+                Ok(reference) => reference,
+                Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+                Err(err) => return Err(err).context("failed to find patch tag"),
+            };
+            let tag = reference
+                .peel_to_tag()
+                .context("failed to peel reference to tag")?;
+            Ok(Some(revision_from_tag(&tag)))
+        })
+        .await?;
+    let revision = match maybe_revision {
+        Some(revision) => revision,
+        None => return Ok(false),
+    };
+
+    let topic = event_topic(local_peer_id, id);
+    let envelopes = event_log
+        .get(project_urn.id, topic.clone())
+        .await
+        .context("failed to get patch events")?
+        .events;
+    let latest = fold_revisions(local_peer_id, &envelopes).pop();
+    if latest.map(|latest| latest.commit) == Some(revision.commit) {
+        return Ok(false);
+    }
+
+    event_log
+        .publish(project_urn.id, &topic, Event::AddRevision(revision).into())
+        .await
+        .context("failed to publish patch revision")?;
+    Ok(true)
+}
+
+/// Call [`record_revision`] for every patch of the local peer in every project. This records the
+/// revisions of patch tags that were updated while Upstream was not running, for example with
+/// `upstream patch update`.
+///
+/// Failures to record the revision of a single patch are logged.
+///
+/// # Errors
+/// * Cannot access the monorepo
+pub async fn record_all_revisions(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+) -> anyhow::Result<()> {
+    let patches = peer
+        .monorepo_unblock(|repo| {
+            let mut patches = vec![];
+            for reference_result in repo
+                .references_glob(&format!("refs/namespaces/*/refs/tags/{TAG_PREFIX}*"))
+                .context("failed to list patch tags")?
+            {
+                let reference = reference_result.context("failed to get patch tag")?;
+                let (namespace, path) = match reference
+                    .name()
+                    .and_then(|name| name.strip_prefix("refs/namespaces/"))
+                    .and_then(|name| name.split_once('/'))
+                {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if let Some(id) = id_from_tag_ref(path) {
+                    patches.push((namespace.to_string(), id.to_string()));
+                }
+            }
+            Ok(patches)
+        })
+        .await?;
+
+    for (namespace, id) in patches {
+        let project_urn = match format!("rad:git:{namespace}").parse::<Urn>() {
+            Ok(project_urn) => project_urn,
+            Err(err) => {
+                tracing::warn!(?err, %namespace, "invalid namespace in monorepo");
+                continue;
+            },
+        };
+        if let Err(err) = record_revision(peer, event_log, project_urn, &id).await {
+            tracing::warn!(?err, %id, "failed to record patch revision");
+        }
+    }
+    Ok(())
+}
+
+/// Returns the patch ID if `path` is the reference of a patch tag relative to a project namespace,
+/// for example `refs/tags/radicle-patch/<id>`.
+pub fn id_from_tag_ref(path: &str) -> Option<&str> {
+    path.strip_prefix("refs/tags/")?.strip_prefix(TAG_PREFIX)
+}
+
+/// Get the revisions of the patch `id` authored by `peer_id` in chronological order. Returns
+/// `None` if the patch does not exist.
+///
+/// If the current patch tag has not been recorded as a revision, it is included as the latest
+/// revision.
+///
+/// # Errors
+/// * Cannot access the monorepo
+/// * Cannot read the event log of the patch
+pub async fn revisions(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+    project_urn: Urn,
+    peer_id: librad::PeerId,
+    id: &str,
+) -> anyhow::Result<Option<Vec<RevisionDiff>>> {
+    let project_info = ProjectInfo::load(peer, project_urn.clone()).await?;
+    let tag = match find_tag(peer, &project_urn, &project_info, peer_id, id).await? {
+        Some(tag) => tag,
+        None => return Ok(None),
+    };
+
+    let envelopes = event_log
+        .get(project_urn.id, event_topic(peer_id, id))
+        .await
+        .context("failed to get patch events")?
+        .events;
+    let mut revisions = fold_revisions(peer_id, &envelopes);
+    if revisions.last().map(|latest| latest.commit) != Some(tag.commit) {
+        revisions.push(tag.revision);
+    }
+
+    let default_branch_head = project_info.default_branch_head;
+    let revision_diffs = peer
+        .monorepo_unblock(move |repo| {
+            let mut revision_diffs = Vec::<RevisionDiff>::new();
+            for revision in revisions {
+                let range_diff = match revision_diffs.last() {
+                    Some(previous) => {
+                        let old = *previous.revision.commit;
+                        let new = *revision.commit;
+                        if repo.find_commit(old).is_ok() && repo.find_commit(new).is_ok() {
+                            Some(crate::diff::RangeDiff::between(
+                                &repo,
+                                default_branch_head,
+                                old,
+                                new,
+                            )?)
+                        } else {
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                revision_diffs.push(RevisionDiff {
+                    revision,
+                    range_diff,
+                });
+            }
+            Ok(revision_diffs)
+        })
+        .await?;
+
+    Ok(Some(revision_diffs))
+}
+
+/// Result of a successful [`merge`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeOutcome {
+    /// How the patch was merged into the default branch.
+    pub strategy: MergeStrategy,
+    /// Head commit of the local default branch after the merge.
+    pub head: Oid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeStrategy {
+    /// The patch commit was already contained in the default branch.
+    UpToDate,
+    /// The default branch was fast-forwarded to the patch commit.
+    FastForward,
+    /// A merge commit was created on top of the default branch.
+    MergeCommit,
+}
+
+/// Errors returned by [`merge`].
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("patch not found")]
+    NotFound,
+    #[error("only delegates of the project can merge patches")]
+    NotDelegate,
+    #[error("patch conflicts with the default branch")]
+    Conflicts {
+        /// Paths of the files with conflicts.
+        paths: Vec<String>,
+    },
+    #[error("default branch was updated concurrently")]
+    DefaultBranchChanged,
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+/// Merge the patch `id` authored by `peer_id` into the default branch of the local peer and
+/// publish a `setStatus` event that marks the patch as merged.
+///
+/// The merge is performed in the monorepo without a working copy. If the default branch is an
+/// ancestor of the patch commit, the branch is fast-forwarded. Otherwise, a merge commit is
+/// created, signed with the Git user configured for the monorepo.
+///
+/// # Errors
+/// * The local peer is not a delegate of the project
+/// * The patch does not exist
+/// * The patch cannot be merged without conflicts
+/// * Cannot access the monorepo or publish the event
+pub async fn merge(
+    peer: &crate::peer::Peer,
+    event_log: &crate::events::EventLog,
+    project_urn: Urn,
+    peer_id: librad::PeerId,
+    id: &str,
+) -> Result<MergeOutcome, MergeError> {
+    let local_peer_id = peer.librad_peer().peer_id();
+    let project_info = ProjectInfo::load(peer, project_urn.clone()).await?;
+    if !project_info.delegates.contains(&local_peer_id) {
+        return Err(MergeError::NotDelegate);
+    }
+
+    let patch = get(peer, event_log, project_urn.clone(), peer_id, id)
+        .await?
+        .ok_or(MergeError::NotFound)?;
+
+    let signature = peer
+        .librad_peer()
+        .using_storage(|store| -> anyhow::Result<_> {
+            let config = store.config()?;
+            Ok(crate::daemon::project::create::Signature {
+                name: config.user_name()?,
+                email: config.user_email()?,
+            })
+        })
+        .await
+        .context("failed to access storage")?
+        .context("failed to get Git user from storage config")?;
+
+    let ref_name = format!(
+        "refs/namespaces/{}/refs/heads/{}",
+        project_urn.encode_id(),
+        project_info.default_branch
+    );
+    let message = format!("Merge patch '{}' from {}", patch.id, peer_id);
+    let patch_commit = git2::Oid::from(patch.commit);
+    let outcome = peer
+        .monorepo_unblock(move |repo| {
+            Ok(merge_into(
+                &repo,
+                &ref_name,
+                patch_commit,
+                &message,
+                signature,
+            ))
+        })
+        .await??;
+
+    if outcome.strategy != MergeStrategy::UpToDate {
+        peer.librad_peer()
+            .using_storage({
+                let project_urn = project_urn.clone();
+                move |storage| librad::git::refs::Refs::update(storage, &project_urn)
+            })
+            .await
+            .context("failed to access storage")?
+            .context("failed to update project refs")?;
+    }
+
+    let event = Event::SetStatus {
+        status: State::Merged,
+    };
+    event_log
+        .publish(
+            project_urn.id,
+            &event_topic(peer_id, &patch.id),
+            event.into(),
+        )
+        .await
+        .context("failed to publish patch status")?;
+
+    Ok(outcome)
+}
+
+/// Merge `commit` into the branch `ref_name` of `repo`.
+///
+/// Returns [`MergeError::DefaultBranchChanged`] if `ref_name` was updated while the merge was in
+/// progress.
+fn merge_into(
+    repo: &git2::Repository,
+    ref_name: &str,
+    commit: git2::Oid,
+    message: &str,
+    signature: crate::daemon::project::create::Signature,
+) -> Result<MergeOutcome, MergeError> {
+    let head = repo
+        .refname_to_id(ref_name)
+        .context("failed to resolve default branch")?;
+
+    if head == commit
+        || repo
+            .graph_descendant_of(head, commit)
+            .context("failed to compare commits")?
+    {
+        return Ok(MergeOutcome {
+            strategy: MergeStrategy::UpToDate,
+            head: Oid::from(head),
+        });
+    }
+
+    let (strategy, new_head) = if repo
+        .graph_descendant_of(commit, head)
+        .context("failed to compare commits")?
+    {
+        (MergeStrategy::FastForward, commit)
+    } else {
+        let head_commit = repo.find_commit(head).context("failed to find commit")?;
+        let patch_commit = repo.find_commit(commit).context("failed to find commit")?;
+        let mut index = repo
+            .merge_commits(&head_commit, &patch_commit, None)
+            .context("failed to merge commits")?;
+        let paths = conflicting_paths(&index)?;
+        if !paths.is_empty() {
+            return Err(MergeError::Conflicts { paths });
+        }
+
+        let tree_id = index
+            .write_tree_to(repo)
+            .context("failed to write merge tree")?;
+        let tree = repo.find_tree(tree_id).context("failed to find tree")?;
+        let signature =
+            git2::Signature::try_from(signature).context("failed to create signature")?;
+        let merge_commit = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &[&head_commit, &patch_commit],
+            )
+            .context("failed to create merge commit")?;
+        (MergeStrategy::MergeCommit, merge_commit)
+    };
+
+    match repo.reference_matching(ref_name, new_head, true, head, message) {
+        Ok(_) => Ok(MergeOutcome {
+            strategy,
+            head: Oid::from(new_head),
+        }),
+        Err(err) if err.code() == git2::ErrorCode::Modified => {
+            Err(MergeError::DefaultBranchChanged)
+        },
+        Err(err) => Err(anyhow::Error::new(err)
+            .context("failed to update default branch")
+            .into()),
+    }
+}
+
+/// Merge `commit` into `head` in memory and return the paths of the conflicting files.
+fn merge_conflicts(
+    repo: &git2::Repository,
+    head: git2::Oid,
+    commit: git2::Oid,
+) -> anyhow::Result<Vec<String>> {
+    let head_commit = repo
+        .find_commit(head)
+        .context("failed to find default branch head")?;
+    let patch_commit = repo
+        .find_commit(commit)
+        .context("failed to find patch commit")?;
+    let index = repo
+        .merge_commits(&head_commit, &patch_commit, None)
+        .context("failed to merge commits")?;
+    conflicting_paths(&index)
+}
+
+/// Returns the paths of all conflicting files in the result of a merge.
+fn conflicting_paths(index: &git2::Index) -> anyhow::Result<Vec<String>> {
+    let mut paths = vec![];
+    if !index.has_conflicts() {
+        return Ok(paths);
+    }
+    for conflict in index.conflicts().context("failed to get merge conflicts")? {
+        let conflict = conflict.context("failed to get merge conflict")?;
+        let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+        if let Some(entry) = entry {
+            paths.push(String::from_utf8_lossy(&entry.path).to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Returns the peer IDs of all delegates of `project`, including the keys of indirect delegations.
+pub fn delegates(project: &link_identities::Project) -> Vec<librad::PeerId> {
+    project
+        .delegations()
+        .iter()
+        .flat_map(|either| match either {
+            Either::Left(pk) => Either::Left(std::iter::once(pk)),
+            Either::Right(indirect) => Either::Right(indirect.delegations().iter()),
+        })
+        .map(|pk| librad::PeerId::from(*pk))
+        .collect()
+}
+
+/// Information about a project required to construct [`Patch`]es.
+struct ProjectInfo {
+    /// Name of the project’s default branch.
+    default_branch: radicle_git_ext::RefLike,
+    /// Head commit of the first delegate's default branch.
+    default_branch_head: git2::Oid,
+    /// Peer IDs of all the project delegates.
+    delegates: Vec<librad::PeerId>,
+}
+
+impl ProjectInfo {
+    async fn load(peer: &crate::peer::Peer, project_urn: Urn) -> anyhow::Result<Self> {
         let project = peer
             .librad_peer()
             .using_storage({
@@ -54,16 +687,10 @@ pub async fn list(peer: &crate::peer::Peer, project_urn: Urn) -> anyhow::Result<
             .context("failed to access storage")?
             .context("failed to get project")?
             .ok_or_else(|| anyhow::anyhow!("project {project_urn} not found"))?;
-        let first_delegate = project
-            .delegations()
-            .iter()
-            .flat_map(|either| match either {
-                Either::Left(pk) => Either::Left(std::iter::once(pk)),
-                Either::Right(indirect) => Either::Right(indirect.delegations().iter()),
-            })
-            .next()
+        let delegates = delegates(&project);
+        let first_delegate = *delegates
+            .first()
             .context("project does not have any delegations")?;
-        let first_delegate = librad::PeerId::from(*first_delegate);
         let remote = if first_delegate == peer.librad_peer().peer_id() {
             None
         } else {
@@ -82,78 +709,481 @@ pub async fn list(peer: &crate::peer::Peer, project_urn: Urn) -> anyhow::Result<
         let reference = librad::git::types::Reference::head(
             librad::git::types::Namespace::from(project_urn.clone()),
             remote,
-            default_branch_ref_name,
+            default_branch_ref_name.clone(),
         );
-        peer.monorepo_unblock(move |repo| Ok(reference.oid(&repo)?))
+        let default_branch_head = peer
+            .monorepo_unblock(move |repo| Ok(reference.oid(&repo)?))
+            .await
+            .context("failed to resolve git reference")?;
+
+        Ok(Self {
+            default_branch: default_branch_ref_name,
+            default_branch_head,
+            delegates,
+        })
+    }
+}
+
+/// Patch data obtained from a patch tag.
+struct PatchTag {
+    id: String,
+    /// Revision of the patch represented by the tag.
+    revision: Revision,
+    peer:
+        crate::daemon::project::Peer<crate::daemon::project::peer::Status<link_identities::Person>>,
+    message: Option<String>,
+    commit: Oid,
+    merge_base: Option<Oid>,
+}
+
+impl PatchTag {
+    /// Read the patch events from `event_log` and construct the [`Patch`].
+    async fn into_patch(
+        self,
+        event_log: &crate::events::EventLog,
+        project_urn: &Urn,
+        project_info: &ProjectInfo,
+    ) -> anyhow::Result<Patch> {
+        let author = self.peer.peer_id();
+        let envelopes = event_log
+            .get(project_urn.id, event_topic(author, &self.id))
             .await
-            .context("failed to resolve git reference")?
+            .context("failed to get patch events")?
+            .events;
+        let merged = self.merge_base == Some(self.commit);
+        let folded = fold_events(author, &project_info.delegates, merged, &envelopes);
+
+        Ok(Patch {
+            id: self.id,
+            peer: self.peer.into(),
+            message: self.message,
+            commit: self.commit,
+            merge_base: self.merge_base,
+            status: folded.status,
+            comments: folded.comments,
+            last_activity: folded.last_activity,
+        })
+    }
+}
+
+/// Find the patch tag `id` of the project peer `peer_id`. Returns `None` if the tag does not exist
+/// or if `peer_id` is not a peer of the project.
+async fn find_tag(
+    peer: &crate::peer::Peer,
+    project_urn: &Urn,
+    project_info: &ProjectInfo,
+    peer_id: librad::PeerId,
+    id: &str,
+) -> anyhow::Result<Option<PatchTag>> {
+    let maybe_project_peer =
+        crate::daemon::state::list_project_peers(peer.librad_peer(), project_urn.clone())
+            .await?
+            .into_iter()
+            .find(|project_peer| project_peer.peer_id() == peer_id);
+    let project_peer = match maybe_project_peer {
+        Some(project_peer) => project_peer,
+        None => return Ok(None),
     };
 
-    let mut patches = Vec::new();
+    Ok(
+        list_tags(peer, project_urn, &project_peer, project_info, id)
+            .await?
+            .into_iter()
+            .find(|tag| tag.id == id),
+    )
+}
 
-    for project_peer in
-        crate::daemon::state::list_project_peers(peer.librad_peer(), project_urn.clone()).await?
-    {
-        let namespace = project_urn.encode_id();
-        let ref_glob = match &project_peer {
-            crate::daemon::project::Peer::Local { .. } => {
-                format!("refs/namespaces/{namespace}/refs/tags/{TAG_PREFIX}*")
-            },
-            crate::daemon::project::Peer::Remote { peer_id, .. } => {
-                format!("refs/namespaces/{namespace}/refs/remotes/{peer_id}/tags/{TAG_PREFIX}*")
+/// List the patch tags of `project_peer` whose ID matches the glob `id_pattern`.
+async fn list_tags(
+    peer: &crate::peer::Peer,
+    project_urn: &Urn,
+    project_peer: &crate::daemon::project::Peer<
+        crate::daemon::project::peer::Status<link_identities::Person>,
+    >,
+    project_info: &ProjectInfo,
+    id_pattern: &str,
+) -> anyhow::Result<Vec<PatchTag>> {
+    let namespace = project_urn.encode_id();
+    let ref_glob = match project_peer {
+        crate::daemon::project::Peer::Local { .. } => {
+            format!("refs/namespaces/{namespace}/refs/tags/{TAG_PREFIX}{id_pattern}")
+        },
+        crate::daemon::project::Peer::Remote { peer_id, .. } => {
+            format!(
+                "refs/namespaces/{namespace}/refs/remotes/{peer_id}/tags/{TAG_PREFIX}{id_pattern}"
+            )
+        },
+    };
+    let default_branch_head_commit_id = project_info.default_branch_head;
+
+    peer.monorepo_unblock({
+        let project_peer = project_peer.clone();
+        move |repo| {
+            let mut tags = vec![];
+            for ref_result in repo
+                .references_glob(&ref_glob)
+                .context("failed to get references from glob")?
+            {
+                let reference = ref_result.context("failed to resolve reference")?;
+                let tag = reference
+                    .peel_to_tag()
+                    .context("failed to peel reference to tag")?;
+
+                let id = tag
+                    .name()
+                    .ok_or_else(|| anyhow::anyhow!("tag name is not valid UTF-8"))?
+                    .strip_prefix(TAG_PREFIX)
+                    .expect("tag name must have prefix")
+                    .to_string();
+                let commit_id = tag.target_id();
+                let merge_base = match repo.merge_base(commit_id, default_branch_head_commit_id) {
+                    Ok(base) => Some(Oid::from(base)),
+                    Err(err) if err.code() == git2::ErrorCode::NotFound => None,
+                    Err(err) => {
+                        return Err(err).context("failed to determine merge base for commits")
+                    },
+                };
+                tags.push(PatchTag {
+                    id,
+                    revision: revision_from_tag(&tag),
+                    peer: project_peer.clone(),
+                    message: Some(
+                        tag.message()
+                            .ok_or_else(|| anyhow::anyhow!("tag message is not valid UTF-8"))?
+                            .to_string(),
+                    ),
+                    commit: Oid::from(commit_id),
+                    merge_base,
+                })
+            }
+            Ok(tags)
+        }
+    })
+    .await
+}
+
+/// Returns the [`Revision`] a patch tag represents. The timestamp is the time the tag was created
+/// or zero if the tag has no tagger.
+fn revision_from_tag(tag: &git2::Tag) -> Revision {
+    Revision {
+        commit: Oid::from(tag.target_id()),
+        message: tag.message().map(ToString::to_string),
+        timestamp: tag
+            .tagger()
+            .and_then(|tagger| u64::try_from(tagger.when().seconds()).ok())
+            .unwrap_or_default()
+            * 1000,
+    }
+}
+
+/// Derive the revisions of a patch from `envelopes` as returned by
+/// [`crate::events::EventLog::get`]. Only revisions recorded by the `author` are considered.
+/// Revisions are returned in chronological order.
+fn fold_revisions(author: librad::PeerId, envelopes: &[crate::events::Envelope]) -> Vec<Revision> {
+    let mut revisions = envelopes
+        .iter()
+        .filter(|envelope| envelope.peer_id == author)
+        .filter_map(|envelope| {
+            match crate::events::parse::<Event>(&envelope.topic, &envelope.event) {
+                Ok(Event::AddRevision(revision)) => Some(revision),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    // Envelopes are ordered from newest to oldest.
+    revisions.reverse();
+    revisions
+}
+
+/// State of a patch derived from its events.
+#[derive(Debug, PartialEq, Eq)]
+struct FoldedEvents {
+    status: Status,
+    comments: Vec<Comment>,
+    last_activity: Option<u64>,
+}
+
+/// Derive the state of a patch from `envelopes` as returned by [`crate::events::EventLog::get`].
+///
+/// The status is determined by the most recent `setStatus` event published by either the
+/// `author` of the patch or one of the `delegates`. Events from other peers are ignored. If the
+/// patch is `merged` the status is always [`State::Merged`].
+fn fold_events(
+    author: librad::PeerId,
+    delegates: &[librad::PeerId],
+    merged: bool,
+    envelopes: &[crate::events::Envelope],
+) -> FoldedEvents {
+    let mut status = None;
+    let mut comments = vec![];
+
+    for envelope in envelopes {
+        match crate::events::parse::<Event>(&envelope.topic, &envelope.event).ok() {
+            Some(Event::SetStatus { status: state }) => {
+                let authorized =
+                    envelope.peer_id == author || delegates.contains(&envelope.peer_id);
+                if status.is_none() && authorized {
+                    status = Some(Status {
+                        current: state,
+                        by_peer_id: Some(envelope.peer_id),
+                    })
+                }
             },
+            Some(Event::AddComment { comment, timestamp }) => comments.push(Comment {
+                peer_id: envelope.peer_id,
+                comment,
+                timestamp,
+            }),
+            Some(Event::AddRevision(_)) | None => {},
+        }
+    }
+
+    let status = if merged {
+        Status {
+            current: State::Merged,
+            by_peer_id: None,
+        }
+    } else {
+        status.unwrap_or(Status {
+            current: State::Open,
+            by_peer_id: None,
+        })
+    };
+
+    // Envelopes are ordered from newest to oldest.
+    comments.reverse();
+
+    FoldedEvents {
+        status,
+        comments,
+        last_activity: envelopes
+            .iter()
+            .filter_map(|envelope| envelope.timestamp)
+            .max(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::commit;
+    use pretty_assertions::assert_eq;
+
+    fn envelope(peer_id: librad::PeerId, event: Event, timestamp: u64) -> crate::events::Envelope {
+        crate::events::Envelope {
+            peer_id,
+            identity: git2::Oid::zero().into(),
+            topic: "patch".to_string(),
+            event: event.into(),
+            timestamp: Some(timestamp),
+            commit: None,
+        }
+    }
+
+    fn new_peer_id() -> librad::PeerId {
+        librad::PeerId::from(link_crypto::SecretKey::new())
+    }
+
+    #[test]
+    fn fold_status() {
+        let author = new_peer_id();
+        let delegate = new_peer_id();
+        let other = new_peer_id();
+
+        let folded = fold_events(author, &[delegate], false, &[]);
+        assert_eq!(
+            folded,
+            FoldedEvents {
+                status: Status {
+                    current: State::Open,
+                    by_peer_id: None
+                },
+                comments: vec![],
+                last_activity: None,
+            }
+        );
+
+        // Newest events come first
+        let envelopes = vec![
+            envelope(
+                other,
+                Event::SetStatus {
+                    status: State::Open,
+                },
+                3000,
+            ),
+            envelope(
+                delegate,
+                Event::SetStatus {
+                    status: State::Closed,
+                },
+                2000,
+            ),
+            envelope(
+                author,
+                Event::SetStatus {
+                    status: State::Open,
+                },
+                1000,
+            ),
+        ];
+        let folded = fold_events(author, &[delegate], false, &envelopes);
+        assert_eq!(
+            folded.status,
+            Status {
+                current: State::Closed,
+                by_peer_id: Some(delegate)
+            }
+        );
+        assert_eq!(folded.last_activity, Some(3000));
+
+        let folded = fold_events(author, &[delegate], true, &envelopes);
+        assert_eq!(folded.status.current, State::Merged);
+    }
+
+    #[test]
+    fn fold_comments() {
+        let author = new_peer_id();
+        let other = new_peer_id();
+
+        let envelopes = vec![
+            envelope(
+                other,
+                Event::AddComment {
+                    comment: "second".to_string(),
+                    timestamp: 2,
+                },
+                2000,
+            ),
+            envelope(
+                author,
+                Event::AddComment {
+                    comment: "first".to_string(),
+                    timestamp: 1,
+                },
+                1000,
+            ),
+        ];
+        let folded = fold_events(author, &[], false, &envelopes);
+        assert_eq!(
+            folded.comments,
+            vec![
+                Comment {
+                    peer_id: author,
+                    comment: "first".to_string(),
+                    timestamp: 1,
+                },
+                Comment {
+                    peer_id: other,
+                    comment: "second".to_string(),
+                    timestamp: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fold_revisions_by_author() {
+        let author = new_peer_id();
+        let other = new_peer_id();
+        let revision = |commit: &str| Revision {
+            commit: Oid::from(git2::Oid::from_str(commit).unwrap()),
+            message: Some(commit.to_string()),
+            timestamp: 0,
         };
 
-        let patch = peer
-            .monorepo_unblock({
-                move |repo| {
-                    let mut patches = vec![];
-                    for ref_result in repo
-                        .references_glob(&ref_glob)
-                        .context("failed to get references from glob")?
-                    {
-                        let reference = ref_result.context("failed to resolve reference")?;
-                        let tag = reference
-                            .peel_to_tag()
-                            .context("failed to peel reference to tag")?;
-
-                        let id = tag
-                            .name()
-                            .ok_or_else(|| anyhow::anyhow!("tag name is not valid UTF-8"))?
-                            .strip_prefix(TAG_PREFIX)
-                            .expect("tag name must have prefix")
-                            .to_string();
-                        let commit_id = tag.target_id();
-                        let merge_base =
-                            match repo.merge_base(commit_id, default_branch_head_commit_id) {
-                                Ok(base) => Some(Oid::from(base)),
-                                Err(err) if err.code() == git2::ErrorCode::NotFound => None,
-                                Err(err) => {
-                                    return Err(err)
-                                        .context("failed to determine merge base for commits")
-                                },
-                            };
-                        patches.push(Patch {
-                            id,
-                            peer: project_peer.clone().into(),
-                            message: Some(
-                                tag.message()
-                                    .ok_or_else(|| {
-                                        anyhow::anyhow!("tag message is not valid UTF-8")
-                                    })?
-                                    .to_string(),
-                            ),
-                            commit: Oid::from(commit_id),
-                            merge_base,
-                        })
-                    }
-                    Ok(patches)
-                }
-            })
-            .await?;
+        let envelopes = vec![
+            envelope(author, Event::AddRevision(revision("02")), 3000),
+            envelope(other, Event::AddRevision(revision("03")), 2000),
+            envelope(author, Event::AddRevision(revision("01")), 1000),
+        ];
+        assert_eq!(
+            fold_revisions(author, &envelopes),
+            vec![revision("01"), revision("02")]
+        );
+    }
 
-        patches.extend(patch)
+    #[test]
+    fn tag_ref_id() {
+        assert_eq!(id_from_tag_ref("refs/tags/radicle-patch/foo"), Some("foo"));
+        assert_eq!(id_from_tag_ref("refs/tags/foo"), None);
+        assert_eq!(id_from_tag_ref("refs/heads/radicle-patch/foo"), None);
     }
 
-    Ok(patches)
+    #[test]
+    fn merge_into_branch() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(temp_dir.path()).unwrap();
+        let signature = crate::daemon::project::create::Signature {
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+        };
+        let ref_name = "refs/heads/main";
+
+        let base = commit(&repo, &[], "base", &[("a", Some("base"))]);
+        repo.reference(ref_name, base, false, "").unwrap();
+
+        let outcome = merge_into(&repo, ref_name, base, "merge", signature.clone()).unwrap();
+        assert_eq!(outcome.strategy, MergeStrategy::UpToDate);
+
+        let patch = commit(&repo, &[base], "patch", &[("b", Some("patch"))]);
+        let outcome = merge_into(&repo, ref_name, patch, "merge", signature.clone()).unwrap();
+        assert_eq!(outcome.strategy, MergeStrategy::FastForward);
+        assert_eq!(repo.refname_to_id(ref_name).unwrap(), patch);
+
+        let head = commit(&repo, &[patch], "head", &[("c", Some("head"))]);
+        repo.reference(ref_name, head, true, "").unwrap();
+        let patch = commit(&repo, &[patch], "patch 2", &[("d", Some("patch 2"))]);
+        let outcome = merge_into(&repo, ref_name, patch, "merge", signature.clone()).unwrap();
+        assert_eq!(outcome.strategy, MergeStrategy::MergeCommit);
+        let merge_commit = repo.find_commit(*outcome.head).unwrap();
+        assert_eq!(
+            merge_commit.parent_ids().collect::<Vec<_>>(),
+            vec![head, patch]
+        );
+
+        let head = commit(
+            &repo,
+            &[*outcome.head],
+            "head change",
+            &[("a", Some("head change"))],
+        );
+        repo.reference(ref_name, head, true, "").unwrap();
+        let patch = commit(
+            &repo,
+            &[*outcome.head],
+            "patch change",
+            &[("a", Some("patch change"))],
+        );
+        match merge_into(&repo, ref_name, patch, "merge", signature) {
+            Err(MergeError::Conflicts { paths }) => assert_eq!(paths, vec!["a".to_string()]),
+            other => panic!("unexpected merge result: {other:?}"),
+        }
+        assert_eq!(repo.refname_to_id(ref_name).unwrap(), head);
+    }
+
+    #[test]
+    fn merge_conflicts_preview() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(temp_dir.path()).unwrap();
+        let base = commit(&repo, &[], "base", &[("a", Some("base"))]);
+        let head = commit(&repo, &[base], "head change", &[("a", Some("head change"))]);
+
+        let patch = commit(&repo, &[base], "patch", &[("b", Some("patch"))]);
+        assert_eq!(
+            merge_conflicts(&repo, head, patch).unwrap(),
+            Vec::<String>::new()
+        );
+
+        let patch = commit(
+            &repo,
+            &[patch],
+            "patch change",
+            &[("a", Some("patch change"))],
+        );
+        assert_eq!(
+            merge_conflicts(&repo, head, patch).unwrap(),
+            vec!["a".to_string()]
+        );
+    }
 }
@@ -165,6 +165,7 @@ async fn run_session(
         let (git_fetch, git_fetch_runner) = crate::git_fetch::create(
             peer.clone(),
             args.git_seeds.unwrap_or_default(),
+            args.git_fetch_quorum,
             std::time::Duration::from_secs(args.git_fetch_interval),
             &store,
         )
@@ -173,10 +174,6 @@ async fn run_session(
         let (watch_monorepo, watch_monorepo_runner) = crate::watch_monorepo::create(peer.clone());
 
         tokio::task::spawn(log_daemon_peer_events(peer.events()));
-        tokio::task::spawn(handle_monorepo_events(
-            watch_monorepo.updates(),
-            git_fetch.clone(),
-        ));
 
         shutdown_runner
             .add_with_shutdown(|shutdown| git_fetch_runner.run(shutdown).map(Ok).boxed());
@@ -192,12 +189,50 @@ async fn run_session(
 
         let event_log = crate::events::EventLog::new(peer.clone(), git_fetch.clone());
 
+        tokio::task::spawn(handle_monorepo_events(
+            buffer_unbounded(watch_monorepo.updates()),
+            git_fetch.clone(),
+            peer.clone(),
+            event_log.clone(),
+        ));
+
+        let (inbox, inbox_runner) = crate::inbox::create(peer.clone(), event_log.clone(), &store)?;
+        let notifications = crate::notification::local_peer_notifications(
+            peer.events(),
+            &git_fetch,
+            &watch_monorepo,
+            peer.librad_peer().peer_id(),
+        );
+        shutdown_runner.add_without_shutdown(
+            inbox_runner
+                .run(buffer_unbounded(notifications))
+                .map(Ok)
+                .boxed(),
+        );
+
+        let (webhooks, webhooks_runner) = crate::webhooks::create(event_log.clone(), &store)?;
+        let notifications = crate::notification::local_peer_notifications(
+            peer.events(),
+            &git_fetch,
+            &watch_monorepo,
+            peer.librad_peer().peer_id(),
+        );
+        shutdown_runner.add_without_shutdown(
+            webhooks_runner
+                .run(buffer_unbounded(notifications))
+                .map(Ok)
+                .boxed(),
+        );
+
         context::Context::Unsealed(context::Unsealed {
             peer,
+            blame_cache: crate::source::BlameCache::default(),
             rest: sealed,
             event_log,
             git_fetch,
+            inbox,
             watch_monorepo,
+            webhooks,
         })
     } else {
         context::Context::Sealed(sealed)
@@ -222,6 +257,25 @@ async fn run_session(
     Ok(())
 }
 
+/// Returns a stream of the items of `stream` that buffers items without bound.
+///
+/// `stream` is consumed by a separate task as soon as items are available. We use this for
+/// consumers that do slow work for every item of a broadcast channel. Otherwise a consumer that
+/// lags behind fills up the channel and new items are dropped for all subscribers.
+fn buffer_unbounded<T: Send + 'static>(
+    stream: impl Stream<Item = T> + Send + 'static,
+) -> impl Stream<Item = T> + Send + 'static {
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    // Forwarding stops with an error when the receiver is dropped.
+    tokio::task::spawn(
+        stream
+            .map(Ok::<_, futures::channel::mpsc::SendError>)
+            .forward(tx)
+            .map(|_| ()),
+    );
+    rx
+}
+
 async fn log_daemon_peer_events(events: impl Stream<Item = crate::daemon::peer::Event>) {
     events
         .for_each(|event| {
@@ -258,19 +312,47 @@ async fn log_daemon_peer_events(events: impl Stream<Item = crate::daemon::peer::
 }
 
 // Trigger a `git_fetch` whenever a project is cloned via `rad clone` to set the project's seed URL
-// in the KV store.
+// in the KV store. Record a patch revision whenever a local patch tag is updated. Push our
+// contributions to the project's seeds whenever a local branch or tag changes.
+//
+// Before handling the first event, we record the revisions of patch tags that were updated while
+// Upstream was not running.
 async fn handle_monorepo_events(
-    events: impl Stream<Item = link_identities::Urn<link_identities::Revision>>,
+    events: impl Stream<Item = crate::watch_monorepo::Update>,
     git_fetch_handle: crate::git_fetch::Handle,
+    peer: crate::peer::Peer,
+    event_log: crate::events::EventLog,
 ) {
     let git_fetch_handle = &git_fetch_handle;
+    let peer = &peer;
+    let event_log = &event_log;
+    if let Err(err) = crate::patch::record_all_revisions(peer, event_log).await {
+        tracing::warn!(?err, "failed to record patch revisions");
+    }
     events
-        .for_each(|event| async move {
-            if let Some(path) = event.path {
-                if path == librad::reflike!("refs/rad/id") {
-                    git_fetch_handle.add(event.id).await;
+        .for_each(|update| async move {
+            let identity = update.urn.id;
+            let mut contributions_changed = false;
+            for change in update.changes {
+                if change.name == "refs/rad/id" {
+                    git_fetch_handle.add(identity).await;
+                } else if let Some(patch_id) = crate::patch::id_from_tag_ref(&change.name) {
+                    let project_urn = librad::git::Urn::new(identity);
+                    if let Err(err) =
+                        crate::patch::record_revision(peer, event_log, project_urn, patch_id).await
+                    {
+                        tracing::warn!(?err, %patch_id, "failed to record patch revision");
+                    }
+                }
+
+                if change.name.starts_with("refs/heads/") || change.name.starts_with("refs/tags/") {
+                    contributions_changed = true;
                 }
             }
+
+            if contributions_changed {
+                git_fetch_handle.push_contributions(identity).await;
+            }
         })
         .await;
 }
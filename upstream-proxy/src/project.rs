@@ -193,7 +193,10 @@ impl Projects {
     ///   * We couldn't get the list of projects
     ///   * We couldn't inspect the `signed_refs` of the project
     ///   * We couldn't get stats for a project
-    pub async fn list(peer: &crate::peer::Peer) -> Result<Self, error::Error> {
+    pub async fn list(
+        peer: &crate::peer::Peer,
+        git_fetch: &crate::git_fetch::Handle,
+    ) -> Result<Self, error::Error> {
         let mut projects = Self {
             tracked: vec![],
             contributed: vec![],
@@ -230,10 +233,10 @@ impl Projects {
                 };
 
             let project = Project {
+                seed: git_fetch.get_seed(urn.id),
                 urn,
                 metadata,
                 stats,
-                seed: None,
             };
 
             let refs = match crate::daemon::state::load_refs(
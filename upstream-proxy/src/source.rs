@@ -0,0 +1,1610 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Operations on project source code that are not provided by [`radicle_source`].
+//!
+//! Revisions are resolved to commits in the namespace of a project (see [`resolve_revision`]).
+//! The operations then work on the commits in the monorepo directly with [`git2`].
+
+use std::{
+    collections::VecDeque,
+    convert::TryFrom as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use librad::git::types::{Reference, Single};
+use link_crypto::PeerId;
+use link_identities::git::Urn;
+
+/// Resolve `revision` to a commit in the namespace of the project `urn`. If `revision` is `None`,
+/// returns the commit that `branch` points to. Returns `None` if the revision does not exist.
+pub fn resolve_revision(
+    repo: &git2::Repository,
+    urn: &Urn,
+    branch: &Reference<Single>,
+    revision: Option<radicle_source::Revision<PeerId>>,
+) -> anyhow::Result<Option<git2::Oid>> {
+    let namespace = urn.encode_id();
+    let branch_ref = |name: &str, peer_id: Option<PeerId>| match peer_id {
+        None => format!("refs/namespaces/{namespace}/refs/heads/{name}"),
+        Some(peer_id) => format!("refs/namespaces/{namespace}/refs/remotes/{peer_id}/heads/{name}"),
+    };
+    let reference_name = match revision {
+        None => branch_ref(branch.name.as_str(), branch.remote),
+        Some(radicle_source::Revision::Branch { name, peer_id }) => branch_ref(&name, peer_id),
+        Some(radicle_source::Revision::Tag { name }) => {
+            format!("refs/namespaces/{namespace}/refs/tags/{name}")
+        },
+        Some(radicle_source::Revision::Sha { sha }) => {
+            let sha = git2::Oid::from(sha);
+            return match repo.find_commit(sha) {
+                Ok(commit) => Ok(Some(commit.id())),
+                Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+                Err(err) => Err(err).context(format!("failed to find commit {sha}")),
+            };
+        },
+    };
+
+    let reference = match repo.find_reference(&reference_name) {
+        Ok(reference) => reference,
+        Err(err)
+            if err.code() == git2::ErrorCode::NotFound
+                || err.code() == git2::ErrorCode::InvalidSpec =>
+        {
+            return Ok(None)
+        },
+        Err(err) => return Err(err).context(format!("failed to find reference {reference_name}")),
+    };
+    let commit = reference.peel_to_commit().context(format!(
+        "failed to get commit of reference {reference_name}"
+    ))?;
+    Ok(Some(commit.id()))
+}
+
+/// Summary of a commit. The serialization matches the commit headers of [`radicle_source`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitHeader {
+    pub sha1: radicle_git_ext::Oid,
+    pub author: Person,
+    pub committer: Person,
+    /// First line of the commit message.
+    pub summary: String,
+    /// Commit message without the summary.
+    pub description: String,
+    /// Commit time in seconds since the Unix epoch.
+    pub committer_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+}
+
+impl Person {
+    fn from_git(signature: &git2::Signature) -> Self {
+        Self {
+            name: String::from_utf8_lossy(signature.name_bytes()).to_string(),
+            email: String::from_utf8_lossy(signature.email_bytes()).to_string(),
+        }
+    }
+}
+
+impl CommitHeader {
+    pub fn from_git(commit: &git2::Commit) -> Self {
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        let (summary, description) = match message.split_once('\n') {
+            Some((summary, description)) => (summary, description.trim()),
+            None => (&*message, ""),
+        };
+        Self {
+            sha1: commit.id().into(),
+            author: Person::from_git(&commit.author()),
+            committer: Person::from_git(&commit.committer()),
+            summary: summary.trim().to_string(),
+            description: description.to_string(),
+            committer_time: commit.committer().when().seconds(),
+        }
+    }
+}
+
+/// Page of the commit history of a revision.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct History {
+    /// Commits, most recent first.
+    pub headers: Vec<CommitHeader>,
+    /// `true` if there are older commits than the ones in `headers`.
+    pub has_more: bool,
+}
+
+impl History {
+    /// Walk the history of `head`, most recent commit first.
+    ///
+    /// If `path` is given, only commits that changed the file or directory at `path` are included.
+    /// Renames of the path are followed. If `after` is given, the history starts with the commit
+    /// after `after`. The history is empty if `after` is not part of it. At most `limit` commits
+    /// are returned.
+    pub fn walk(
+        repo: &git2::Repository,
+        head: git2::Oid,
+        path: Option<&str>,
+        after: Option<git2::Oid>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Self> {
+        let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+        revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .context("failed to set revwalk sorting")?;
+        revwalk
+            .push(head)
+            .context(format!("failed to push commit {head} to revwalk"))?;
+
+        let mut path = path
+            .map(|path| path.trim_matches('/'))
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        let mut skipping = after.is_some();
+        let mut headers = vec![];
+        let mut has_more = false;
+        for oid_result in revwalk {
+            let id = oid_result.context("failed to get commit from revwalk")?;
+            let commit = repo
+                .find_commit(id)
+                .context(format!("failed to find commit {id}"))?;
+
+            // We need to look at every commit, including skipped ones, to follow renames.
+            let touches_path = match &mut path {
+                None => true,
+                Some(path) => match path_change(repo, &commit, path)? {
+                    PathChange::Unchanged => false,
+                    PathChange::Changed => true,
+                    PathChange::RenamedFrom(old_path) => {
+                        *path = old_path;
+                        true
+                    },
+                },
+            };
+
+            if skipping {
+                skipping = after != Some(id);
+                continue;
+            }
+            if !touches_path {
+                continue;
+            }
+            if limit.map_or(false, |limit| headers.len() >= limit) {
+                has_more = true;
+                break;
+            }
+            headers.push(CommitHeader::from_git(&commit));
+        }
+
+        Ok(Self { headers, has_more })
+    }
+}
+
+/// How a commit changed the tree entry at a path relative to its parents.
+enum PathChange {
+    Unchanged,
+    Changed,
+    /// The entry was moved from the given path.
+    RenamedFrom(PathBuf),
+}
+
+/// Determine how `commit` changed the tree entry at `path`.
+///
+/// Like `git log`, a merge commit only changes the entry if it differs from the entry in every
+/// parent. Renames are only detected relative to the first parent.
+fn path_change(
+    repo: &git2::Repository,
+    commit: &git2::Commit,
+    path: &Path,
+) -> anyhow::Result<PathChange> {
+    let tree = commit.tree().context("failed to get commit tree")?;
+    let entry_id = tree_entry_id(&tree, path)?;
+    let parent_trees = commit
+        .parents()
+        .map(|parent| parent.tree().context("failed to get parent commit tree"))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let parent_entry_ids = parent_trees
+        .iter()
+        .map(|parent_tree| tree_entry_id(parent_tree, path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if parent_trees.is_empty() {
+        return Ok(if entry_id.is_some() {
+            PathChange::Changed
+        } else {
+            PathChange::Unchanged
+        });
+    }
+    if parent_entry_ids.contains(&entry_id) {
+        return Ok(PathChange::Unchanged);
+    }
+
+    // The entry was added relative to the first parent. It may have been moved from another path.
+    if entry_id.is_some() && parent_entry_ids[0].is_none() {
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&parent_trees[0]), Some(&tree), None)
+            .context("failed to diff commit trees")?;
+        let mut find_options = git2::DiffFindOptions::new();
+        find_options.renames(true);
+        diff.find_similar(Some(&mut find_options))
+            .context("failed to detect renames")?;
+        for delta in diff.deltas() {
+            if delta.status() == git2::Delta::Renamed && delta.new_file().path() == Some(path) {
+                if let Some(old_path) = delta.old_file().path() {
+                    return Ok(PathChange::RenamedFrom(old_path.to_path_buf()));
+                }
+            }
+        }
+    }
+
+    Ok(PathChange::Changed)
+}
+
+/// Returns the ID of the tree entry at `path` or `None` if there is no such entry.
+fn tree_entry_id(tree: &git2::Tree, path: &Path) -> anyhow::Result<Option<git2::Oid>> {
+    match tree.get_path(path) {
+        Ok(entry) => Ok(Some(entry.id())),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err).context(format!("failed to get tree entry {}", path.display())),
+    }
+}
+
+/// Comparison of two commits.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub base: radicle_git_ext::Oid,
+    pub head: radicle_git_ext::Oid,
+    /// Best common ancestor of `base` and `head`. `None` if the commits have unrelated histories.
+    pub merge_base: Option<radicle_git_ext::Oid>,
+    /// Commits reachable from `head` but not from `base`, most recent first.
+    pub commits: Vec<CommitHeader>,
+    /// Changes from the merge base to `head`. If there is no merge base, the changes from `base`
+    /// to `head`.
+    pub diff: crate::diff::Diff,
+    pub stats: crate::diff::DiffStats,
+}
+
+impl Comparison {
+    pub fn between(
+        repo: &git2::Repository,
+        base: git2::Oid,
+        head: git2::Oid,
+    ) -> anyhow::Result<Self> {
+        let merge_base = match repo.merge_base(base, head) {
+            Ok(merge_base) => Some(merge_base),
+            Err(err) if err.code() == git2::ErrorCode::NotFound => None,
+            Err(err) => return Err(err).context("failed to determine merge base for commits"),
+        };
+
+        let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+        revwalk
+            .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .context("failed to set revwalk sorting")?;
+        revwalk
+            .push(head)
+            .context(format!("failed to push commit {head} to revwalk"))?;
+        revwalk
+            .hide(base)
+            .context(format!("failed to hide commit {base}"))?;
+        let mut commits = vec![];
+        for oid_result in revwalk {
+            let id = oid_result.context("failed to get commit from revwalk")?;
+            let commit = repo
+                .find_commit(id)
+                .context(format!("failed to find commit {id}"))?;
+            commits.push(CommitHeader::from_git(&commit));
+        }
+
+        let diff =
+            crate::diff::Diff::between_commits(repo, Some(merge_base.unwrap_or(base)), head)?;
+        Ok(Self {
+            base: base.into(),
+            head: head.into(),
+            merge_base: merge_base.map(Into::into),
+            commits,
+            stats: diff.stats(),
+            diff,
+        })
+    }
+}
+
+/// Number of blames kept in a [`BlameCache`].
+const BLAME_CACHE_SIZE: usize = 64;
+
+/// Maximum number of file matches and of line matches returned by [`SearchResults::search`].
+const MAX_SEARCH_RESULTS: usize = 100;
+
+/// Blobs larger than this number of bytes are not searched for content.
+const MAX_SEARCH_BLOB_SIZE: usize = 1024 * 1024;
+
+/// Number of lines before and after a matching line that are included in a [`LineMatch`].
+const SEARCH_CONTEXT_LINES: usize = 2;
+
+/// Git file mode of executable blobs.
+const FILE_MODE_EXECUTABLE: i32 = 0o100_755;
+
+/// Git file mode of symbolic links.
+const FILE_MODE_LINK: i32 = 0o120_000;
+
+/// Line-by-line attribution of a file to the commits that last changed the lines.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Blame {
+    pub path: String,
+    /// Commit the file was blamed at.
+    pub commit: radicle_git_ext::Oid,
+    /// Consecutive ranges of lines, ordered by line number, that cover the whole file.
+    pub hunks: Vec<BlameHunk>,
+}
+
+/// A range of lines that were last changed by the same commit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameHunk {
+    /// Line number of the first line of the range, starting at 1.
+    pub start_line: usize,
+    /// Number of lines in the range.
+    pub lines: usize,
+    /// Commit that last changed the lines.
+    pub commit: CommitHeader,
+}
+
+impl Blame {
+    /// Blame the file at `path` in `commit`. Returns `None` if there is no file at `path`.
+    ///
+    /// Blames are cached in `cache` since computing them requires walking the history of the file.
+    pub fn compute(
+        repo: &git2::Repository,
+        cache: &BlameCache,
+        commit: git2::Oid,
+        path: &str,
+    ) -> anyhow::Result<Option<Arc<Self>>> {
+        let tree = repo
+            .find_commit(commit)
+            .context(format!("failed to find commit {commit}"))?
+            .tree()
+            .context("failed to get commit tree")?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err).context(format!("failed to get tree entry {path}")),
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) {
+            return Ok(None);
+        }
+
+        let key = BlameKey {
+            blob: entry.id(),
+            commit,
+            path: path.to_string(),
+        };
+        if let Some(blame) = cache.get(&key) {
+            return Ok(Some(blame));
+        }
+
+        let mut options = git2::BlameOptions::new();
+        options.newest_commit(commit);
+        let git_blame = repo
+            .blame_file(Path::new(path), Some(&mut options))
+            .context(format!("failed to blame {path}"))?;
+
+        let mut headers = std::collections::HashMap::<git2::Oid, CommitHeader>::new();
+        let mut hunks = Vec::with_capacity(git_blame.len());
+        for hunk in git_blame.iter() {
+            let commit_id = hunk.final_commit_id();
+            let header = match headers.get(&commit_id) {
+                Some(header) => header.clone(),
+                None => {
+                    let hunk_commit = repo
+                        .find_commit(commit_id)
+                        .context(format!("failed to find commit {commit_id}"))?;
+                    let header = CommitHeader::from_git(&hunk_commit);
+                    headers.insert(commit_id, header.clone());
+                    header
+                },
+            };
+            hunks.push(BlameHunk {
+                start_line: hunk.final_start_line(),
+                lines: hunk.lines_in_hunk(),
+                commit: header,
+            });
+        }
+
+        let blame = Arc::new(Self {
+            path: path.to_string(),
+            commit: commit.into(),
+            hunks,
+        });
+        cache.insert(key, blame.clone());
+        Ok(Some(blame))
+    }
+}
+
+/// Key of a [`BlameCache`] entry.
+///
+/// The path is part of the key because identical blobs at different paths have different
+/// histories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BlameKey {
+    blob: git2::Oid,
+    commit: git2::Oid,
+    path: String,
+}
+
+/// Cache for the most recently used [`Blame`]s. Clones share the same cache.
+#[derive(Clone, Default)]
+pub struct BlameCache {
+    /// Entries ordered by use, the most recently used entry first.
+    entries: Arc<parking_lot::Mutex<VecDeque<(BlameKey, Arc<Blame>)>>>,
+}
+
+impl BlameCache {
+    fn get(&self, key: &BlameKey) -> Option<Arc<Blame>> {
+        let mut entries = self.entries.lock();
+        let index = entries.iter().position(|(entry_key, _)| entry_key == key)?;
+        let entry = entries.remove(index)?;
+        let blame = entry.1.clone();
+        entries.push_front(entry);
+        Some(blame)
+    }
+
+    fn insert(&self, key: BlameKey, blame: Arc<Blame>) {
+        let mut entries = self.entries.lock();
+        entries.retain(|(entry_key, _)| entry_key != &key);
+        entries.push_front((key, blame));
+        entries.truncate(BLAME_CACHE_SIZE);
+    }
+}
+
+/// Files and lines in a tree that match a search query.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    /// Files whose path matches the query, best match first.
+    pub files: Vec<FileMatch>,
+    /// Lines that contain the query, ordered by path and line number.
+    pub lines: Vec<LineMatch>,
+    /// `true` if there were more matches than returned.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatch {
+    pub path: String,
+    /// Quality of the match. Higher is better.
+    pub score: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineMatch {
+    pub path: String,
+    /// Line number of the matching line, starting at 1.
+    pub line_number: usize,
+    pub line: String,
+    /// Lines immediately before the matching line.
+    pub context_before: Vec<String>,
+    /// Lines immediately after the matching line.
+    pub context_after: Vec<String>,
+}
+
+impl SearchResults {
+    /// Search the tree of `commit` below the directory `path_prefix` for files whose path fuzzy
+    /// matches `query` (see [`fuzzy_score`]) and for lines that contain `query`. Matching ignores
+    /// case. Returns `None` if there is no directory at `path_prefix`.
+    ///
+    /// At most [`MAX_SEARCH_RESULTS`] matches of each kind are returned. Binary blobs and blobs
+    /// larger than [`MAX_SEARCH_BLOB_SIZE`] are not searched for content.
+    pub fn search(
+        repo: &git2::Repository,
+        commit: git2::Oid,
+        query: &str,
+        path_prefix: Option<&str>,
+    ) -> anyhow::Result<Option<Self>> {
+        let tree = repo
+            .find_commit(commit)
+            .context(format!("failed to find commit {commit}"))?
+            .tree()
+            .context("failed to get commit tree")?;
+        let path_prefix = path_prefix
+            .map(|path_prefix| path_prefix.trim_matches('/'))
+            .filter(|path_prefix| !path_prefix.is_empty());
+        let tree = match path_prefix {
+            None => tree,
+            Some(path_prefix) => match tree.get_path(Path::new(path_prefix)) {
+                Ok(entry) if entry.kind() == Some(git2::ObjectType::Tree) => repo
+                    .find_tree(entry.id())
+                    .context(format!("failed to find tree {path_prefix}"))?,
+                Ok(_) => return Ok(None),
+                Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+                Err(err) => {
+                    return Err(err).context(format!("failed to get tree entry {path_prefix}"))
+                },
+            },
+        };
+
+        let mut blobs = vec![];
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                if let Some(name) = entry.name() {
+                    let path = match path_prefix {
+                        Some(path_prefix) => format!("{path_prefix}/{root}{name}"),
+                        None => format!("{root}{name}"),
+                    };
+                    blobs.push((path, entry.id()));
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .context("failed to walk tree")?;
+
+        let mut files = blobs
+            .iter()
+            .filter_map(|(path, _)| {
+                fuzzy_score(path, query).map(|score| FileMatch {
+                    path: path.clone(),
+                    score,
+                })
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.path.len().cmp(&b.path.len()))
+                .then(a.path.cmp(&b.path))
+        });
+        let mut truncated = files.len() > MAX_SEARCH_RESULTS;
+        files.truncate(MAX_SEARCH_RESULTS);
+
+        let query = query.to_lowercase();
+        let mut lines = vec![];
+        'blobs: for (path, id) in &blobs {
+            let blob = repo
+                .find_blob(*id)
+                .context(format!("failed to find blob {id}"))?;
+            if blob.size() > MAX_SEARCH_BLOB_SIZE || blob.is_binary() {
+                continue;
+            }
+            let content = match std::str::from_utf8(blob.content()) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            let file_lines = content.lines().collect::<Vec<_>>();
+            for (index, line) in file_lines.iter().enumerate() {
+                if !line.to_lowercase().contains(&query) {
+                    continue;
+                }
+                if lines.len() >= MAX_SEARCH_RESULTS {
+                    truncated = true;
+                    break 'blobs;
+                }
+                let context_end = (index + 1 + SEARCH_CONTEXT_LINES).min(file_lines.len());
+                lines.push(LineMatch {
+                    path: path.clone(),
+                    line_number: index + 1,
+                    line: line.to_string(),
+                    context_before: file_lines[index.saturating_sub(SEARCH_CONTEXT_LINES)..index]
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                    context_after: file_lines[index + 1..context_end]
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                });
+            }
+        }
+
+        Ok(Some(Self {
+            files,
+            lines,
+            truncated,
+        }))
+    }
+}
+
+/// Score how well `path` matches `query`. Returns `None` if the characters of `query` do not
+/// appear in `path` in the same order. Matching ignores case.
+///
+/// Consecutive characters, characters at the start of a path segment or word, and characters in
+/// the file name increase the score.
+fn fuzzy_score(path: &str, query: &str) -> Option<usize> {
+    let path = path.to_lowercase().chars().collect::<Vec<_>>();
+    let query = query.to_lowercase().chars().collect::<Vec<_>>();
+    let file_name_start = path.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut previous_match = None;
+    for (index, c) in path.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if *c != query[query_index] {
+            continue;
+        }
+
+        score += 1;
+        if index > 0 && previous_match == Some(index - 1) {
+            score += 5;
+        }
+        if index == 0 || matches!(path[index - 1], '/' | '_' | '-' | '.' | ' ') {
+            score += 3;
+        }
+        if index >= file_name_start {
+            score += 2;
+        }
+        previous_match = Some(index);
+        query_index += 1;
+    }
+
+    if query_index == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+/// A file whose content can be written with [`RawBlob::write`].
+#[derive(Debug, Clone)]
+pub struct RawBlob {
+    pub id: git2::Oid,
+    /// Size of the content in bytes.
+    pub size: usize,
+    /// MIME type guessed from the path and the content.
+    pub content_type: String,
+}
+
+impl RawBlob {
+    /// Find the file at `path` in `commit`. Returns `None` if there is no file at `path`.
+    pub fn find(
+        repo: &git2::Repository,
+        commit: git2::Oid,
+        path: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let tree = repo
+            .find_commit(commit)
+            .context(format!("failed to find commit {commit}"))?
+            .tree()
+            .context("failed to get commit tree")?;
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err).context(format!("failed to get tree entry {path}")),
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) {
+            return Ok(None);
+        }
+        let blob = repo
+            .find_blob(entry.id())
+            .context(format!("failed to find blob {}", entry.id()))?;
+
+        let content_type = match mime_guess::from_path(path).first() {
+            Some(mime) => mime.to_string(),
+            None if blob.is_binary() => "application/octet-stream".to_string(),
+            None => "text/plain; charset=utf-8".to_string(),
+        };
+        Ok(Some(Self {
+            id: blob.id(),
+            size: blob.size(),
+            content_type,
+        }))
+    }
+
+    /// Write the content of the file to `writer`.
+    pub fn write(
+        &self,
+        repo: &git2::Repository,
+        mut writer: impl std::io::Write,
+    ) -> anyhow::Result<()> {
+        let blob = repo
+            .find_blob(self.id)
+            .context(format!("failed to find blob {}", self.id))?;
+        writer
+            .write_all(blob.content())
+            .context("failed to write blob content")
+    }
+}
+
+/// File format of an archive created with [`archive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::TarGz => "application/gzip",
+            Self::Zip => "application/zip",
+        }
+    }
+}
+
+/// Write an archive of the tree of `commit` to `writer`. The paths of all files in the archive
+/// start with the directory `prefix`.
+///
+/// File modes and symbolic links are preserved. Submodules are not included. Zip archives are
+/// written to a temporary file first because the zip writer needs to seek.
+pub fn archive(
+    repo: &git2::Repository,
+    commit: git2::Oid,
+    format: ArchiveFormat,
+    prefix: &str,
+    writer: impl std::io::Write,
+) -> anyhow::Result<()> {
+    let commit = repo
+        .find_commit(commit)
+        .context(format!("failed to find commit {commit}"))?;
+    let tree = commit.tree().context("failed to get commit tree")?;
+    let mtime = u64::try_from(commit.committer().when().seconds()).unwrap_or_default();
+
+    let mut entries = vec![];
+    tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                entries.push((
+                    format!("{prefix}/{root}{name}"),
+                    entry.id(),
+                    entry.filemode(),
+                ));
+            }
+        }
+        git2::TreeWalkResult::Ok
+    })
+    .context("failed to walk tree")?;
+
+    let mut archive = match format {
+        ArchiveFormat::TarGz => ArchiveWriter::TarGz(tar::Builder::new(
+            flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+        )),
+        ArchiveFormat::Zip => ArchiveWriter::Zip {
+            zip: zip::ZipWriter::new(
+                tempfile::tempfile().context("failed to create temporary file")?,
+            ),
+            writer,
+        },
+    };
+    for (path, id, filemode) in entries {
+        let blob = repo
+            .find_blob(id)
+            .context(format!("failed to find blob {id}"))?;
+        archive
+            .append(&path, filemode, blob.content(), mtime)
+            .context(format!("failed to add {path} to archive"))?;
+    }
+    archive.finish().context("failed to write archive")
+}
+
+enum ArchiveWriter<W: std::io::Write> {
+    TarGz(tar::Builder<flate2::write::GzEncoder<W>>),
+    /// Zip archive in a temporary file that is copied to `writer` when it is finished.
+    Zip {
+        zip: zip::ZipWriter<std::fs::File>,
+        writer: W,
+    },
+}
+
+impl<W: std::io::Write> ArchiveWriter<W> {
+    /// Add a file with the given Git `filemode`. If the file is a symbolic link, `content` is the
+    /// link target.
+    fn append(
+        &mut self,
+        path: &str,
+        filemode: i32,
+        content: &[u8],
+        mtime: u64,
+    ) -> anyhow::Result<()> {
+        let mode = if filemode == FILE_MODE_EXECUTABLE {
+            0o755
+        } else {
+            0o644
+        };
+        match self {
+            Self::TarGz(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_mtime(mtime);
+                if filemode == FILE_MODE_LINK {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_mode(0o777);
+                    header.set_size(0);
+                    let target = String::from_utf8_lossy(content);
+                    builder.append_link(&mut header, path, &*target)?;
+                } else {
+                    header.set_mode(mode);
+                    header.set_size(content.len() as u64);
+                    builder.append_data(&mut header, path, content)?;
+                }
+            },
+            Self::Zip { zip, .. } => {
+                let options = zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated);
+                if filemode == FILE_MODE_LINK {
+                    let target = String::from_utf8_lossy(content);
+                    zip.add_symlink(path, &*target, options)?;
+                } else {
+                    zip.start_file(path, options.unix_permissions(mode))?;
+                    std::io::Write::write_all(zip, content)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Self::TarGz(builder) => {
+                builder.into_inner()?.finish()?;
+            },
+            Self::Zip {
+                mut zip,
+                mut writer,
+            } => {
+                let mut file = zip.finish()?;
+                std::io::Seek::rewind(&mut file)?;
+                std::io::copy(&mut file, &mut writer)?;
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{commit, commit_tree, write_tree, FILE_MODE_BLOB};
+    use librad::git::types::Namespace;
+    use pretty_assertions::assert_eq;
+
+    fn summaries(headers: &[CommitHeader]) -> Vec<&str> {
+        headers
+            .iter()
+            .map(|header| header.summary.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn resolve_revision_in_namespace() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let urn = Urn::new(
+            git2::Oid::hash_object(git2::ObjectType::Blob, b"project")
+                .unwrap()
+                .into(),
+        );
+        let namespace = urn.encode_id();
+        let peer_id = PeerId::from(link_crypto::SecretKey::new());
+
+        let main = commit(&repo, &[], "main", &[("a.txt", Some("a\n"))]);
+        let dev = commit(&repo, &[main], "dev", &[("a.txt", Some("dev\n"))]);
+        repo.reference(
+            &format!("refs/namespaces/{namespace}/refs/heads/main"),
+            main,
+            false,
+            "",
+        )
+        .unwrap();
+        repo.reference(
+            &format!("refs/namespaces/{namespace}/refs/remotes/{peer_id}/heads/dev"),
+            dev,
+            false,
+            "",
+        )
+        .unwrap();
+        let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tag = repo
+            .tag_annotation_create(
+                "v1",
+                &repo.find_object(main, None).unwrap(),
+                &signature,
+                "v1",
+            )
+            .unwrap();
+        repo.reference(
+            &format!("refs/namespaces/{namespace}/refs/tags/v1"),
+            tag,
+            false,
+            "",
+        )
+        .unwrap();
+        // Branches outside of the namespace are not considered.
+        repo.reference("refs/heads/other", dev, false, "").unwrap();
+
+        let branch = Reference::head(Namespace::from(urn.clone()), None, "main".parse().unwrap());
+        let resolve = |revision| resolve_revision(&repo, &urn, &branch, revision).unwrap();
+
+        assert_eq!(resolve(None), Some(main));
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Branch {
+                name: "main".to_string(),
+                peer_id: None,
+            })),
+            Some(main)
+        );
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Branch {
+                name: "dev".to_string(),
+                peer_id: Some(peer_id),
+            })),
+            Some(dev)
+        );
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Tag {
+                name: "v1".to_string(),
+            })),
+            Some(main)
+        );
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Sha { sha: dev.into() })),
+            Some(dev)
+        );
+
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Branch {
+                name: "other".to_string(),
+                peer_id: None,
+            })),
+            None
+        );
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Branch {
+                name: "..".to_string(),
+                peer_id: None,
+            })),
+            None
+        );
+        assert_eq!(
+            resolve(Some(radicle_source::Revision::Sha {
+                sha: git2::Oid::hash_object(git2::ObjectType::Blob, b"missing")
+                    .unwrap()
+                    .into(),
+            })),
+            None
+        );
+    }
+
+    /// Create a history in which `old.txt` is changed, renamed to `new.txt` and changed again.
+    /// Returns the commits, oldest first.
+    fn rename_history(repo: &git2::Repository) -> Vec<git2::Oid> {
+        let content = "1\n2\n3\n4\n5\n";
+        let changed = "1\n2\n3\n4\nfive\n";
+        let first = commit(
+            repo,
+            &[],
+            "add old",
+            &[("old.txt", Some(content)), ("other.txt", Some("a\n"))],
+        );
+        let second = commit(
+            repo,
+            &[first],
+            "change other",
+            &[("other.txt", Some("b\n"))],
+        );
+        let third = commit(repo, &[second], "change old", &[("old.txt", Some(changed))]);
+        let fourth = commit(
+            repo,
+            &[third],
+            "rename old to new",
+            &[("old.txt", None), ("new.txt", Some(changed))],
+        );
+        let fifth = commit(
+            repo,
+            &[fourth],
+            "change new",
+            &[("new.txt", Some("one\n2\n3\n4\nfive\n"))],
+        );
+        vec![first, second, third, fourth, fifth]
+    }
+
+    #[test]
+    fn history_follows_renames() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commits = rename_history(&repo);
+        let head = commits[4];
+
+        let history = History::walk(&repo, head, Some("/new.txt"), None, None).unwrap();
+        assert_eq!(
+            summaries(&history.headers),
+            vec!["change new", "rename old to new", "change old", "add old"]
+        );
+        assert!(!history.has_more);
+
+        let history = History::walk(&repo, head, Some("other.txt"), None, None).unwrap();
+        assert_eq!(summaries(&history.headers), vec!["change other", "add old"]);
+
+        let history = History::walk(&repo, head, Some("missing.txt"), None, None).unwrap();
+        assert!(history.headers.is_empty());
+    }
+
+    #[test]
+    fn history_pages() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commits = rename_history(&repo);
+        let head = commits[4];
+
+        let history = History::walk(&repo, head, None, None, Some(2)).unwrap();
+        assert_eq!(
+            summaries(&history.headers),
+            vec!["change new", "rename old to new"]
+        );
+        assert!(history.has_more);
+
+        let history = History::walk(&repo, head, None, Some(commits[3]), Some(2)).unwrap();
+        assert_eq!(
+            summaries(&history.headers),
+            vec!["change old", "change other"]
+        );
+        assert!(history.has_more);
+
+        let history = History::walk(&repo, head, None, Some(commits[1]), Some(2)).unwrap();
+        assert_eq!(summaries(&history.headers), vec!["add old"]);
+        assert!(!history.has_more);
+
+        // The rename is followed in the skipped commits.
+        let history =
+            History::walk(&repo, head, Some("new.txt"), Some(commits[3]), Some(1)).unwrap();
+        assert_eq!(summaries(&history.headers), vec!["change old"]);
+        assert!(history.has_more);
+
+        // A commit that is not part of the history results in an empty page.
+        let unrelated = commit(&repo, &[], "unrelated", &[("new.txt", Some("unrelated\n"))]);
+        let history = History::walk(&repo, head, None, Some(unrelated), Some(2)).unwrap();
+        assert!(history.headers.is_empty());
+        assert!(!history.has_more);
+    }
+
+    #[test]
+    fn comparison_diverged() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let root = commit(&repo, &[], "root", &[("a.txt", Some("a\n"))]);
+        let base = commit(&repo, &[root], "base", &[("a.txt", Some("a\nbase\n"))]);
+        let head1 = commit(&repo, &[root], "head 1", &[("b.txt", Some("b\n"))]);
+        let head2 = commit(&repo, &[head1], "head 2", &[("b.txt", Some("b\nc\n"))]);
+
+        let comparison = Comparison::between(&repo, base, head2).unwrap();
+        assert_eq!(
+            comparison.merge_base,
+            Some(radicle_git_ext::Oid::from(root))
+        );
+        assert_eq!(summaries(&comparison.commits), vec!["head 2", "head 1"]);
+        // The changes on the base branch are not part of the diff.
+        assert_eq!(
+            comparison.stats,
+            crate::diff::DiffStats {
+                files_changed: 1,
+                additions: 2,
+                deletions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn comparison_head_is_ancestor_of_base() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let head = commit(&repo, &[], "head", &[("a.txt", Some("a\n"))]);
+        let base = commit(&repo, &[head], "base", &[("a.txt", Some("a\nb\n"))]);
+
+        let comparison = Comparison::between(&repo, base, head).unwrap();
+        assert_eq!(
+            comparison.merge_base,
+            Some(radicle_git_ext::Oid::from(head))
+        );
+        assert!(comparison.commits.is_empty());
+        assert!(comparison.diff.files.is_empty());
+        assert_eq!(comparison.stats, crate::diff::DiffStats::default());
+    }
+
+    #[test]
+    fn comparison_unrelated_histories() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let base = commit(&repo, &[], "base", &[("a.txt", Some("a\nb\n"))]);
+        let head1 = commit(&repo, &[], "head 1", &[("c.txt", Some("c\n"))]);
+        let head2 = commit(&repo, &[head1], "head 2", &[("c.txt", Some("c\nd\n"))]);
+
+        let comparison = Comparison::between(&repo, base, head2).unwrap();
+        assert_eq!(comparison.merge_base, None);
+        assert_eq!(summaries(&comparison.commits), vec!["head 2", "head 1"]);
+        // Without a merge base, the diff is relative to the base.
+        assert_eq!(
+            comparison.stats,
+            crate::diff::DiffStats {
+                files_changed: 2,
+                additions: 2,
+                deletions: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn blame_hunks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let first = commit(&repo, &[], "first", &[("src/a.txt", Some("a\nb\n"))]);
+        let second = commit(
+            &repo,
+            &[first],
+            "second",
+            &[("src/a.txt", Some("a\nb\nc\nd\ne\n"))],
+        );
+        let third = commit(
+            &repo,
+            &[second],
+            "third",
+            &[("src/a.txt", Some("a\nb\nC\nd\ne\n"))],
+        );
+        let cache = BlameCache::default();
+
+        let blame = Blame::compute(&repo, &cache, third, "src/a.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(blame.path, "src/a.txt");
+        assert_eq!(blame.commit, radicle_git_ext::Oid::from(third));
+        assert_eq!(
+            blame
+                .hunks
+                .iter()
+                .map(|hunk| (hunk.start_line, hunk.lines, hunk.commit.summary.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, "first"), (3, 1, "third"), (4, 2, "second")]
+        );
+
+        // Lines changed after the blamed commit are not considered.
+        let blame = Blame::compute(&repo, &cache, second, "src/a.txt")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            blame
+                .hunks
+                .iter()
+                .map(|hunk| (hunk.start_line, hunk.lines, hunk.commit.summary.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, 2, "first"), (3, 3, "second")]
+        );
+
+        assert!(Blame::compute(&repo, &cache, third, "src/missing.txt")
+            .unwrap()
+            .is_none());
+        assert!(Blame::compute(&repo, &cache, third, "src")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn blame_cache_hits() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let first = commit(
+            &repo,
+            &[],
+            "first",
+            &[("a.txt", Some("a\n")), ("b.txt", Some("a\n"))],
+        );
+        let second = commit(&repo, &[first], "second", &[("c.txt", Some("c\n"))]);
+        let cache = BlameCache::default();
+
+        let blame = Blame::compute(&repo, &cache, second, "a.txt")
+            .unwrap()
+            .unwrap();
+        let cached = Blame::compute(&repo, &cache, second, "a.txt")
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&blame, &cached));
+
+        // Clones share the cache.
+        let cached = Blame::compute(&repo, &cache.clone(), second, "a.txt")
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&blame, &cached));
+
+        // The same blob at another path or commit is a different entry.
+        let other_path = Blame::compute(&repo, &cache, second, "b.txt")
+            .unwrap()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&blame, &other_path));
+        assert_eq!(other_path.path, "b.txt");
+        let other_commit = Blame::compute(&repo, &cache, first, "a.txt")
+            .unwrap()
+            .unwrap();
+        assert!(!Arc::ptr_eq(&blame, &other_commit));
+        assert_eq!(other_commit.commit, radicle_git_ext::Oid::from(first));
+    }
+
+    #[test]
+    fn blame_cache_evicts_least_recently_used() {
+        let key = |index: usize| BlameKey {
+            blob: git2::Oid::zero(),
+            commit: git2::Oid::hash_object(git2::ObjectType::Blob, index.to_string().as_bytes())
+                .unwrap(),
+            path: "a.txt".to_string(),
+        };
+        let blame = || {
+            Arc::new(Blame {
+                path: "a.txt".to_string(),
+                commit: git2::Oid::zero().into(),
+                hunks: vec![],
+            })
+        };
+        let cache = BlameCache::default();
+
+        for index in 0..BLAME_CACHE_SIZE {
+            cache.insert(key(index), blame());
+        }
+        // Using the oldest entry makes the second oldest entry the least recently used.
+        assert!(cache.get(&key(0)).is_some());
+        cache.insert(key(BLAME_CACHE_SIZE), blame());
+
+        assert!(cache.get(&key(0)).is_some());
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(2)).is_some());
+        assert!(cache.get(&key(BLAME_CACHE_SIZE)).is_some());
+    }
+
+    /// Create a commit with a regular file, an executable, a symbolic link and a file in a
+    /// subdirectory.
+    fn archive_commit(repo: &git2::Repository) -> git2::Oid {
+        let tree = write_tree(
+            repo,
+            &[
+                ("README.md", "readme\n", FILE_MODE_BLOB),
+                ("bin/run.sh", "#!/bin/sh\n", FILE_MODE_EXECUTABLE),
+                ("link", "README.md", FILE_MODE_LINK),
+                ("src/lib.rs", "lib\n", FILE_MODE_BLOB),
+            ],
+        );
+        commit_tree(repo, &[], tree, "initial")
+    }
+
+    #[test]
+    fn archive_tar_gz() {
+        use std::io::Read as _;
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit = archive_commit(&repo);
+
+        let mut content = vec![];
+        archive(
+            &repo,
+            commit,
+            ArchiveFormat::TarGz,
+            "project-abc",
+            &mut content,
+        )
+        .unwrap();
+
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(&content[..]));
+        let entries = tar
+            .entries()
+            .unwrap()
+            .map(|entry_result| {
+                let mut entry = entry_result.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().to_string();
+                let mode = entry.header().mode().unwrap();
+                let link_name = entry
+                    .link_name()
+                    .unwrap()
+                    .map(|link_name| link_name.to_string_lossy().to_string());
+                let mut data = String::new();
+                entry.read_to_string(&mut data).unwrap();
+                (path, mode, link_name, data)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "project-abc/README.md".to_string(),
+                    0o644,
+                    None,
+                    "readme\n".to_string()
+                ),
+                (
+                    "project-abc/bin/run.sh".to_string(),
+                    0o755,
+                    None,
+                    "#!/bin/sh\n".to_string()
+                ),
+                (
+                    "project-abc/link".to_string(),
+                    0o777,
+                    Some("README.md".to_string()),
+                    String::new()
+                ),
+                (
+                    "project-abc/src/lib.rs".to_string(),
+                    0o644,
+                    None,
+                    "lib\n".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn archive_zip() {
+        use std::io::Read as _;
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit = archive_commit(&repo);
+
+        let mut content = vec![];
+        archive(
+            &repo,
+            commit,
+            ArchiveFormat::Zip,
+            "project-abc",
+            &mut content,
+        )
+        .unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(content)).unwrap();
+        let entries = (0..zip.len())
+            .map(|index| {
+                let mut file = zip.by_index(index).unwrap();
+                let name = file.name().to_string();
+                let mode = file.unix_mode().unwrap();
+                let mut data = String::new();
+                file.read_to_string(&mut data).unwrap();
+                (name, mode, data)
+            })
+            .collect::<Vec<_>>();
+
+        // The content of a symbolic link is its target.
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "project-abc/README.md".to_string(),
+                    0o100_644,
+                    "readme\n".to_string()
+                ),
+                (
+                    "project-abc/bin/run.sh".to_string(),
+                    0o100_755,
+                    "#!/bin/sh\n".to_string()
+                ),
+                (
+                    "project-abc/link".to_string(),
+                    0o120_777,
+                    "README.md".to_string()
+                ),
+                (
+                    "project-abc/src/lib.rs".to_string(),
+                    0o100_644,
+                    "lib\n".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_blob() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit = archive_commit(&repo);
+
+        let blob = RawBlob::find(&repo, commit, "README.md").unwrap().unwrap();
+        assert_eq!(blob.size, 7);
+        assert_eq!(blob.content_type, "text/markdown");
+        let mut content = vec![];
+        blob.write(&repo, &mut content).unwrap();
+        assert_eq!(content, b"readme\n");
+
+        let blob = RawBlob::find(&repo, commit, "bin/run.sh").unwrap().unwrap();
+        assert_eq!(blob.content_type, "application/x-sh");
+
+        assert!(RawBlob::find(&repo, commit, "src").unwrap().is_none());
+        assert!(RawBlob::find(&repo, commit, "missing").unwrap().is_none());
+    }
+
+    /// Returns the path and line number of every line match in `results`.
+    fn line_matches(results: &SearchResults) -> Vec<(&str, usize)> {
+        results
+            .lines
+            .iter()
+            .map(|line| (line.path.as_str(), line.line_number))
+            .collect()
+    }
+
+    #[test]
+    fn search_lines_with_context() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit = commit(
+            &repo,
+            &[],
+            "initial",
+            &[
+                ("README.md", Some("needle\nend\n")),
+                ("lib/needle.rs", Some("nothing\n")),
+                (
+                    "src/main.rs",
+                    Some("one\ntwo\nNeedle three\nfour\nfive\nsix\n"),
+                ),
+            ],
+        );
+
+        let results = SearchResults::search(&repo, commit, "NEEDLE", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            results
+                .files
+                .iter()
+                .map(|file| file.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["lib/needle.rs"]
+        );
+        assert_eq!(
+            line_matches(&results),
+            vec![("README.md", 1), ("src/main.rs", 3)]
+        );
+        assert!(results.lines[0].context_before.is_empty());
+        assert_eq!(results.lines[0].context_after, vec!["end"]);
+        assert_eq!(results.lines[1].line, "Needle three");
+        assert_eq!(results.lines[1].context_before, vec!["one", "two"]);
+        assert_eq!(results.lines[1].context_after, vec!["four", "five"]);
+        assert!(!results.truncated);
+    }
+
+    #[test]
+    fn search_path_prefix() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit = commit(
+            &repo,
+            &[],
+            "initial",
+            &[
+                ("README.md", Some("needle\n")),
+                ("src/needle.rs", Some("needle\n")),
+                ("src/http/mod.rs", Some("needle\n")),
+            ],
+        );
+
+        let results = SearchResults::search(&repo, commit, "needle", Some("/src/"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            line_matches(&results),
+            vec![("src/http/mod.rs", 1), ("src/needle.rs", 1)]
+        );
+        assert_eq!(
+            results
+                .files
+                .iter()
+                .map(|file| file.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["src/needle.rs"]
+        );
+
+        // An empty prefix searches the whole tree.
+        let results = SearchResults::search(&repo, commit, "needle", Some("/"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.lines.len(), 3);
+
+        assert!(
+            SearchResults::search(&repo, commit, "needle", Some("missing"))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            SearchResults::search(&repo, commit, "needle", Some("README.md"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn search_skips_binary_and_large_blobs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let large = "needle\n".repeat(MAX_SEARCH_BLOB_SIZE / "needle\n".len() + 1);
+        let commit = commit(
+            &repo,
+            &[],
+            "initial",
+            &[
+                ("binary", Some("needle\n\0")),
+                ("large", Some(large.as_str())),
+                ("small", Some("needle\n")),
+            ],
+        );
+
+        let results = SearchResults::search(&repo, commit, "needle", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(line_matches(&results), vec![("small", 1)]);
+    }
+
+    #[test]
+    fn search_truncates_results() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let limit = "needle\n".repeat(MAX_SEARCH_RESULTS);
+        let over_limit = "needle\n".repeat(MAX_SEARCH_RESULTS + 1);
+
+        let commit_at_limit = commit(&repo, &[], "at limit", &[("a", Some(limit.as_str()))]);
+        let results = SearchResults::search(&repo, commit_at_limit, "needle", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.lines.len(), MAX_SEARCH_RESULTS);
+        assert!(!results.truncated);
+
+        let commit_over_limit = commit(
+            &repo,
+            &[commit_at_limit],
+            "over limit",
+            &[("b", Some("needle\n"))],
+        );
+        let results = SearchResults::search(&repo, commit_over_limit, "needle", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.lines.len(), MAX_SEARCH_RESULTS);
+        assert!(results.lines.iter().all(|line| line.path == "a"));
+        assert!(results.truncated);
+
+        let commit_over_limit = commit(
+            &repo,
+            &[],
+            "over limit",
+            &[("a", Some(over_limit.as_str()))],
+        );
+        let results = SearchResults::search(&repo, commit_over_limit, "needle", None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(results.lines.len(), MAX_SEARCH_RESULTS);
+        assert!(results.truncated);
+    }
+
+    #[test]
+    fn fuzzy_score_requires_all_characters_in_order() {
+        assert!(fuzzy_score("src/main.rs", "mainrs").is_some());
+        assert!(fuzzy_score("src/main.rs", "SRCMAIN").is_some());
+        assert_eq!(fuzzy_score("src/main.rs", "mian"), None);
+        assert_eq!(fuzzy_score("src/main.rs", "lib"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_file_names_and_consecutive_characters() {
+        let file_name = fuzzy_score("src/http/source.rs", "source").unwrap();
+        let directory = fuzzy_score("source/http/mod.rs", "source").unwrap();
+        let scattered = fuzzy_score("src/other/user_ce.rs", "source").unwrap();
+        assert!(file_name > directory, "{file_name} <= {directory}");
+        assert!(directory > scattered, "{directory} <= {scattered}");
+    }
+}
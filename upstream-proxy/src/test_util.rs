@@ -0,0 +1,98 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Helpers for tests that create Git objects.
+
+/// Git file mode of regular blobs.
+pub const FILE_MODE_BLOB: i32 = 0o100_644;
+
+/// Write a tree with the `(path, content, mode)` entries to `repo`.
+pub fn write_tree(repo: &git2::Repository, entries: &[(&str, &str, i32)]) -> git2::Oid {
+    let mut index = git2::Index::new().unwrap();
+    for (path, content, mode) in entries {
+        let id = repo.blob(content.as_bytes()).unwrap();
+        index
+            .add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: *mode as u32,
+                uid: 0,
+                gid: 0,
+                file_size: content.len() as u32,
+                id,
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            })
+            .unwrap();
+    }
+    index.write_tree_to(repo).unwrap()
+}
+
+/// Create a commit with `parents` that applies `changes` to the tree of the first parent. A change
+/// with content `None` deletes the file. Files are regular blobs.
+///
+/// Commit times increase with the number of ancestors so that histories are ordered
+/// deterministically.
+pub fn commit(
+    repo: &git2::Repository,
+    parents: &[git2::Oid],
+    message: &str,
+    changes: &[(&str, Option<&str>)],
+) -> git2::Oid {
+    let base_tree = match parents.first() {
+        Some(parent) => repo.find_commit(*parent).unwrap().tree().unwrap(),
+        None => repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap(),
+    };
+    let mut update = git2::build::TreeUpdateBuilder::new();
+    for (path, content) in changes {
+        match content {
+            Some(content) => {
+                let blob = repo.blob(content.as_bytes()).unwrap();
+                update.upsert(*path, blob, git2::FileMode::Blob);
+            },
+            None => {
+                update.remove(*path);
+            },
+        }
+    }
+    let tree = update.create_updated(repo, &base_tree).unwrap();
+    commit_tree(repo, parents, tree, message)
+}
+
+/// Create a commit of `tree` with `parents`. See [`commit`] for the commit times.
+pub fn commit_tree(
+    repo: &git2::Repository,
+    parents: &[git2::Oid],
+    tree: git2::Oid,
+    message: &str,
+) -> git2::Oid {
+    let parents = parents
+        .iter()
+        .map(|parent| repo.find_commit(*parent).unwrap())
+        .collect::<Vec<_>>();
+    let time = parents
+        .iter()
+        .map(|parent| parent.time().seconds() + 60)
+        .max()
+        .unwrap_or(1_600_000_000);
+    let signature =
+        git2::Signature::new("Alice", "alice@example.com", &git2::Time::new(time, 0)).unwrap();
+    let tree = repo.find_tree(tree).unwrap();
+    repo.commit(
+        None,
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
@@ -5,10 +5,28 @@
 // LICENSE file.
 
 //! Service for watching the local monorepo for updates.
+//!
+//! We watch the `refs` directory and the `packed-refs` file of the monorepo for filesystem
+//! changes. Changes are debounced and only the namespaces of the changed references are scanned.
+//! A change to `packed-refs` results in a scan of all namespaces. If the filesystem watcher cannot
+//! be set up we fall back to scanning all namespaces at a fixed interval.
+//!
+//! Only the references of the local peer in project namespaces are considered. Remote references
+//! are ignored.
 
-use anyhow::Context;
-use link_identities::git::Revision;
-use std::collections::HashSet;
+use anyhow::Context as _;
+use radicle_git_ext::Oid;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Time without further filesystem events after which we scan for changed references.
+const DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Maximum time we wait for filesystem events to settle before we scan for changed references.
+const MAX_DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Interval at which all namespaces are scanned if filesystem notifications are not available.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 pub fn create(peer: crate::peer::Peer) -> (Handle, Runner) {
     let (update_tx, update_rx) = async_broadcast::broadcast(32);
@@ -20,107 +38,596 @@ pub fn create(peer: crate::peer::Peer) -> (Handle, Runner) {
     (handle, runner)
 }
 
+/// Changes to the references of the local peer in a project namespace.
+#[derive(Debug, Clone)]
+pub struct Update {
+    /// URN of the project without a path.
+    pub urn: link_identities::Urn<link_identities::git::Revision>,
+    pub changes: Vec<RefChange>,
+}
+
+/// Change of a reference in the monorepo.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefChange {
+    /// Name of the reference without the namespace prefix, for example `refs/heads/main` or
+    /// `refs/remotes/<peer>/heads/main`.
+    #[serde(rename = "ref")]
+    pub name: String,
+    /// Target of the reference before the change. `None` if the reference was created or if
+    /// `old_unknown` is set.
+    pub old: Option<Oid>,
+    /// `true` if the target of the reference before the change is not known. This is the case for
+    /// references fetched because of a gossip message.
+    pub old_unknown: bool,
+    /// Target of the reference after the change. `None` if the reference was deleted.
+    pub new: Option<Oid>,
+}
+
 #[derive(Clone)]
 pub struct Handle {
-    update_rx: async_broadcast::InactiveReceiver<link_identities::Urn<Revision>>,
+    update_rx: async_broadcast::InactiveReceiver<Update>,
 }
 
 impl Handle {
-    pub fn updates(&self) -> async_broadcast::Receiver<link_identities::Urn<Revision>> {
+    pub fn updates(&self) -> async_broadcast::Receiver<Update> {
         self.update_rx.activate_cloned()
     }
 }
 
 pub struct Runner {
     peer: crate::peer::Peer,
-    update_tx: async_broadcast::Sender<link_identities::Urn<Revision>>,
+    update_tx: async_broadcast::Sender<Update>,
 }
 
 impl Runner {
     pub async fn run(self) {
         let Self { peer, update_tx } = self;
-
-        let mut old = match get_refs(&peer).await {
-            Ok(list) => list,
+        // Filesystem events report canonical paths (for example `/private/var/...` on macOS). We
+        // canonicalize the git directory so that we can strip it from the event paths.
+        let git_dir = peer.paths().git_dir().to_owned();
+        let git_dir = match std::fs::canonicalize(&git_dir) {
+            Ok(canonical_git_dir) => canonical_git_dir,
             Err(err) => {
-                tracing::warn!(?err, "could not get the refs for the initial project list");
-                HashSet::new()
+                tracing::warn!(?err, ?git_dir, "failed to canonicalize monorepo path");
+                git_dir
             },
         };
 
-        loop {
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let mut state = State {
+            peer,
+            update_tx,
+            refs: HashMap::new(),
+            projects: HashMap::new(),
+        };
 
-            let new = match get_refs(&peer).await {
-                Ok(new_list) => new_list,
-                Err(err) => {
-                    tracing::warn!(?err, "could not get the refs for the new project list");
-                    continue;
-                },
-            };
+        // We start watching before the initial scan so that changes made during the scan are not
+        // missed.
+        let (fs_event_tx, mut fs_event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let watcher_result = watch(&git_dir, move |event_result| {
+            // Sending only fails if the receiver has been dropped when we’re shutting down.
+            let _ = fs_event_tx.send(event_result);
+        });
+
+        if let Err(err) = state.scan(Scope::All, false).await {
+            tracing::warn!(?err, "could not get the refs for the initial project list");
+        }
 
-            let updates = new.symmetric_difference(&old);
-            let deduped_updates = updates.map(|(urn, _)| urn).collect::<HashSet<_>>();
-
-            for urn in deduped_updates {
-                let result = update_tx.try_broadcast(urn.clone());
-                match result {
-                    Err(err) if !err.is_disconnected() => {
-                        tracing::warn!(?err, "failed to broadcast")
-                    },
-                    _ => {},
-                };
+        let _watcher = match watcher_result {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    "failed to watch monorepo for changes, falling back to polling"
+                );
+                state.poll().await;
+                return;
+            },
+        };
+
+        while let Some(event_result) = fs_event_rx.recv().await {
+            let mut scope = Scope::Namespaces(HashSet::new());
+            scope.add_event(&git_dir, event_result);
+            // We scan at the latest after `MAX_DEBOUNCE` even if events keep arriving.
+            let deadline = tokio::time::Instant::now() + MAX_DEBOUNCE;
+            loop {
+                let timeout = std::cmp::min(tokio::time::Instant::now() + DEBOUNCE, deadline);
+                match tokio::time::timeout_at(timeout, fs_event_rx.recv()).await {
+                    Ok(Some(event_result)) => scope.add_event(&git_dir, event_result),
+                    Ok(None) | Err(_) => break,
+                }
             }
 
-            old = new;
+            if let Err(err) = state.scan(scope, true).await {
+                tracing::warn!(?err, "could not get the refs for the updated projects");
+            }
         }
+
+        tracing::warn!("monorepo watcher stopped, falling back to polling");
+        state.poll().await;
     }
 }
 
-async fn get_refs(
-    peer: &crate::peer::Peer,
-) -> Result<HashSet<(librad::git::Urn, radicle_git_ext::Oid)>, anyhow::Error> {
-    let peer = peer.librad_peer();
+/// Watch the references of the monorepo at `git_dir` and call `handler` for every filesystem
+/// event. The returned watcher must be kept alive.
+fn watch(
+    git_dir: &std::path::Path,
+    handler: impl notify::EventHandler,
+) -> anyhow::Result<notify::RecommendedWatcher> {
+    use notify::Watcher as _;
 
-    let identities = peer
-        .using_storage(move |store| {
-            let identities = librad::git::identities::any::list(store)?
-                .filter_map(Result::ok)
-                .collect::<Vec<_>>();
-            Ok::<_, anyhow::Error>(identities)
-        })
-        .await
-        .context("failed to use storage")?
-        .context("failed to list identities")?;
+    let mut watcher =
+        notify::recommended_watcher(handler).context("failed to create filesystem watcher")?;
+    watcher
+        .watch(&git_dir.join("refs"), notify::RecursiveMode::Recursive)
+        .context("failed to watch refs directory")?;
+    // `packed-refs` is replaced when it is written so we watch the directory that contains it.
+    watcher
+        .watch(git_dir, notify::RecursiveMode::NonRecursive)
+        .context("failed to watch git directory")?;
+    Ok(watcher)
+}
 
-    let mut updates = HashSet::new();
+/// Namespaces that need to be scanned for changed references.
+enum Scope {
+    All,
+    /// Encoded identifiers of namespaces.
+    Namespaces(HashSet<String>),
+}
 
-    for identity in identities {
-        let urn = match identity {
-            link_identities::SomeIdentity::Project(project) => project.urn(),
-            _ => continue,
+impl Scope {
+    /// Extend the scope with the namespaces affected by a filesystem event.
+    fn add_event(
+        &mut self,
+        git_dir: &std::path::Path,
+        event_result: notify::Result<notify::Event>,
+    ) {
+        let namespaces = match self {
+            Self::All => return,
+            Self::Namespaces(namespaces) => namespaces,
         };
 
-        let refs = peer
+        let event = match event_result {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!(?err, "error while watching monorepo");
+                *self = Self::All;
+                return;
+            },
+        };
+        if event.need_rescan() {
+            *self = Self::All;
+            return;
+        }
+
+        for path in &event.paths {
+            let relative_path = match path.strip_prefix(git_dir) {
+                Ok(relative_path) => relative_path,
+                Err(_) => continue,
+            };
+            let mut components = relative_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy());
+            match components.next().as_deref() {
+                Some("packed-refs" | "packed-refs.lock") => {
+                    *self = Self::All;
+                    return;
+                },
+                Some("refs") => {},
+                _ => continue,
+            }
+            if components.next().as_deref() != Some("namespaces") {
+                continue;
+            }
+            if let Some(namespace) = components.next() {
+                namespaces.insert(namespace.into_owned());
+            }
+        }
+    }
+}
+
+struct State {
+    peer: crate::peer::Peer,
+    update_tx: async_broadcast::Sender<Update>,
+    /// References of the local peer for every project namespace.
+    refs: HashMap<String, BTreeMap<String, Oid>>,
+    /// Whether a namespace holds a project identity.
+    projects: HashMap<String, bool>,
+}
+
+impl State {
+    /// Scan all namespaces at [`POLL_INTERVAL`].
+    async fn poll(&mut self) {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if let Err(err) = self.scan(Scope::All, true).await {
+                tracing::warn!(?err, "could not get the refs for the new project list");
+            }
+        }
+    }
+
+    /// Load the references of the namespaces in `scope` and update our state. If `notify` is
+    /// `true`, an [`Update`] is broadcast for every project with changed references.
+    async fn scan(&mut self, scope: Scope, notify: bool) -> anyhow::Result<()> {
+        let scanned = match scope {
+            Scope::All => {
+                let all = self
+                    .peer
+                    .monorepo_unblock(|repo| {
+                        let mut all = HashMap::<String, BTreeMap<String, Oid>>::new();
+                        for reference_result in repo
+                            .references_glob("refs/namespaces/*")
+                            .context("failed to list references")?
+                        {
+                            let reference = reference_result.context("failed to get reference")?;
+                            if let Some((namespace, name, target)) = local_ref(&reference) {
+                                all.entry(namespace).or_default().insert(name, target);
+                            }
+                        }
+                        Ok(all)
+                    })
+                    .await?;
+                let removed = self
+                    .refs
+                    .keys()
+                    .filter(|namespace| !all.contains_key(*namespace))
+                    .map(|namespace| (namespace.clone(), BTreeMap::new()))
+                    .collect::<Vec<_>>();
+                all.into_iter().chain(removed).collect::<Vec<_>>()
+            },
+            Scope::Namespaces(namespaces) => {
+                let mut scanned = vec![];
+                for namespace in namespaces {
+                    let refs = self
+                        .peer
+                        .monorepo_unblock({
+                            let namespace = namespace.clone();
+                            move |repo| namespace_refs(&repo, &namespace, false)
+                        })
+                        .await?;
+                    scanned.push((namespace, refs));
+                }
+                scanned
+            },
+        };
+
+        for (namespace, refs) in scanned {
+            let before = self.refs.get(&namespace).cloned().unwrap_or_default();
+            let changes = ref_changes(&before, &refs);
+            if refs.is_empty() {
+                self.refs.remove(&namespace);
+            } else {
+                self.refs.insert(namespace.clone(), refs);
+            }
+
+            if !notify || changes.is_empty() {
+                continue;
+            }
+
+            let urn = match format!("rad:git:{namespace}").parse::<link_identities::Urn<_>>() {
+                Ok(urn) => urn,
+                Err(err) => {
+                    tracing::warn!(?err, %namespace, "invalid namespace in monorepo");
+                    continue;
+                },
+            };
+            if !self.is_project(&namespace, &urn).await {
+                continue;
+            }
+
+            let result = self.update_tx.try_broadcast(Update { urn, changes });
+            match result {
+                Err(err) if !err.is_disconnected() => {
+                    tracing::warn!(?err, "failed to broadcast")
+                },
+                _ => {},
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if `namespace` holds a project identity. Results are cached.
+    async fn is_project(
+        &mut self,
+        namespace: &str,
+        urn: &link_identities::Urn<link_identities::git::Revision>,
+    ) -> bool {
+        if let Some(is_project) = self.projects.get(namespace) {
+            return *is_project;
+        }
+
+        let result = self
+            .peer
+            .librad_peer()
             .using_storage({
                 let urn = urn.clone();
-                move |store| librad::git::refs::Refs::load(store, &urn, None)
+                move |storage| librad::git::identities::any::get(storage, &urn)
             })
-            .await
-            .context("failed to use storage")?
-            .context("failed to load refs")?;
+            .await;
+        match result {
+            Ok(Ok(Some(identity))) => {
+                let is_project = matches!(identity, link_identities::SomeIdentity::Project(_));
+                self.projects.insert(namespace.to_string(), is_project);
+                is_project
+            },
+            // The identity may not be complete yet. We try again on the next change.
+            Ok(Ok(None)) => false,
+            Ok(Err(err)) => {
+                tracing::warn!(?err, %urn, "failed to get identity");
+                false
+            },
+            Err(err) => {
+                tracing::warn!(?err, "failed to use storage");
+                false
+            },
+        }
+    }
+}
 
-        let refs = match refs {
-            Some(refs) => refs,
+/// Returns the namespace, name without namespace prefix and target of `reference` if it is a
+/// direct reference of the local peer in a namespace.
+fn local_ref(reference: &git2::Reference) -> Option<(String, String, Oid)> {
+    let rest = reference.name()?.strip_prefix("refs/namespaces/")?;
+    let (namespace, name) = rest.split_once('/')?;
+    if name.starts_with("refs/remotes/") {
+        return None;
+    }
+    Some((
+        namespace.to_string(),
+        name.to_string(),
+        reference.target()?.into(),
+    ))
+}
+
+/// Returns the targets of all direct references in the namespace with the encoded identifier
+/// `namespace`, keyed by their name without the namespace prefix. References of remote peers are
+/// only included if `include_remotes` is `true`.
+pub fn namespace_refs(
+    repo: &git2::Repository,
+    namespace: &str,
+    include_remotes: bool,
+) -> anyhow::Result<BTreeMap<String, Oid>> {
+    let prefix = format!("refs/namespaces/{namespace}/");
+    let mut refs = BTreeMap::new();
+    for reference_result in repo
+        .references_glob(&format!("{prefix}*"))
+        .context("failed to list references")?
+    {
+        let reference = reference_result.context("failed to get reference")?;
+        let name = match reference.name().and_then(|name| name.strip_prefix(&prefix)) {
+            Some(name) => name,
             None => continue,
         };
+        if !include_remotes && name.starts_with("refs/remotes/") {
+            continue;
+        }
+        if let Some(target) = reference.target() {
+            refs.insert(name.to_string(), target.into());
+        }
+    }
+    Ok(refs)
+}
 
-        for ((one_level, oid), category) in refs.iter_categorised() {
-            let path =
-                radicle_git_ext::RefLike::from(one_level.clone().into_qualified(category.into()));
-            let urn = urn.clone().with_path(path);
-            updates.insert((urn, *oid));
+/// Returns the changes from the references `before` to the references `after`, ordered by
+/// reference name.
+pub fn ref_changes(
+    before: &BTreeMap<String, Oid>,
+    after: &BTreeMap<String, Oid>,
+) -> Vec<RefChange> {
+    let mut changes = vec![];
+    for (name, old) in before {
+        match after.get(name) {
+            Some(new) if new == old => {},
+            new => changes.push(RefChange {
+                name: name.clone(),
+                old: Some(*old),
+                old_unknown: false,
+                new: new.copied(),
+            }),
+        }
+    }
+    for (name, new) in after {
+        if !before.contains_key(name) {
+            changes.push(RefChange {
+                name: name.clone(),
+                old: None,
+                old_unknown: false,
+                new: Some(*new),
+            });
         }
     }
-    Ok(updates)
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn oid(content: &str) -> Oid {
+        git2::Oid::hash_object(git2::ObjectType::Blob, content.as_bytes())
+            .unwrap()
+            .into()
+    }
+
+    fn refs(entries: &[(&str, &str)]) -> BTreeMap<String, Oid> {
+        entries
+            .iter()
+            .map(|(name, content)| (name.to_string(), oid(content)))
+            .collect()
+    }
+
+    fn event(paths: &[std::path::PathBuf]) -> notify::Result<notify::Event> {
+        let mut event = notify::Event::new(notify::EventKind::Any);
+        for path in paths {
+            event = event.add_path(path.clone());
+        }
+        Ok(event)
+    }
+
+    fn namespaces(scope: &Scope) -> Option<Vec<String>> {
+        match scope {
+            Scope::All => None,
+            Scope::Namespaces(namespaces) => {
+                let mut namespaces = namespaces.iter().cloned().collect::<Vec<_>>();
+                namespaces.sort();
+                Some(namespaces)
+            },
+        }
+    }
+
+    #[test]
+    fn ref_changes_created_updated_deleted() {
+        let before = refs(&[
+            ("refs/heads/main", "1"),
+            ("refs/heads/old", "2"),
+            ("refs/tags/v1", "3"),
+        ]);
+        let after = refs(&[
+            ("refs/heads/main", "4"),
+            ("refs/heads/new", "5"),
+            ("refs/tags/v1", "3"),
+        ]);
+
+        assert_eq!(
+            ref_changes(&before, &after),
+            vec![
+                RefChange {
+                    name: "refs/heads/main".to_string(),
+                    old: Some(oid("1")),
+                    old_unknown: false,
+                    new: Some(oid("4")),
+                },
+                RefChange {
+                    name: "refs/heads/new".to_string(),
+                    old: None,
+                    old_unknown: false,
+                    new: Some(oid("5")),
+                },
+                RefChange {
+                    name: "refs/heads/old".to_string(),
+                    old: Some(oid("2")),
+                    old_unknown: false,
+                    new: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ref_changes_unchanged() {
+        let refs = refs(&[("refs/heads/main", "1")]);
+        assert_eq!(ref_changes(&refs, &refs), vec![]);
+        assert_eq!(ref_changes(&BTreeMap::new(), &BTreeMap::new()), vec![]);
+    }
+
+    #[test]
+    fn local_ref_filters_references() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_bare(tmp_dir.path()).unwrap();
+        let commit_id = crate::test_util::commit(&repo, &[], "initial", &[]);
+
+        let local = repo
+            .reference("refs/namespaces/abc/refs/heads/main", commit_id, false, "")
+            .unwrap();
+        let remote = repo
+            .reference(
+                "refs/namespaces/abc/refs/remotes/peer/heads/main",
+                commit_id,
+                false,
+                "",
+            )
+            .unwrap();
+        let symbolic = repo
+            .reference_symbolic(
+                "refs/namespaces/abc/refs/heads/alias",
+                "refs/namespaces/abc/refs/heads/main",
+                false,
+                "",
+            )
+            .unwrap();
+        let outside = repo
+            .reference("refs/heads/main", commit_id, false, "")
+            .unwrap();
+
+        assert_eq!(
+            local_ref(&local),
+            Some((
+                "abc".to_string(),
+                "refs/heads/main".to_string(),
+                commit_id.into()
+            ))
+        );
+        assert_eq!(local_ref(&remote), None);
+        assert_eq!(local_ref(&symbolic), None);
+        assert_eq!(local_ref(&outside), None);
+    }
+
+    #[test]
+    fn add_event_extracts_namespaces() {
+        let git_dir = std::path::Path::new("/monorepo/git");
+        let mut scope = Scope::Namespaces(HashSet::new());
+
+        scope.add_event(
+            git_dir,
+            event(&[
+                git_dir.join("refs/namespaces/abc/refs/heads/main"),
+                git_dir.join("refs/namespaces/def/refs/remotes/peer/heads/main.lock"),
+            ]),
+        );
+        scope.add_event(
+            git_dir,
+            event(&[
+                git_dir.join("refs/namespaces/abc/refs/tags/v1"),
+                git_dir.join("refs/heads/main"),
+                git_dir.join("objects/ab/cdef"),
+                git_dir.join("HEAD"),
+                std::path::PathBuf::from("/elsewhere/refs/namespaces/ghi/refs/heads/main"),
+            ]),
+        );
+
+        assert_eq!(
+            namespaces(&scope),
+            Some(vec!["abc".to_string(), "def".to_string()])
+        );
+    }
+
+    #[test]
+    fn add_event_packed_refs_scans_all() {
+        let git_dir = std::path::Path::new("/monorepo/git");
+
+        for file_name in ["packed-refs", "packed-refs.lock"] {
+            let mut scope = Scope::Namespaces(HashSet::new());
+            scope.add_event(
+                git_dir,
+                event(&[git_dir.join("refs/namespaces/abc/refs/heads/main")]),
+            );
+            scope.add_event(git_dir, event(&[git_dir.join(file_name)]));
+            assert_eq!(namespaces(&scope), None);
+
+            // Once all namespaces are scanned further events do not narrow the scope.
+            scope.add_event(
+                git_dir,
+                event(&[git_dir.join("refs/namespaces/def/refs/heads/main")]),
+            );
+            assert_eq!(namespaces(&scope), None);
+        }
+    }
+
+    #[test]
+    fn add_event_error_or_rescan_scans_all() {
+        let git_dir = std::path::Path::new("/monorepo/git");
+
+        let mut scope = Scope::Namespaces(HashSet::new());
+        scope.add_event(git_dir, Err(notify::Error::generic("watch failed")));
+        assert_eq!(namespaces(&scope), None);
+
+        let mut scope = Scope::Namespaces(HashSet::new());
+        let rescan =
+            notify::Event::new(notify::EventKind::Other).set_flag(notify::event::Flag::Rescan);
+        scope.add_event(git_dir, Ok(rescan));
+        assert_eq!(namespaces(&scope), None);
+    }
 }
@@ -0,0 +1,772 @@
+// Copyright © 2022 The Radicle Upstream Contributors
+//
+// This file is part of radicle-upstream, distributed under the GPLv3
+// with Radicle Linking Exception. For full terms see the included
+// LICENSE file.
+
+//! Deliver notifications to webhooks.
+//!
+//! Webhooks are managed with [`Handle`] and persisted in the kv store. The [`Runner`] sends every
+//! [`Notification`] of the local peer and every new patch event as a JSON encoded `POST` request
+//! to all webhooks that subscribed to the type of the event. The `type` field of the payload
+//! holds the event type. Patch events have the type `patchEvent`.
+//!
+//! Every request carries the following headers:
+//!
+//! * `X-Radicle-Event`: type of the event
+//! * `X-Radicle-Delivery`: ID of the delivery
+//! * `X-Radicle-Signature-256`: `sha256=` followed by the hex-encoded HMAC-SHA256 of the request
+//!   body keyed with the secret of the webhook
+//!
+//! A patch event is delivered at most once to every webhook, even if several notifications report
+//! it. The commits of the delivered patch events are persisted for every webhook.
+//!
+//! Failed deliveries are retried with exponential backoff. The outcome of every delivery attempt
+//! is recorded in a delivery log. Only the most recent deliveries of each webhook are kept.
+//!
+//! Webhooks are delivered over plain HTTP only. They are intended for services on the local
+//! machine or network, for example a CI runner.
+
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use futures::prelude::*;
+use hmac::{Mac as _, NewMac as _};
+use serde::{Deserialize, Serialize};
+
+use link_crypto::PeerId;
+use link_identities::git::Urn;
+use radicle_git_ext::Oid;
+
+use crate::notification::Notification;
+
+/// Number of times a delivery is attempted before it is considered failed.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Delay before the first retry of a failed delivery. The delay doubles with every retry.
+const INITIAL_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Time after which a delivery attempt is aborted.
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Number of deliveries kept in the delivery log of every webhook.
+const MAX_DELIVERIES: usize = 50;
+
+/// Event type of the payload sent by [`Handle::test`].
+const PING_EVENT_TYPE: &str = "ping";
+
+/// Event type of payloads for new patch events.
+const PATCH_EVENT_TYPE: &str = "patchEvent";
+
+pub fn create(
+    event_log: crate::events::EventLog,
+    store: &kv::Store,
+) -> anyhow::Result<(Handle, Runner)> {
+    let handle = Handle::new(store)?;
+    let runner = Runner {
+        event_log,
+        handle: handle.clone(),
+        log_tips: HashMap::new(),
+    };
+    Ok((handle, runner))
+}
+
+/// A webhook without its secret.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Types of events that are delivered to the webhook. If empty, all events are delivered.
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredWebhook {
+    id: String,
+    url: String,
+    /// Key for signing payloads.
+    secret: String,
+    events: Vec<String>,
+}
+
+impl StoredWebhook {
+    fn info(&self) -> Webhook {
+        Webhook {
+            id: self.id.clone(),
+            url: self.url.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    fn subscribes_to(&self, event_type: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|event| event == event_type)
+    }
+}
+
+/// Delivery of an event to a webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event_type: String,
+    /// Time the delivery was created in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub status: DeliveryStatus,
+    /// Delivery attempts in chronological order.
+    pub attempts: Vec<DeliveryAttempt>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryStatus {
+    /// The delivery has not succeeded yet and will be retried.
+    Pending,
+    /// The webhook responded with a success status code.
+    Delivered,
+    /// All delivery attempts failed.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeliveryAttempt {
+    /// Time of the attempt in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// HTTP status code of the response. `None` if no response was received.
+    pub status_code: Option<u16>,
+    /// Error message if no response was received.
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Handle {
+    webhooks: kv::Bucket<'static, String, kv::Json<StoredWebhook>>,
+    deliveries: kv::Bucket<'static, String, kv::Json<Delivery>>,
+    /// Patch events that were delivered to a webhook. Keys are `<webhook id>/<event commit>`,
+    /// values are empty.
+    delivered_events: kv::Bucket<'static, String, String>,
+    client: hyper::Client<hyper::client::HttpConnector>,
+}
+
+impl Handle {
+    fn new(store: &kv::Store) -> anyhow::Result<Self> {
+        Ok(Self {
+            webhooks: store
+                .bucket(Some("webhooks"))
+                .context("failed to get webhooks bucket")?,
+            deliveries: store
+                .bucket(Some("webhook_deliveries"))
+                .context("failed to get webhook deliveries bucket")?,
+            delivered_events: store
+                .bucket(Some("webhook_delivered_events"))
+                .context("failed to get webhook delivered events bucket")?,
+            client: hyper::Client::new(),
+        })
+    }
+
+    /// Returns all webhooks ordered by URL.
+    pub fn list(&self) -> anyhow::Result<Vec<Webhook>> {
+        let mut webhooks = self
+            .stored_webhooks()?
+            .iter()
+            .map(StoredWebhook::info)
+            .collect::<Vec<_>>();
+        webhooks.sort_by(|a, b| a.url.cmp(&b.url).then(a.id.cmp(&b.id)));
+        Ok(webhooks)
+    }
+
+    /// Add a webhook that receives the `events`. If `secret` is `None`, a random secret is
+    /// generated. Returns the new webhook and its secret.
+    pub fn add(
+        &self,
+        url: url::Url,
+        secret: Option<String>,
+        events: Vec<String>,
+    ) -> anyhow::Result<(Webhook, String)> {
+        let secret = secret.unwrap_or_else(random_id);
+        let webhook = StoredWebhook {
+            id: random_id(),
+            url: url.to_string(),
+            secret: secret.clone(),
+            events,
+        };
+        self.webhooks
+            .set(webhook.id.clone(), kv::Json(webhook.clone()))
+            .context("failed to store webhook")?;
+        Ok((webhook.info(), secret))
+    }
+
+    /// Remove the webhook `id`, its delivery log and the record of its delivered patch events.
+    /// Returns `false` if there is no such webhook.
+    pub fn remove(&self, id: &str) -> anyhow::Result<bool> {
+        if self.stored_webhook(id)?.is_none() {
+            return Ok(false);
+        }
+        self.webhooks
+            .remove(id.to_string())
+            .context("failed to remove webhook")?;
+        for delivery in self.stored_deliveries(id)? {
+            self.deliveries
+                .remove(delivery.id)
+                .context("failed to remove webhook delivery")?;
+        }
+        let key_prefix = format!("{id}/");
+        for item_result in self.delivered_events.iter() {
+            let key = item_result
+                .context("failed to read delivered webhook event")?
+                .key::<String>()
+                .context("failed to parse delivered webhook event")?;
+            if key.starts_with(&key_prefix) {
+                self.delivered_events
+                    .remove(key)
+                    .context("failed to remove delivered webhook event")?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the delivery log of the webhook `id`, the most recent delivery first. Returns `None`
+    /// if there is no such webhook.
+    pub fn deliveries(&self, id: &str) -> anyhow::Result<Option<Vec<Delivery>>> {
+        if self.stored_webhook(id)?.is_none() {
+            return Ok(None);
+        }
+        self.stored_deliveries(id).map(Some)
+    }
+
+    /// Send a `ping` event to the webhook `id` once without retrying and return the recorded
+    /// delivery. Returns `None` if there is no such webhook.
+    pub async fn test(&self, id: &str) -> anyhow::Result<Option<Delivery>> {
+        let webhook = match self.stored_webhook(id)? {
+            Some(webhook) => webhook,
+            None => return Ok(None),
+        };
+        let payload = serde_json::json!({
+            "type": PING_EVENT_TYPE,
+            "webhookId": webhook.id,
+        });
+        let delivery = self.deliver_to(&webhook, &payload, 1).await?;
+        Ok(Some(delivery))
+    }
+
+    /// Deliver `payload` in the background to all webhooks that subscribed to the type of the
+    /// payload. A patch event is not delivered again to a webhook that it was delivered to
+    /// before.
+    fn deliver(&self, payload: serde_json::Value) -> anyhow::Result<()> {
+        let event_type = payload_type(&payload).to_string();
+        let event_commit = if event_type == PATCH_EVENT_TYPE {
+            payload.get("commit").and_then(serde_json::Value::as_str)
+        } else {
+            None
+        };
+        for webhook in self.stored_webhooks()? {
+            if !webhook.subscribes_to(&event_type) {
+                continue;
+            }
+            if let Some(commit) = event_commit {
+                if !self.mark_event_delivered(&webhook.id, commit)? {
+                    continue;
+                }
+            }
+            let handle = self.clone();
+            let payload = payload.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle.deliver_to(&webhook, &payload, MAX_ATTEMPTS).await {
+                    tracing::warn!(?err, webhook = %webhook.url, "failed to deliver webhook");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Record that the patch event `commit` is delivered to the webhook `webhook_id`. Returns
+    /// `false` if the event was delivered to the webhook before.
+    fn mark_event_delivered(&self, webhook_id: &str, commit: &str) -> anyhow::Result<bool> {
+        let key = format!("{webhook_id}/{commit}");
+        if self
+            .delivered_events
+            .get(key.clone())
+            .context("failed to read delivered webhook event")?
+            .is_some()
+        {
+            return Ok(false);
+        }
+        self.delivered_events
+            .set(key, String::new())
+            .context("failed to store delivered webhook event")?;
+        Ok(true)
+    }
+
+    /// Send `payload` to `webhook` until the webhook accepts it or `max_attempts` attempts have
+    /// been made. The delivery log is updated after every attempt.
+    async fn deliver_to(
+        &self,
+        webhook: &StoredWebhook,
+        payload: &serde_json::Value,
+        max_attempts: usize,
+    ) -> anyhow::Result<Delivery> {
+        let body = serde_json::to_vec(payload).context("failed to serialize payload")?;
+        let mut delivery = Delivery {
+            id: random_id(),
+            webhook_id: webhook.id.clone(),
+            event_type: payload_type(payload).to_string(),
+            timestamp: unix_timestamp_ms(),
+            status: DeliveryStatus::Pending,
+            attempts: vec![],
+        };
+
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+        loop {
+            let attempt = self.attempt(webhook, &delivery, &body).await;
+            let success = attempt
+                .status_code
+                .map_or(false, |status_code| (200..300).contains(&status_code));
+            delivery.attempts.push(attempt);
+            if success {
+                delivery.status = DeliveryStatus::Delivered;
+            } else if delivery.attempts.len() >= max_attempts {
+                delivery.status = DeliveryStatus::Failed;
+            }
+            self.record(&delivery)?;
+
+            if delivery.status != DeliveryStatus::Pending {
+                return Ok(delivery);
+            }
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        }
+    }
+
+    async fn attempt(
+        &self,
+        webhook: &StoredWebhook,
+        delivery: &Delivery,
+        body: &[u8],
+    ) -> DeliveryAttempt {
+        let timestamp = unix_timestamp_ms();
+        let request_result = http::Request::post(&webhook.url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header("X-Radicle-Event", &delivery.event_type)
+            .header("X-Radicle-Delivery", &delivery.id)
+            .header(
+                "X-Radicle-Signature-256",
+                format!("sha256={}", signature(&webhook.secret, body)),
+            )
+            .body(hyper::Body::from(body.to_vec()));
+        let request = match request_result {
+            Ok(request) => request,
+            Err(err) => {
+                return DeliveryAttempt {
+                    timestamp,
+                    status_code: None,
+                    error: Some(format!("invalid request: {err}")),
+                }
+            },
+        };
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, self.client.request(request)).await {
+            Ok(Ok(response)) => DeliveryAttempt {
+                timestamp,
+                status_code: Some(response.status().as_u16()),
+                error: None,
+            },
+            Ok(Err(err)) => DeliveryAttempt {
+                timestamp,
+                status_code: None,
+                error: Some(err.to_string()),
+            },
+            Err(_) => DeliveryAttempt {
+                timestamp,
+                status_code: None,
+                error: Some("request timed out".to_string()),
+            },
+        }
+    }
+
+    /// Store `delivery` in the delivery log and remove the oldest deliveries of the webhook that
+    /// exceed [`MAX_DELIVERIES`].
+    fn record(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        self.deliveries
+            .set(delivery.id.clone(), kv::Json(delivery.clone()))
+            .context("failed to store webhook delivery")?;
+        for outdated in self
+            .stored_deliveries(&delivery.webhook_id)?
+            .into_iter()
+            .skip(MAX_DELIVERIES)
+        {
+            self.deliveries
+                .remove(outdated.id)
+                .context("failed to remove webhook delivery")?;
+        }
+        Ok(())
+    }
+
+    fn stored_webhook(&self, id: &str) -> anyhow::Result<Option<StoredWebhook>> {
+        let webhook = self
+            .webhooks
+            .get(id.to_string())
+            .context("failed to read webhook")?;
+        Ok(webhook.map(|webhook| webhook.0))
+    }
+
+    fn stored_webhooks(&self) -> anyhow::Result<Vec<StoredWebhook>> {
+        self.webhooks
+            .iter()
+            .map(|item_result| {
+                let item = item_result.context("failed to read webhook")?;
+                let webhook = item
+                    .value::<kv::Json<StoredWebhook>>()
+                    .context("failed to parse webhook")?;
+                Ok(webhook.0)
+            })
+            .collect()
+    }
+
+    /// Returns the deliveries of the webhook `id`, the most recent delivery first.
+    fn stored_deliveries(&self, webhook_id: &str) -> anyhow::Result<Vec<Delivery>> {
+        let mut deliveries = vec![];
+        for item_result in self.deliveries.iter() {
+            let item = item_result.context("failed to read webhook delivery")?;
+            let delivery = item
+                .value::<kv::Json<Delivery>>()
+                .context("failed to parse webhook delivery")?
+                .0;
+            if delivery.webhook_id == webhook_id {
+                deliveries.push(delivery);
+            }
+        }
+        deliveries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.id.cmp(&b.id)));
+        Ok(deliveries)
+    }
+}
+
+pub struct Runner {
+    event_log: crate::events::EventLog,
+    handle: Handle,
+    /// Last known targets of the patch event logs, keyed by project, peer and reference name.
+    /// Used for changes whose previous target is unknown.
+    log_tips: HashMap<(Oid, PeerId, String), Oid>,
+}
+
+impl Runner {
+    /// Deliver `notifications` and the patch events derived from them until the stream ends.
+    pub async fn run(mut self, notifications: impl Stream<Item = Notification>) {
+        futures::pin_mut!(notifications);
+        while let Some(notification) = notifications.next().await {
+            let mut payloads = match self.patch_event_payloads(&notification).await {
+                Ok(payloads) => payloads,
+                Err(err) => {
+                    tracing::warn!(?err, "failed to get patch events for webhooks");
+                    vec![]
+                },
+            };
+            match serde_json::to_value(&notification) {
+                Ok(payload) => payloads.insert(0, payload),
+                Err(err) => tracing::error!(?err, "failed to serialize notification"),
+            }
+
+            for payload in payloads {
+                if let Err(err) = self.handle.deliver(payload) {
+                    tracing::error!(?err, "failed to deliver webhooks");
+                }
+            }
+        }
+    }
+
+    /// Returns a payload for every patch event that was added to an event log in a
+    /// [`Notification::RefsUpdated`], in chronological order.
+    ///
+    /// If the previous target of an event log is not known, for example for changes fetched
+    /// because of a gossip message, the last target seen by the runner is used instead. If the
+    /// runner has not seen the log before, the log is skipped instead of replaying all of its
+    /// events.
+    async fn patch_event_payloads(
+        &mut self,
+        notification: &Notification,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        let (urn, peer, changes) = match notification {
+            Notification::RefsUpdated { urn, peer, changes } => (urn, peer, changes),
+            _ => return Ok(vec![]),
+        };
+        let log_ref_prefix = format!("refs/{}/", crate::events::REF_PREFIX);
+
+        let mut payloads = vec![];
+        for change in changes {
+            let topic = match change.name.strip_prefix(&log_ref_prefix) {
+                Some(topic) => topic,
+                None => continue,
+            };
+            let (patch_peer, patch_id) = match topic
+                .strip_prefix("patch/")
+                .and_then(|patch| patch.split_once('/'))
+            {
+                Some(patch) => patch,
+                None => continue,
+            };
+            let new = match change.new {
+                Some(new) => new,
+                None => continue,
+            };
+            let log_key = (urn.id, *peer, change.name.clone());
+            let last_tip = self.log_tips.insert(log_key, new);
+            let old = if change.old_unknown {
+                match last_tip {
+                    Some(last_tip) => Some(last_tip),
+                    None => continue,
+                }
+            } else {
+                change.old
+            };
+            if old == Some(new) {
+                continue;
+            }
+
+            let mut new_envelopes = self
+                .event_log
+                .get(urn.id, topic.to_string())
+                .await
+                .context("failed to get patch events")?
+                .events
+                .into_iter()
+                .filter(|envelope| envelope.peer_id == *peer)
+                .take_while(|envelope| envelope.commit != old)
+                .collect::<Vec<_>>();
+            new_envelopes.reverse();
+
+            payloads.extend(new_envelopes.into_iter().map(|envelope| {
+                serde_json::json!({
+                    "type": PATCH_EVENT_TYPE,
+                    "urn": Urn::new(urn.id),
+                    "patchPeer": patch_peer,
+                    "patchId": patch_id,
+                    "peer": envelope.peer_id,
+                    "event": envelope.event,
+                    "timestamp": envelope.timestamp,
+                    "commit": envelope.commit,
+                })
+            }));
+        }
+
+        Ok(payloads)
+    }
+}
+
+/// Returns the hex-encoded HMAC-SHA256 of `body` keyed with `secret`.
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take keys of any size");
+    mac.update(body);
+    data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+/// Returns the `type` field of a payload.
+fn payload_type(payload: &serde_json::Value) -> &str {
+    payload
+        .get("type")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default()
+}
+
+fn random_id() -> String {
+    data_encoding::HEXLOWER.encode(&rand::random::<[u8; 16]>())
+}
+
+fn unix_timestamp_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("system time is before the Unix epoch")
+        .as_millis()
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    type ReceivedRequest = (http::HeaderMap, Vec<u8>);
+
+    /// Start an HTTP server that responds to the requests with `statuses` in turn. The last status
+    /// is used for all remaining requests. Returns the URL of the server and a channel that
+    /// receives the headers and bodies of the requests.
+    fn stub_server(
+        statuses: &[http::StatusCode],
+    ) -> (
+        url::Url,
+        tokio::sync::mpsc::UnboundedReceiver<ReceivedRequest>,
+    ) {
+        let (request_tx, request_rx) = tokio::sync::mpsc::unbounded_channel();
+        let statuses = std::sync::Arc::new(parking_lot::Mutex::new(
+            statuses
+                .iter()
+                .copied()
+                .collect::<std::collections::VecDeque<_>>(),
+        ));
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let request_tx = request_tx.clone();
+            let statuses = statuses.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                    move |request: hyper::Request<hyper::Body>| {
+                        let request_tx = request_tx.clone();
+                        let status = {
+                            let mut statuses = statuses.lock();
+                            if statuses.len() > 1 {
+                                statuses.pop_front().unwrap()
+                            } else {
+                                statuses[0]
+                            }
+                        };
+                        async move {
+                            let (parts, body) = request.into_parts();
+                            let body = hyper::body::to_bytes(body).await?;
+                            request_tx.send((parts.headers, body.to_vec())).unwrap();
+                            Ok::<_, hyper::Error>(
+                                hyper::Response::builder()
+                                    .status(status)
+                                    .body(hyper::Body::empty())
+                                    .unwrap(),
+                            )
+                        }
+                    },
+                ))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/hook", server.local_addr())
+            .parse()
+            .unwrap();
+        tokio::spawn(server);
+        (url, request_rx)
+    }
+
+    #[test]
+    fn signature_matches_known_value() {
+        assert_eq!(
+            signature("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::new(&store).unwrap();
+        let (url, mut requests) = stub_server(&[http::StatusCode::OK]);
+        let (webhook, secret) = handle.add(url, Some("secret".to_string()), vec![]).unwrap();
+
+        let delivery = handle.test(&webhook.id).await.unwrap().unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts.len(), 1);
+        assert_eq!(delivery.attempts[0].status_code, Some(200));
+
+        let (headers, body) = requests.recv().await.unwrap();
+        assert_eq!(headers["X-Radicle-Event"], PING_EVENT_TYPE);
+        assert_eq!(headers["X-Radicle-Delivery"], delivery.id.as_str());
+        assert_eq!(
+            headers["X-Radicle-Signature-256"],
+            format!("sha256={}", signature(&secret, &body)).as_str()
+        );
+        let payload = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(
+            payload,
+            serde_json::json!({ "type": PING_EVENT_TYPE, "webhookId": webhook.id })
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_logged() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::new(&store).unwrap();
+        let (url, _requests) = stub_server(&[http::StatusCode::INTERNAL_SERVER_ERROR]);
+        let (webhook, _) = handle.add(url, None, vec![]).unwrap();
+
+        let delivery = handle.test(&webhook.id).await.unwrap().unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.attempts[0].status_code, Some(500));
+
+        let deliveries = handle.deliveries(&webhook.id).unwrap().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].id, delivery.id);
+        assert_eq!(deliveries[0].status, DeliveryStatus::Failed);
+
+        assert!(handle.remove(&webhook.id).unwrap());
+        assert!(handle.deliveries(&webhook.id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried_with_backoff() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::new(&store).unwrap();
+        let (url, mut requests) = stub_server(&[
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            http::StatusCode::SERVICE_UNAVAILABLE,
+            http::StatusCode::OK,
+        ]);
+        let (webhook, _) = handle.add(url, None, vec![]).unwrap();
+        let webhook = handle.stored_webhook(&webhook.id).unwrap().unwrap();
+
+        let payload = serde_json::json!({ "type": "test" });
+        let delivery = handle
+            .deliver_to(&webhook, &payload, MAX_ATTEMPTS)
+            .await
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(
+            delivery
+                .attempts
+                .iter()
+                .map(|attempt| attempt.status_code)
+                .collect::<Vec<_>>(),
+            vec![Some(500), Some(503), Some(200)]
+        );
+        let delays = delivery
+            .attempts
+            .windows(2)
+            .map(|attempts| attempts[1].timestamp - attempts[0].timestamp)
+            .collect::<Vec<_>>();
+        let initial_retry_delay = INITIAL_RETRY_DELAY.as_millis() as u64;
+        assert!(delays[0] >= initial_retry_delay, "{delays:?}");
+        assert!(delays[1] >= 2 * initial_retry_delay, "{delays:?}");
+
+        // All attempts use the same delivery ID.
+        for _ in 0..3 {
+            let (headers, _) = requests.recv().await.unwrap();
+            assert_eq!(headers["X-Radicle-Delivery"], delivery.id.as_str());
+        }
+
+        let deliveries = handle.deliveries(&webhook.id).unwrap().unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(deliveries[0].attempts.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn patch_event_is_delivered_once() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let store = kv::Store::new(kv::Config::new(tmp_dir.path())).unwrap();
+        let handle = Handle::new(&store).unwrap();
+        let url = "http://127.0.0.1/hook".parse().unwrap();
+        let (webhook, _) = handle.add(url, None, vec![]).unwrap();
+
+        assert!(handle.mark_event_delivered(&webhook.id, "abc").unwrap());
+        assert!(!handle.mark_event_delivered(&webhook.id, "abc").unwrap());
+        assert!(handle.mark_event_delivered(&webhook.id, "def").unwrap());
+        assert!(handle.mark_event_delivered("other", "abc").unwrap());
+
+        // The record is persisted.
+        let handle = Handle::new(&store).unwrap();
+        assert!(!handle.mark_event_delivered(&webhook.id, "abc").unwrap());
+
+        assert!(handle.remove(&webhook.id).unwrap());
+        assert!(handle.mark_event_delivered(&webhook.id, "abc").unwrap());
+        assert!(!handle.mark_event_delivered("other", "abc").unwrap());
+    }
+}